@@ -1,27 +1,63 @@
 use crate::date::Date;
+use crate::date::recurrence::Recurrence;
+use crate::editor;
 use crate::error::{CodeComponent, Error};
+use crate::index::{Index, IndexEntry};
+use crate::journal;
 use crate::output::Render;
 use crate::output::RenderFormat;
 use crate::output::line::OutputLine;
+use crate::query::Query;
+use crate::registry::Registry;
+use crate::storage;
+use crate::todo::agenda::Agenda;
 use crate::todo::document::Document;
 use crate::todo::item::Item;
-use crate::todo::list::TodoList;
+use crate::todo::list::{List, TodoList};
+use crate::todo::markdown::ListFormat;
 use crate::todo::path::ItemPath;
-use crate::{match_error, match_result, propagate, search_paths};
+use crate::todo::sort::SortSpec;
+use crate::todo::tags::{self, TagFilter};
+use crate::{match_error, match_option, match_result, propagate, search_paths, settings};
 
 use std::fs;
+use std::io::Read;
 use std::path::PathBuf;
 
+/// The formats that `import` can read.
+pub enum ImportFormat {
+    Json,
+    TodoTxt,
+    Ics,
+}
+
+/// The formats that `export` can write.
+pub enum ExportFormat {
+    TodoTxt,
+    Ics,
+}
+
 pub fn init(path: PathBuf) -> Result<(), Error> {
-    let todo_path = path.join(".todo");
-    if fs::exists(&todo_path).unwrap_or(false) {
+    let existing = match_error!(
+        search_paths::list_file(&path),
+        CodeComponent::Executor,
+        format!("Could not check for a list at path '{}'.", path.display())
+    );
+    let todo_path = path.join(
+        existing
+            .clone()
+            .unwrap_or_else(search_paths::default_list_file),
+    );
+    if existing.is_some() {
         println!("[LIST]: '{}' already exists.", todo_path.display());
+    } else if storage::is_dry_run() {
+        storage::print_diff(&todo_path, None, Some("# New Todo\n\n"));
     } else {
         match_result!(
             fs::write(&todo_path, "# New Todo\n\n"),
             CodeComponent::Executor,
             format!(
-                "Could not write file '.todo' at path '{path}'.",
+                "Could not write file at path '{path}'.",
                 path = &todo_path.display(),
             )
         );
@@ -37,31 +73,16 @@ pub fn next(
     show_children: bool,
     down: bool,
     format: RenderFormat,
+    tag_filter: TagFilter,
+    query: Option<Query>,
+    sort: Option<SortSpec>,
 ) -> Result<(), Error> {
-    let paths: Vec<PathBuf>;
-
-    if down {
-        paths = match_error!(
-            search_paths::search_down(&path),
-            CodeComponent::Executor,
-            format!("Could not search down from '{}'.", path.display())
-        );
-    } else {
-        paths = match_error!(
-            search_paths::search_up(&path),
-            CodeComponent::FileSearcher,
-            format!("Could not search up from path '{}'", path.display())
-        );
-    }
-
-    let mut lists = vec![];
-    for path in paths {
-        lists.push(match_error!(
-            Document::from_path(&path),
-            CodeComponent::Executor,
-            format!("Could not parse the document at path '{}'", path.display())
-        ));
-    }
+    // Lists that are archived or empty can't have the next item, so they don't need to be read.
+    let mut lists = match_error!(
+        find_documents(&path, down, |entry| !entry.archived && entry.items > 0),
+        CodeComponent::Executor,
+        format!("Could not read the lists from '{}'.", path.display())
+    );
 
     // Remove archived lists
     lists = lists
@@ -70,13 +91,44 @@ pub fn next(
         .rev()
         .collect::<Vec<Document>>();
 
-    lists.sort_by(|a, b| b.priority.cmp(&a.priority));
-    let mut top_list = lists[0].clone();
+    for list in &mut lists {
+        list.items.recursive_filter(&|item| item.archived);
+        list.items
+            .recursive_filter(&|item| tag_filter.removes(item));
+        if let Some(query) = &query {
+            list.items.recursive_filter(&|item| query.removes(item));
+        }
+        // Only open items can be next, so `--sort` just picks which of them comes first.
+        list.items.retain(|item| !item.completed);
+    }
+    // Lists that don't have anything left can't have the next item.
+    lists.retain(|list| !list.items.is_empty());
+
+    sort_documents(&mut lists, &sort);
+    let top_list = match lists.first() {
+        Some(list) => list.clone(),
+        None => {
+            return Err(propagate!(
+                CodeComponent::Executor,
+                format!("There are no items to pick from.")
+            ));
+        }
+    };
 
-    top_list.items.recursive_filter(|item| item.archived);
-    top_list.items.sort_by(|a, b| b.priority.cmp(&a.priority));
     let top_item = top_list.items[0].clone();
 
+    if let RenderFormat::Json = format {
+        println!(
+            "{}",
+            match_error!(
+                top_item.to_json(),
+                CodeComponent::Executor,
+                format!("Could not convert the item '{}' to JSON.", top_item.name)
+            )
+        );
+        return Ok(());
+    }
+
     let output = match_error!(
         top_item.format_detail(show_children),
         CodeComponent::Executor,
@@ -87,40 +139,28 @@ pub fn next(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn list(
     down: bool,
     format: RenderFormat,
     path: PathBuf,
     show_archived: bool,
     show_completed: bool,
+    tag_filter: TagFilter,
+    query: Option<Query>,
+    sort: Option<SortSpec>,
 ) -> Result<(), Error> {
     let search_start = path;
 
-    let paths: Vec<PathBuf>;
-
-    if down {
-        paths = match_error!(
-            search_paths::search_down(&search_start),
-            CodeComponent::Executor,
-            format!("Could not search down from '{}'.", search_start.display())
-        );
-    } else {
-        paths = match_error!(
-            search_paths::search_up(&search_start),
-            CodeComponent::FileSearcher,
-            format!("Could not search up from path '{}'", search_start.display())
-        );
-    }
-
-    let mut documents = vec![];
-
-    for path in paths {
-        documents.push(match_error!(
-            Document::from_path(&path),
-            CodeComponent::Executor,
-            format!("Could not parse the document at path '{}'", path.display())
-        ));
-    }
+    let mut documents = match_error!(
+        find_documents(&search_start, down, |entry| show_archived
+            || !entry.archived),
+        CodeComponent::Executor,
+        format!(
+            "Could not read the lists from '{}'.",
+            search_start.display()
+        )
+    );
 
     if !show_archived {
         documents = documents
@@ -129,16 +169,47 @@ pub fn list(
             .collect::<Vec<Document>>();
     }
 
-    documents.sort_by(|a, b| b.priority.cmp(&a.priority));
+    sort_documents(&mut documents, &sort);
 
-    for mut document in documents {
+    for document in &mut documents {
         if !show_archived {
-            document.items.recursive_filter(|item| item.archived)
+            document.items.recursive_filter(&|item| item.archived)
         }
         if !show_completed {
-            document.items.recursive_filter(|item| item.completed)
+            document.items.recursive_filter(&|item| item.completed)
+        }
+        document
+            .items
+            .recursive_filter(&|item| tag_filter.removes(item));
+        if let Some(query) = &query {
+            document.items.recursive_filter(&|item| query.removes(item));
         }
+    }
+
+    if let RenderFormat::Json = format {
+        let mut values = vec![];
+        for document in &documents {
+            values.push(match_error!(
+                document.to_json_value(),
+                CodeComponent::Executor,
+                format!(
+                    "Could not convert the document '#{}' to JSON.",
+                    document.name
+                )
+            ));
+        }
+        println!(
+            "{}",
+            match_result!(
+                serde_json::to_string_pretty(&values),
+                CodeComponent::Executor,
+                format!("Could not serialize the documents.")
+            )
+        );
+        return Ok(());
+    }
 
+    for document in documents {
         print!(
             "{}",
             match_error!(
@@ -165,193 +236,304 @@ pub fn add(
     item_name: String,
     date: Option<Date>,
     priority: Option<&i64>,
+    recurrence: Option<Recurrence>,
     down: bool,
 ) -> Result<(), Error> {
-    let mut list = match_error!(
-        search_paths::find_list(&path.document.clone(), down),
-        CodeComponent::Executor,
-        format!("Could not find list with name '#{}'.", path.document)
-    );
-
-    let item = Item {
-        name: item_name,
-        date: date,
-        priority: *priority.unwrap_or(&0_i64),
-        completed: false,
-        archived: false,
-        items: vec![],
-    };
+    storage::with_lock(|| {
+        let mut list = match_error!(
+            search_paths::find_list(&path, down),
+            CodeComponent::Executor,
+            format!("Could not find list with name '#{}'.", path.document)
+        );
 
-    match list.items.add_item(item.clone(), path.clone()) {
-        Ok(()) => {}
-        Err(err) => {
-            return Err(propagate!(
-                CodeComponent::Executor,
-                format!("Could not add item to path {}", path.display()),
-                err
-            ));
-        }
-    };
+        let (item_name, tags) = tags::split_tags(&item_name);
+        let item = Item {
+            name: item_name,
+            date,
+            priority: *priority.unwrap_or(&0_i64),
+            created: Date::today().ok(),
+            recurrence,
+            tags,
+            id: Some(Item::generate_id(&list.items)),
+            completed: false,
+            archived: false,
+            items: vec![],
+            note: None,
+            trailing: vec![],
+            source: None,
+        };
+
+        match list.items.add_item(item.clone(), path.clone()) {
+            Ok(()) => {}
+            Err(err) => {
+                return Err(propagate!(
+                    CodeComponent::Executor,
+                    format!("Could not add item to path {}", path.display()),
+                    err
+                ));
+            }
+        };
 
-    match_error!(
-        list.clone().save(),
-        CodeComponent::Executor,
-        format!("Could not same the document")
-    );
+        match_error!(
+            list.clone().save(),
+            CodeComponent::Executor,
+            format!("Could not same the document")
+        );
 
-    println!(
-        "[LIST]: Added '{item_name}' to #{list_name}",
-        item_name = item.name,
-        list_name = list.name
-    );
+        println!(
+            "[LIST]: {verb} '{item_name}' (@{item_id}) to #{list_name}",
+            verb = done("Added", "Would add"),
+            item_name = item.name,
+            item_id = item.id.clone().unwrap_or_default(),
+            list_name = list.name
+        );
 
-    Ok(())
+        Ok(())
+    })
 }
 
 pub fn complete(path: ItemPath, down: bool) -> Result<(), Error> {
-    let mut list = match_error!(
-        search_paths::find_list(&path.document, down),
-        CodeComponent::Executor,
-        format!(
-            "Could not find a list with the name '{}'",
-            path.document.clone()
-        )
-    );
+    storage::with_lock(|| {
+        let mut list = match_error!(
+            search_paths::find_list(&path, down),
+            CodeComponent::Executor,
+            format!(
+                "Could not find a list with the name '{}'",
+                path.document.clone()
+            )
+        );
 
-    let item = match_error!(
-        list.items.find(&path.clone()),
-        CodeComponent::Executor,
-        format!("Could not find item at path '{}'.", path.display())
-    );
-    item.completed = true;
+        let indices = match_error!(
+            list.items.locate(&path),
+            CodeComponent::Executor,
+            format!("Could not find item at path '{}'.", path.display())
+        );
+        let done = match_error!(
+            list.items.complete_at(&indices),
+            CodeComponent::Executor,
+            format!("Could not complete the item at path '{}'.", path.display())
+        );
 
-    println!(
-        "[LIST]: Completed '{item_name}' in #{list_name}.",
-        item_name = item.name,
-        list_name = list.name
-    );
+        print_completed(&list, &indices, done.is_some());
 
-    match_error!(
-        list.clone().save(),
-        CodeComponent::Executor,
-        format!("Could not same the document")
-    );
+        match_error!(
+            list.clone().save(),
+            CodeComponent::Executor,
+            format!("Could not same the document")
+        );
 
-    Ok(())
+        Ok(())
+    })
+}
+
+/// Tell the user that an item was completed, and when it is due next if it repeats.
+fn print_completed(list: &Document, indices: &[usize], repeated: bool) {
+    if let Some(item) = list.items.item_at(indices) {
+        println!(
+            "[LIST]: {verb} '{item_name}' in #{list_name}.",
+            verb = done("Completed", "Would complete"),
+            item_name = item.name,
+            list_name = list.name
+        );
+
+        if repeated {
+            println!(
+                "[LIST]: '{item_name}' repeats {rule}, so it {is} due again {date}.",
+                is = done("is", "would be"),
+                item_name = item.name,
+                rule = item
+                    .recurrence
+                    .as_ref()
+                    .map(|recurrence| recurrence.display())
+                    .unwrap_or_default(),
+                date = item.date.map(|date| date.pretty()).unwrap_or_default()
+            );
+        }
+    }
+}
+
+/// The verb for a confirmation, which says what would have happened under `--dry-run`, since
+/// nothing was actually changed.
+fn done(verb: &'static str, dry_run: &'static str) -> &'static str {
+    if storage::is_dry_run() { dry_run } else { verb }
 }
 
 pub fn toggle(path: ItemPath, down: bool) -> Result<(), Error> {
-    let mut list = match_error!(
-        search_paths::find_list(&path.document, down),
-        CodeComponent::Executor,
-        format!(
-            "Could not find a list with the name '{}'",
-            path.document.clone()
-        )
-    );
+    storage::with_lock(|| {
+        let mut list = match_error!(
+            search_paths::find_list(&path, down),
+            CodeComponent::Executor,
+            format!(
+                "Could not find a list with the name '{}'",
+                path.document.clone()
+            )
+        );
 
-    let item = match_error!(
-        list.items.find(&path.clone()),
-        CodeComponent::Executor,
-        format!("Could not find item at path '{}'.", path.display())
-    );
-    item.completed = !item.completed;
+        let item = match_error!(
+            list.items.find(&path.clone()),
+            CodeComponent::Executor,
+            format!("Could not find item at path '{}'.", path.display())
+        );
 
-    println!(
-        "[LIST]: Toggled '{item_name}' in #{list_name}.",
-        item_name = item.name,
-        list_name = list.name
-    );
+        if item.completed {
+            item.completed = false;
 
-    match_error!(
-        list.clone().save(),
-        CodeComponent::Executor,
-        format!("Could not same the document")
-    );
+            println!(
+                "[LIST]: {verb} '{item_name}' in #{list_name}.",
+                verb = done("Toggled", "Would toggle"),
+                item_name = item.name,
+                list_name = list.name
+            );
+        } else {
+            let indices = match_error!(
+                list.items.locate(&path),
+                CodeComponent::Executor,
+                format!("Could not find item at path '{}'.", path.display())
+            );
+            let done = match_error!(
+                list.items.complete_at(&indices),
+                CodeComponent::Executor,
+                format!("Could not complete the item at path '{}'.", path.display())
+            );
 
-    Ok(())
+            print_completed(&list, &indices, done.is_some());
+        }
+
+        match_error!(
+            list.clone().save(),
+            CodeComponent::Executor,
+            format!("Could not same the document")
+        );
+
+        Ok(())
+    })
 }
 
 pub fn incomplete(path: ItemPath, down: bool) -> Result<(), Error> {
-    let mut list = match_error!(
-        search_paths::find_list(&path.document, down),
-        CodeComponent::Executor,
-        format!(
-            "Could not find a list with the name '{}'",
-            path.document.clone()
-        )
-    );
+    storage::with_lock(|| {
+        let mut list = match_error!(
+            search_paths::find_list(&path, down),
+            CodeComponent::Executor,
+            format!(
+                "Could not find a list with the name '{}'",
+                path.document.clone()
+            )
+        );
 
-    let item = match_error!(
-        list.items.find(&path.clone()),
-        CodeComponent::Executor,
-        format!("Could not find item at path '{}'.", path.display())
-    );
-    item.completed = false;
+        let item = match_error!(
+            list.items.find(&path.clone()),
+            CodeComponent::Executor,
+            format!("Could not find item at path '{}'.", path.display())
+        );
+        item.completed = false;
 
-    println!(
-        "[LIST]: marked '{item_name}' in #{list_name} as incomplete.",
-        item_name = item.name,
-        list_name = list.name
-    );
+        println!(
+            "[LIST]: {verb} '{item_name}' in #{list_name} as incomplete.",
+            verb = done("marked", "Would mark"),
+            item_name = item.name,
+            list_name = list.name
+        );
 
-    match_error!(
-        list.clone().save(),
-        CodeComponent::Executor,
-        format!("Could not same the document")
-    );
+        match_error!(
+            list.clone().save(),
+            CodeComponent::Executor,
+            format!("Could not same the document")
+        );
 
-    Ok(())
+        Ok(())
+    })
 }
 
 pub fn prune(path: PathBuf, single: bool, down: bool) -> Result<(), Error> {
-    let search_start = path;
+    storage::with_lock(|| {
+        let search_start = path;
 
-    let paths = if single {
-        vec![search_start]
-    } else {
-        if down {
-            match_error!(
-                search_paths::search_down(&search_start),
-                CodeComponent::Executor,
-                format!("Could not search down from '{}'.", search_start.display())
-            )
+        let paths = if single {
+            vec![search_start]
         } else {
+            if down {
+                match_error!(
+                    search_paths::search_down(&search_start),
+                    CodeComponent::Executor,
+                    format!("Could not search down from '{}'.", search_start.display())
+                )
+            } else {
+                match_error!(
+                    search_paths::search_up(&search_start),
+                    CodeComponent::FileSearcher,
+                    format!("Could not search up from path '{}'", search_start.display())
+                )
+            }
+        };
+
+        for path in paths {
+            let mut document = match_error!(
+                Document::from_path(&path),
+                CodeComponent::Executor,
+                format!("Could not parse the document at path '{}'", path.display())
+            );
+            document.items.prune();
+
             match_error!(
-                search_paths::search_up(&search_start),
-                CodeComponent::FileSearcher,
-                format!("Could not search up from path '{}'", search_start.display())
-            )
+                document.clone().save(),
+                CodeComponent::Executor,
+                format!("Could not same the document")
+            );
+
+            println!(
+                "[LIST]: {verb} #{list_name} at '{list_path}'",
+                verb = done("Pruned", "Would prune"),
+                list_name = document.name,
+                list_path = document.path.display()
+            );
         }
-    };
 
-    for path in paths {
-        let mut document = match_error!(
-            Document::from_path(&path),
+        Ok(())
+    })
+}
+
+pub fn remove(path: ItemPath, down: bool) -> Result<(), Error> {
+    storage::with_lock(|| {
+        let mut list = match_error!(
+            search_paths::find_list(&path, down),
             CodeComponent::Executor,
-            format!("Could not parse the document at path '{}'", path.display())
+            format!(
+                "Could not find a list with the name '{}'",
+                path.document.clone()
+            )
         );
-        document.items.prune();
 
-        match_error!(
-            document.clone().save(),
+        let item = match_error!(
+            list.remove_item(&path),
             CodeComponent::Executor,
-            format!("Could not same the document")
+            format!("Could not remove the item at path '{}'.", path.display())
         );
 
         println!(
-            "[LIST]: Pruned #{list_name} at '{list_path}'",
-            list_name = document.name,
-            list_path = document.path.display()
+            "[LIST]: {verb} '{item_name}' in #{list_name}.",
+            verb = done("Removed", "Would remove"),
+            item_name = item.name,
+            list_name = list.name
         );
-    }
 
-    Ok(())
+        match_error!(
+            list.clone().save(),
+            CodeComponent::Executor,
+            format!("Could not same the document")
+        );
+        Ok(())
+    })
 }
 
-pub fn remove(path: ItemPath, down: bool) -> Result<(), Error> {
+pub fn get(
+    path: ItemPath,
+    format: RenderFormat,
+    down: bool,
+    tag_filter: TagFilter,
+    sort: Option<SortSpec>,
+) -> Result<(), Error> {
     let mut list = match_error!(
-        search_paths::find_list(&path.document, down),
+        search_paths::find_list(&path, down),
         CodeComponent::Executor,
         format!(
             "Could not find a list with the name '{}'",
@@ -359,37 +541,43 @@ pub fn remove(path: ItemPath, down: bool) -> Result<(), Error> {
         )
     );
 
-    let item = match_error!(
-        list.items.remove_by_path(&path),
-        CodeComponent::Executor,
-        format!("Could not remove the item at path '{}'.", path.display())
-    );
+    sort.or(list.sort.clone())
+        .unwrap_or_default()
+        .sort_items(&mut list.items);
 
-    println!(
-        "[LIST]: Removed '{item_name}' in #{list_name}.",
-        item_name = item.name,
-        list_name = list.name
-    );
+    if path.segments.is_empty() {
+        list.items
+            .recursive_filter(&|item| tag_filter.removes(item));
+    } else if !tag_filter.is_empty() {
+        let item = match_error!(
+            list.items.find(&path.clone()),
+            CodeComponent::Executor,
+            format!("Could not find item at path '{}'", path.display())
+        );
+        item.items
+            .recursive_filter(&|item| tag_filter.removes(item));
+    }
 
-    match_error!(
-        list.clone().save(),
-        CodeComponent::Executor,
-        format!("Could not same the document")
-    );
-    Ok(())
-}
-
-pub fn get(path: ItemPath, format: RenderFormat, down: bool) -> Result<(), Error> {
-    let mut list = match_error!(
-        search_paths::find_list(&path.document, down),
-        CodeComponent::Executor,
-        format!(
-            "Could not find a list with the name '{}'",
-            path.document.clone()
-        )
-    );
-
-    if path.item_prefixes.len() == 0 {
+    if let RenderFormat::Json = format {
+        let json = if path.segments.is_empty() {
+            list.to_json()
+        } else {
+            match_error!(
+                list.items.find(&path.clone()),
+                CodeComponent::Executor,
+                format!("Could not find item at path '{}'", path.display())
+            )
+            .to_json()
+        };
+        println!(
+            "{}",
+            match_error!(
+                json,
+                CodeComponent::Executor,
+                format!("Could not convert '{}' to JSON.", path.display())
+            )
+        );
+    } else if path.segments.is_empty() {
         println!(
             "{}",
             match_error!(
@@ -420,124 +608,1007 @@ pub fn get(path: ItemPath, format: RenderFormat, down: bool) -> Result<(), Error
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn edit(
     path: ItemPath,
     down: bool,
     name: Option<&String>,
     date: Option<Date>,
     priority: Option<&i64>,
+    recurrence: Option<Option<Recurrence>>,
     completed: Option<&bool>,
     archived: Option<&bool>,
+    note: Option<&String>,
+    edit_note: bool,
 ) -> Result<(), Error> {
-    let mut list = match_error!(
-        search_paths::find_list(&path.document, down),
+    // `$EDITOR` can stay open for a long time, so the note is edited without holding the lock. The
+    // list is only saved if its file is still what it was when the note was read.
+    let edited_note = if edit_note {
+        let mut list = match_error!(
+            search_paths::find_list(&path, down),
+            CodeComponent::Executor,
+            format!(
+                "Could not find a list with the name '{}'",
+                path.document.clone()
+            )
+        );
+        let item = match_error!(
+            list.items.find(&path.clone()),
+            CodeComponent::Executor,
+            format!("Could not find item at path '{}'.", path.display())
+        );
+        let text = match_error!(
+            editor::edit_text(&item.note.clone().unwrap_or_default(), "note.txt"),
+            CodeComponent::Executor,
+            format!("Could not edit the note of '{}'.", item.name)
+        );
+        Some((text, list.loaded))
+    } else {
+        None
+    };
+
+    storage::with_lock(|| {
+        let mut list = match_error!(
+            search_paths::find_list(&path, down),
+            CodeComponent::Executor,
+            format!(
+                "Could not find a list with the name '{}'",
+                path.document.clone()
+            )
+        );
+        if let Some((_, loaded)) = &edited_note
+            && list.loaded != *loaded
+        {
+            return Err(propagate!(
+                CodeComponent::Executor,
+                format!(
+                    "'{}' was changed by something else while the note was being edited, so it wasn't saved. Try again.",
+                    list.file_path().display()
+                )
+            ));
+        }
+
+        let item = match_error!(
+            list.items.find(&path.clone()),
+            CodeComponent::Executor,
+            format!("Could not find item at path '{}'.", path.display())
+        );
+
+        if let Some(name) = name {
+            (item.name, item.tags) = tags::split_tags(name);
+            println!("Set name to '{}'", name);
+        }
+        if let Some(date) = date {
+            item.date = Some(date);
+            println!("Set date to '{}'", date.pretty());
+        }
+        if let Some(priority) = priority {
+            item.priority = *priority;
+            println!("Set name to '{}'", priority);
+        }
+        if let Some(recurrence) = recurrence {
+            match &recurrence {
+                Some(rule) => println!("Set it to repeat {}", rule.display()),
+                None => println!("Set it to not repeat"),
+            }
+            item.recurrence = recurrence;
+        }
+        if let Some(completed) = completed {
+            item.completed = *completed;
+            println!("Set name to '{}'", completed);
+        }
+        if let Some(archived) = archived {
+            item.archived = *archived;
+            println!("Set name to '{}'", archived);
+        }
+        if let Some(note) = note {
+            item.set_note(note);
+            println!("Set the note");
+        }
+        if let Some((text, _)) = &edited_note {
+            item.set_note(text);
+            println!("Set the note");
+        }
+
+        println!("\nNew Item Values:");
+
+        let output = match_error!(
+            item.format_detail(false),
+            CodeComponent::Executor,
+            format!("Could not render output.")
+        );
+        println!("{}", output.render(&RenderFormat::ANSI));
+
+        match_error!(
+            list.clone().save(),
+            CodeComponent::Executor,
+            format!("Could not same the document")
+        );
+
+        Ok(())
+    })
+}
+
+pub fn undo(force: bool) -> Result<(), Error> {
+    storage::with_lock(|| {
+        match match_error!(
+            journal::undo(force),
+            CodeComponent::Executor,
+            format!("Could not undo the last change.")
+        ) {
+            Some(transaction) => println!(
+                "[LIST]: {} '{}' ({}).",
+                done("Undid", "Would undo"),
+                transaction.command,
+                describe_files(&transaction)
+            ),
+            None => println!("[LIST]: There is nothing to undo."),
+        }
+
+        Ok(())
+    })
+}
+
+pub fn redo(force: bool) -> Result<(), Error> {
+    storage::with_lock(|| {
+        match match_error!(
+            journal::redo(force),
+            CodeComponent::Executor,
+            format!("Could not redo the last change.")
+        ) {
+            Some(transaction) => println!(
+                "[LIST]: {} '{}' ({}).",
+                done("Redid", "Would redo"),
+                transaction.command,
+                describe_files(&transaction)
+            ),
+            None => println!("[LIST]: There is nothing to redo."),
+        }
+
+        Ok(())
+    })
+}
+
+pub fn history(limit: usize) -> Result<(), Error> {
+    let (done, undone) = match_error!(
+        journal::history(),
         CodeComponent::Executor,
-        format!(
-            "Could not find a list with the name '{}'",
-            path.document.clone()
-        )
+        format!("Could not read the history.")
     );
 
-    let item = match_error!(
-        list.items.find(&path.clone()),
+    if done.is_empty() && undone.is_empty() {
+        println!("[LIST]: Nothing has been changed yet.");
+        return Ok(());
+    }
+
+    // Newest first, starting with the changes that could be redone.
+    let transactions = undone
+        .iter()
+        .map(|transaction| (transaction, true))
+        .chain(done.iter().rev().map(|transaction| (transaction, false)))
+        .take(limit);
+    for (transaction, is_undone) in transactions {
+        println!(
+            "{}  {}  ({}){}",
+            transaction.time,
+            transaction.command,
+            describe_files(transaction),
+            if is_undone { " [undone]" } else { "" }
+        );
+    }
+
+    Ok(())
+}
+
+fn describe_files(transaction: &journal::Transaction) -> String {
+    match &transaction.files[..] {
+        [(path, _, _)] => path.display().to_string(),
+        files => format!("{} lists", files.len()),
+    }
+}
+
+pub fn register(path: PathBuf) -> Result<(), Error> {
+    storage::with_lock(|| {
+        let mut registry = match_error!(
+            Registry::load(),
+            CodeComponent::Executor,
+            format!("Could not load the registered lists.")
+        );
+        let name = match_error!(
+            registry.register(&path),
+            CodeComponent::Executor,
+            format!("Could not register the list in '{}'.", path.display())
+        );
+        match_error!(
+            registry.save(),
+            CodeComponent::Executor,
+            format!("Could not save the registered lists.")
+        );
+
+        println!(
+            "[LIST]: {verb} #{name} at '{path}'.",
+            verb = done("Registered", "Would register"),
+            path = path.display()
+        );
+        Ok(())
+    })
+}
+
+/// Unregisters a list, given as `#name` or as its directory.
+pub fn unregister(list: String) -> Result<(), Error> {
+    storage::with_lock(|| {
+        let mut registry = match_error!(
+            Registry::load(),
+            CodeComponent::Executor,
+            format!("Could not load the registered lists.")
+        );
+
+        let path = if list.starts_with("#") {
+            let item_path = match_error!(
+                ItemPath::try_from(&list),
+                CodeComponent::Executor,
+                format!("Could not parse the list '{}'.", list)
+            );
+            match match_error!(
+                registry.find(&item_path),
+                CodeComponent::Executor,
+                format!("Could not look for '{}' in the registered lists.", list)
+            ) {
+                Some(path) => path,
+                None => {
+                    return Err(propagate!(
+                        CodeComponent::Executor,
+                        format!("No registered list is called '{}'.", list)
+                    ));
+                }
+            }
+        } else {
+            PathBuf::from(&list)
+        };
+
+        if !registry.unregister(&path) {
+            return Err(propagate!(
+                CodeComponent::Executor,
+                format!("'{}' isn't registered.", path.display())
+            ));
+        }
+        match_error!(
+            registry.save(),
+            CodeComponent::Executor,
+            format!("Could not save the registered lists.")
+        );
+
+        println!(
+            "[LIST]: {} '{}'.",
+            done("Unregistered", "Would unregister"),
+            path.display()
+        );
+        Ok(())
+    })
+}
+
+pub fn lists() -> Result<(), Error> {
+    let registry = match_error!(
+        Registry::load(),
         CodeComponent::Executor,
-        format!("Could not find item at path '{}'.", path.display())
+        format!("Could not load the registered lists.")
+    );
+    let lists = match_error!(
+        registry.lists(),
+        CodeComponent::Executor,
+        format!("Could not read the registered lists.")
     );
 
-    if let Some(name) = name {
-        item.name = name.clone();
-        println!("Set name to '{}'", name);
+    if lists.is_empty() {
+        println!("[LIST]: No lists have been registered yet.");
+        return Ok(());
     }
-    if let Some(date) = date {
-        item.date = Some(date);
-        println!("Set date to '{}'", date.display());
+
+    for (path, entry) in lists {
+        match entry {
+            Some(IndexEntry {
+                name,
+                alias: Some(alias),
+                ..
+            }) => println!("#{name} ({alias})  {}", path.display()),
+            Some(IndexEntry { name, .. }) => println!("#{name}  {}", path.display()),
+            None => println!("(missing)  {}", path.display()),
+        }
     }
-    if let Some(priority) = priority {
-        item.priority = *priority;
-        println!("Set name to '{}'", priority);
+
+    Ok(())
+}
+
+/// Shows every problem in the lists, like a compiler would. It fails if any of them are errors, so
+/// that it can be used in scripts.
+pub fn check(down: bool, format: RenderFormat, path: PathBuf) -> Result<(), Error> {
+    let documents = match_error!(
+        read_documents(&path, down, |_| true),
+        CodeComponent::Executor,
+        format!("Could not read the lists from '{}'.", path.display())
+    );
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    for document in &documents {
+        let todo_path = document.file_path();
+        for diagnostic in &document.diagnostics {
+            if diagnostic.is_error() {
+                errors += 1;
+            } else {
+                warnings += 1;
+            }
+            print!("{}", diagnostic.format(&todo_path).render(&format));
+            print!("{}", OutputLine::newline(&format));
+            print!("{}", OutputLine::newline(&format));
+        }
     }
-    if let Some(completed) = completed {
-        item.completed = *completed;
-        println!("Set name to '{}'", completed);
+
+    println!(
+        "[LIST]: Checked {count} list{s}: {errors} error{error_s} and {warnings} warning{warning_s}.",
+        count = documents.len(),
+        s = if documents.len() == 1 { "" } else { "s" },
+        error_s = if errors == 1 { "" } else { "s" },
+        warning_s = if warnings == 1 { "" } else { "s" },
+    );
+
+    if errors > 0 {
+        return Err(propagate!(
+            CodeComponent::Executor,
+            format!("Some of the lists have errors.")
+        ));
     }
-    if let Some(archived) = archived {
-        item.archived = *archived;
-        println!("Set name to '{}'", archived);
+    Ok(())
+}
+
+pub fn index_rebuild(path: PathBuf) -> Result<(), Error> {
+    let mut index = Index::load();
+    let count = match_error!(
+        index.rebuild(&path),
+        CodeComponent::Executor,
+        format!("Could not rebuild the index for '{}'.", path.display())
+    );
+    match_error!(
+        index.save(),
+        CodeComponent::Executor,
+        format!("Could not save the index.")
+    );
+
+    println!(
+        "[LIST]: Indexed {count} list{s} under '{path}'.",
+        s = if count == 1 { "" } else { "s" },
+        path = path.display()
+    );
+    Ok(())
+}
+
+/// Opens a list (or just one item and its children) in `$EDITOR`. Without a path, the list's file
+/// in the current directory is opened as it is, so that it can be fixed even if it can't be read.
+pub fn open(path: Option<ItemPath>, down: bool) -> Result<(), Error> {
+    let Some(path) = path else {
+        let directory = match_result!(
+            std::env::current_dir(),
+            CodeComponent::Executor,
+            format!("Couldn't read your current directory.")
+        );
+        let file_name = match_error!(
+            search_paths::list_file(&directory),
+            CodeComponent::Executor,
+            format!("Could not check for a list in '{}'.", directory.display())
+        )
+        .unwrap_or_else(search_paths::default_list_file);
+        let todo_path = directory.join(&file_name);
+        let content = match_result!(
+            fs::read_to_string(&todo_path),
+            CodeComponent::Executor,
+            format!("Could not read '{}'. Try `todo init`.", todo_path.display())
+        );
+
+        let document = match_error!(
+            edit_until_valid(
+                &content,
+                &format!("list.{}", ListFormat::of(&file_name).extension()),
+                |text| Document::from(text.to_string(), directory.clone(), file_name.clone())
+            ),
+            CodeComponent::Executor,
+            format!("Could not edit '{}'.", todo_path.display())
+        );
+        return save_opened(document, Some(content));
+    };
+
+    let mut list = match_error!(
+        search_paths::find_list(&path, down),
+        CodeComponent::Executor,
+        format!("Could not find a list with the name '{}'", path.document)
+    );
+
+    if path.segments.is_empty() {
+        let document = match_error!(
+            edit_until_valid(
+                &list.to_string(),
+                &format!("list.{}", list.list_format().extension()),
+                |text| Document::from(text.to_string(), list.path.clone(), list.file_name.clone())
+            ),
+            CodeComponent::Executor,
+            format!("Could not edit '#{}'.", list.name)
+        );
+        return save_opened(document, list.loaded);
     }
 
-    println!("\nNew Item Values:");
+    let indices = match_error!(
+        list.items.locate(&path),
+        CodeComponent::Executor,
+        format!("Could not find the item at path '{}'.", path.display())
+    );
+    let item = match_option!(
+        list.items.item_at(&indices),
+        CodeComponent::Executor,
+        format!("Could not get the item at path '{}'.", path.display())
+    );
 
-    let output = match_error!(
-        item.format_detail(false),
+    let items = match_error!(
+        edit_until_valid(
+            &item.to_string(0, list.list_format()),
+            &format!("item.{}", list.list_format().extension()),
+            |text| {
+                let items = List::parse(text.to_string(), list.list_format())?;
+                if items.is_empty() {
+                    return Err(propagate!(
+                        CodeComponent::Executor,
+                        format!(
+                            "There aren't any items left. Use `todo remove` to remove an item."
+                        )
+                    ));
+                }
+                if let Some(line) = text.lines().find(|line| !line.trim().is_empty())
+                    && !Item::is_item_line(line)
+                {
+                    return Err(propagate!(
+                        CodeComponent::Executor,
+                        format!("The first line has to be an item, but it is '{line}'.")
+                    ));
+                }
+                Ok(items)
+            }
+        ),
         CodeComponent::Executor,
-        format!("Could not render output.")
+        format!("Could not edit '{}'.", path.display())
     );
-    println!("{}", output.render(&RenderFormat::ANSI));
+    let Some(items) = items else {
+        println!("[LIST]: Nothing was changed.");
+        return Ok(());
+    };
+
+    if let Some((last, parent)) = indices.split_last()
+        && let Some(siblings) = list.items.children_at_mut(parent)
+    {
+        siblings.splice(*last..*last + 1, items);
+    }
+
+    let loaded = list.loaded.clone();
+    save_opened(Some(list), loaded)
+}
+
+/// Saves a list that was opened in the editor, as long as the file hasn't changed since then.
+fn save_opened(document: Option<Document>, loaded: Option<String>) -> Result<(), Error> {
+    let Some(mut document) = document else {
+        println!("[LIST]: Nothing was changed.");
+        return Ok(());
+    };
+    document.loaded = loaded;
 
     match_error!(
-        list.clone().save(),
+        document.save(),
         CodeComponent::Executor,
-        format!("Could not same the document")
+        format!("Could not save '#{}'.", document.name)
+    );
+    println!(
+        "[LIST]: {} #{}.",
+        done("Saved", "Would save"),
+        document.name
     );
 
     Ok(())
 }
 
+/// Opens some text in the editor until `parse` accepts it. If it doesn't, the problem is shown at
+/// the top of the file on lines starting with "#!", which are taken out again before parsing.
+/// Nothing is returned if the text wasn't changed.
+fn edit_until_valid<T>(
+    text: &str,
+    name: &str,
+    parse: impl Fn(&str) -> Result<T, Error>,
+) -> Result<Option<T>, Error> {
+    let mut shown = text.to_string();
+
+    loop {
+        let edited = match_error!(
+            editor::edit_text(&shown, name),
+            CodeComponent::Executor,
+            format!("Could not open the editor.")
+        );
+        let unchanged = edited == shown;
+        let edited = edited
+            .lines()
+            .skip_while(|line| line.starts_with("#!"))
+            .collect::<Vec<&str>>()
+            .join("\n")
+            + if edited.ends_with("\n") { "\n" } else { "" };
+
+        if edited == text {
+            return Ok(None);
+        }
+
+        match parse(&edited) {
+            Ok(value) => return Ok(Some(value)),
+            Err(err) if unchanged => {
+                return Err(propagate!(
+                    CodeComponent::Executor,
+                    format!("The problem wasn't fixed, so nothing was saved."),
+                    err
+                ));
+            }
+            Err(err) => {
+                let problem = err
+                    .format(0)
+                    .replace("\u{001b}[2m", "")
+                    .replace("\u{001b}[0m", "");
+                shown = "#! This couldn't be read, so it hasn't been saved yet. Fix it, or close the editor\n\
+                         #! without changing anything to give up:\n"
+                    .to_string();
+                for line in problem.lines() {
+                    shown += &format!("#!   {line}\n");
+                }
+                shown += &edited;
+            }
+        }
+    }
+}
+
 pub fn move_item(
     from_path: ItemPath,
     down1: bool,
     to_path: ItemPath,
     down2: bool,
 ) -> Result<(), Error> {
-    println!("Moving {} -> {}", from_path.display(), to_path.display());
-    if from_path == to_path {
-        // No-op
-        return Ok(());
-    }
-    let mut list1 = match_error!(
-        search_paths::find_list(&from_path.document, down1),
-        CodeComponent::Executor,
-        format!(
-            "Could not find a list named '{}'",
-            from_path.document.clone()
-        )
-    );
-    let item1 = match_error!(
-        list1.items.remove_by_path(&from_path),
-        CodeComponent::Executor,
-        format!("Could not remove item at path '{}'.", from_path.display())
-    );
+    storage::with_lock(|| {
+        println!(
+            "{} {} -> {}",
+            done("Moving", "Would move"),
+            from_path.display(),
+            to_path.display()
+        );
+        if from_path == to_path {
+            // No-op
+            return Ok(());
+        }
+        let mut list1 = match_error!(
+            search_paths::find_list(&from_path, down1),
+            CodeComponent::Executor,
+            format!(
+                "Could not find a list named '{}'",
+                from_path.document.clone()
+            )
+        );
+        let item1 = match_error!(
+            list1.remove_item(&from_path),
+            CodeComponent::Executor,
+            format!("Could not remove item at path '{}'.", from_path.display())
+        );
 
-    let mut list2 = match_error!(
-        search_paths::find_list(&to_path.document, down2),
-        CodeComponent::Executor,
-        format!("Could not find list with name '#{}'.", to_path.document)
-    );
+        let mut list2 = match_error!(
+            search_paths::find_list(&to_path, down2),
+            CodeComponent::Executor,
+            format!("Could not find list with name '#{}'.", to_path.document)
+        );
 
-    let add_item_result = list2.items.add_item(item1.clone(), to_path.clone());
-    match add_item_result {
-        Ok(_) => {
+        // Moving within one list has to change a single copy of it, or saving the second copy would
+        // put the item back where it came from.
+        if list1.path == list2.path {
             match_error!(
-                list1.clone().save(),
+                list1.items.add_item(item1, to_path.clone()),
                 CodeComponent::Executor,
-                format!("Could not same the target document")
+                format!(
+                    "Could not add target item '{target}' to destination '{dest}'",
+                    target = from_path.display(),
+                    dest = to_path.display()
+                )
             );
             match_error!(
-                list2.clone().save(),
+                list1.save(),
                 CodeComponent::Executor,
-                format!("Could not same the destination document")
+                format!("Could not save the document")
             );
+            return Ok(());
+        }
 
-            Ok(())
+        let add_item_result = list2.items.add_item(item1.clone(), to_path.clone());
+        match add_item_result {
+            Ok(_) => {
+                // Both lists are checked before either is saved, so that the item isn't taken out of
+                // one when it can't be put in the other.
+                for list in [&list1, &list2] {
+                    match_error!(
+                        list.check_saveable(),
+                        CodeComponent::Executor,
+                        format!(
+                            "Could not move the item, because '#{}' can't be saved.",
+                            list.name
+                        )
+                    );
+                }
+                match_error!(
+                    list1.clone().save(),
+                    CodeComponent::Executor,
+                    format!("Could not same the target document")
+                );
+                match_error!(
+                    list2.clone().save(),
+                    CodeComponent::Executor,
+                    format!("Could not same the destination document")
+                );
+
+                Ok(())
+            }
+            Err(err) => Err(propagate!(
+                CodeComponent::Executor,
+                format!(
+                    "Could not add target item '{target}' to destination '{dest}'",
+                    target = from_path.display(),
+                    dest = to_path.display()
+                ),
+                err
+            )),
         }
-        Err(err) => Err(propagate!(
+    })
+}
+
+pub fn tui(path: PathBuf, down: bool) -> Result<(), Error> {
+    let mut documents = match_error!(
+        load_documents(&path, down),
+        CodeComponent::Executor,
+        format!("Could not load the todo lists from '{}'.", path.display())
+    );
+
+    if documents.is_empty() {
+        return Err(propagate!(
             CodeComponent::Executor,
             format!(
-                "Could not add target item '{target}' to destination '{dest}'",
-                target = from_path.display(),
-                dest = to_path.display()
+                "There aren't any todo lists to edit from '{}'. Try `todo init`.",
+                path.display()
+            )
+        ));
+    }
+
+    documents.sort_by_key(|document| std::cmp::Reverse(document.priority));
+
+    match_error!(
+        crate::tui::run(documents),
+        CodeComponent::Executor,
+        format!("The editor ran into a problem.")
+    );
+
+    Ok(())
+}
+
+pub fn import(
+    input: Option<PathBuf>,
+    format: ImportFormat,
+    output: PathBuf,
+    force: bool,
+) -> Result<(), Error> {
+    storage::with_lock(|| {
+        let content = match input {
+            Some(input) if input.as_os_str() != "-" => match_result!(
+                fs::read_to_string(&input),
+                CodeComponent::Executor,
+                format!("Could not read the file '{}'.", input.display())
+            ),
+            _ => {
+                let mut content = String::new();
+                match_result!(
+                    std::io::stdin().read_to_string(&mut content),
+                    CodeComponent::Executor,
+                    format!("Could not read from stdin.")
+                );
+                content
+            }
+        };
+
+        // An existing list is replaced in the same file, even if new lists are made in another one.
+        let existing = match_error!(
+            search_paths::list_file(&output),
+            CodeComponent::Executor,
+            format!("Could not check for a list in '{}'.", output.display())
+        );
+        let file_name = existing
+            .clone()
+            .unwrap_or_else(search_paths::default_list_file);
+        let todo_path = output.join(&file_name);
+        if !force && existing.is_some() {
+            return Err(propagate!(
+                CodeComponent::Executor,
+                format!(
+                    "'{}' already exists. Use --force to overwrite it.",
+                    todo_path.display()
+                )
+            ));
+        }
+
+        let mut document = match format {
+            ImportFormat::Json => match_error!(
+                Document::from_json(&content, output.clone()),
+                CodeComponent::Executor,
+                format!("Could not import the JSON document.")
+            ),
+            ImportFormat::TodoTxt => match_error!(
+                Document::from_todotxt(&content, output.clone()),
+                CodeComponent::Executor,
+                format!("Could not import the todo.txt file.")
+            ),
+            ImportFormat::Ics => match_error!(
+                Document::from_ics(&content, output.clone()),
+                CodeComponent::Executor,
+                format!("Could not import the iCalendar file.")
             ),
-            err
-        )),
+        };
+        document.file_name = file_name;
+
+        match_error!(
+            document.save(),
+            CodeComponent::Executor,
+            format!("Could not save the imported document.")
+        );
+
+        println!(
+            "[LIST]: {verb} #{list_name} to '{list_path}'.",
+            verb = done("Imported", "Would import"),
+            list_name = document.name,
+            list_path = todo_path.display()
+        );
+
+        Ok(())
+    })
+}
+
+/// Writes a list (or just one item and its children) to stdout in another format. Without a path,
+/// it is the list in the current directory.
+pub fn export(path: Option<ItemPath>, format: ExportFormat, down: bool) -> Result<(), Error> {
+    let path = match path {
+        Some(path) => path,
+        None => match_error!(
+            ItemPath::new(None, None, vec![]),
+            CodeComponent::Executor,
+            format!("Could not find the list in your current directory.")
+        ),
+    };
+    let list = match_error!(
+        search_paths::find_list(&path, down),
+        CodeComponent::Executor,
+        format!("Could not find a list with the name '{}'", path.document)
+    );
+    // Lines that couldn't be read would be left out.
+    if list
+        .diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.is_error())
+    {
+        return Err(propagate!(
+            CodeComponent::Executor,
+            format!(
+                "Some of '{}' couldn't be read, so it wasn't exported. Run `todo check` to see what is wrong.",
+                list.file_path().display()
+            )
+        ));
+    }
+
+    let indices = match_error!(
+        list.items.locate(&path),
+        CodeComponent::Executor,
+        format!("Could not find the item at path '{}'.", path.display())
+    );
+
+    let output = match format {
+        ExportFormat::TodoTxt => match_error!(
+            list.to_todotxt(&indices),
+            CodeComponent::Executor,
+            format!("Could not export '#{}' as todo.txt.", list.name)
+        ),
+        ExportFormat::Ics => match_error!(
+            list.to_ics(&indices),
+            CodeComponent::Executor,
+            format!("Could not export '#{}' as iCalendar.", list.name)
+        ),
+    };
+    print!("{output}");
+
+    Ok(())
+}
+
+pub fn agenda(
+    path: PathBuf,
+    down: bool,
+    format: RenderFormat,
+    tag_filter: TagFilter,
+    query: Option<Query>,
+    sort: Option<SortSpec>,
+) -> Result<(), Error> {
+    let mut documents = match_error!(
+        find_documents(&path, down, |entry| !entry.archived),
+        CodeComponent::Executor,
+        format!("Could not load the todo lists from '{}'.", path.display())
+    );
+
+    documents.retain(|document| !document.archived);
+    sort_documents(&mut documents, &sort);
+
+    for document in &mut documents {
+        document
+            .items
+            .recursive_filter(&|item| tag_filter.removes(item));
+        if let Some(query) = &query {
+            // Parents are only kept for context, so they are checked again when flattening.
+            document.items.recursive_filter(&|item| query.removes(item));
+        }
+    }
+
+    let mut agenda = match_error!(
+        Agenda::from_documents(&documents),
+        CodeComponent::Executor,
+        format!("Could not build the agenda.")
+    );
+    if let Some(query) = &query {
+        for (_, entries) in &mut agenda.groups {
+            entries.retain(|entry| query.matches(&entry.item));
+        }
+    }
+    // Without a sort order, the items in each group stay soonest first.
+    if let Some(sort) = &sort {
+        for (_, entries) in &mut agenda.groups {
+            sort.sort_entries(entries, |entry| &entry.item);
+        }
+    }
+
+    if let RenderFormat::Json = format {
+        println!(
+            "{}",
+            match_error!(
+                agenda.to_json(),
+                CodeComponent::Executor,
+                format!("Could not convert the agenda to JSON.")
+            )
+        );
+        return Ok(());
+    }
+
+    println!("{}", agenda.format().render(&format));
+
+    Ok(())
+}
+
+/// Find and parse every document from a starting path, either searching up or down.
+fn load_documents(path: &PathBuf, down: bool) -> Result<Vec<Document>, Error> {
+    let paths = if down {
+        match_error!(
+            search_paths::search_down(path),
+            CodeComponent::Executor,
+            format!("Could not search down from '{}'.", path.display())
+        )
+    } else {
+        match_error!(
+            search_paths::search_up(path),
+            CodeComponent::FileSearcher,
+            format!("Could not search up from path '{}'", path.display())
+        )
+    };
+
+    let mut documents = vec![];
+    for path in paths {
+        documents.push(match_error!(
+            Document::from_path(&path),
+            CodeComponent::Executor,
+            format!("Could not parse the document at path '{}'", path.display())
+        ));
+    }
+
+    Ok(documents)
+}
+
+/// Finds and reads the lists above or below a path. When searching down, the index is used to leave
+/// out the lists that `needed` says aren't, without reading them.
+fn read_documents(
+    path: &PathBuf,
+    down: bool,
+    needed: impl Fn(&IndexEntry) -> bool,
+) -> Result<Vec<Document>, Error> {
+    if !down {
+        let paths = match_error!(
+            search_paths::search_up(path),
+            CodeComponent::FileSearcher,
+            format!("Could not search up from path '{}'", path.display())
+        );
+        return Ok(match_error!(
+            Document::from_paths(&paths),
+            CodeComponent::Executor,
+            format!("Could not read the lists.")
+        ));
+    }
+
+    let paths = match_error!(
+        search_paths::search_down(path),
+        CodeComponent::Executor,
+        format!("Could not search down from '{}'.", path.display())
+    );
+    let mut index = Index::load();
+    let entries = match_error!(
+        index.read(&paths),
+        CodeComponent::Executor,
+        format!("Could not check the lists under '{}'.", path.display())
+    );
+    // It is only a cache, so it doesn't matter if it can't be saved.
+    let _ = index.save();
+
+    // The lists that changed have already been read, so only the rest of the needed ones are.
+    let entries = paths
+        .into_iter()
+        .zip(entries)
+        .filter(|(_, (entry, _))| needed(entry))
+        .collect::<Vec<_>>();
+    let unread = entries
+        .iter()
+        .filter(|(_, (_, document))| document.is_none())
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<PathBuf>>();
+    let mut read = match_error!(
+        Document::from_paths(&unread),
+        CodeComponent::Executor,
+        format!("Could not read the lists.")
+    )
+    .into_iter();
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|(_, (_, document))| document.or_else(|| read.next()))
+        .collect())
+}
+
+/// Like `read_documents`, but lists with errors are still shown even though the lines with errors
+/// are missing from them, so this says so.
+fn find_documents(
+    path: &PathBuf,
+    down: bool,
+    needed: impl Fn(&IndexEntry) -> bool,
+) -> Result<Vec<Document>, Error> {
+    let documents = match_error!(
+        read_documents(path, down, needed),
+        CodeComponent::Executor,
+        format!("Could not read the lists.")
+    );
+    for document in &documents {
+        if document
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.is_error())
+        {
+            eprintln!(
+                "[LIST]: '{}' has problems, so some of it might be missing. Run `todo check` to see them. It can't be saved until they are fixed.",
+                document.file_path().display()
+            );
+        }
+    }
+    Ok(documents)
+}
+
+/// Sorts the documents, and then the items in each of them. A sort order from the command line
+/// is used for everything, and otherwise each list uses its own `# sort` line (or the config's).
+fn sort_documents(documents: &mut Vec<Document>, sort: &Option<SortSpec>) {
+    let default = &settings::get().sort;
+    sort.as_ref().unwrap_or(default).sort_documents(documents);
+
+    for document in documents {
+        sort.as_ref()
+            .or(document.sort.as_ref())
+            .unwrap_or(default)
+            .sort_items(&mut document.items);
     }
 }
@@ -3,10 +3,9 @@ use clap::{ArgAction, Command, arg, value_parser};
 
 macro_rules! output_format {
      () => {
-        arg!(-f --format "Set the output format.")
+        arg!(-f --format "Set the output format (by default, the one in the config, or ansi).")
             .action(ArgAction::Set)
             .value_name("FORMAT")
-            .default_value("ansi")
             .value_parser([
                 PossibleValue::new("ansi").help("Use terminal escape codes (default)."),
                 PossibleValue::new("plain").help("Use plaintext."),
@@ -15,6 +14,7 @@ macro_rules! output_format {
                     "Use HTML, with no porovided styles (bring your own colors).",
                 ),
                 PossibleValue::new("pango").help("Use Pango markup (eg. for waybar)."),
+                PossibleValue::new("json").help("Use JSON (for scripts)."),
             ])
      };
 }
@@ -33,10 +33,40 @@ macro_rules! file_path {
     };
 }
 
+macro_rules! tag_filter {
+    () => {
+        [
+            arg!(-t --tag <TAG> "Only show items with this tag (e.g. +work or @home). Can be used more than once.")
+                .action(ArgAction::Append),
+            arg!(-x --"exclude-tag" <TAG> "Hide items with this tag. Can be used more than once.")
+                .action(ArgAction::Append),
+        ]
+    };
+}
+
+macro_rules! query {
+    () => {
+        arg!(-q --query <QUERY> "Only show items that match a query (e.g. 'priority>=3 and not completed').")
+            .action(ArgAction::Set)
+    };
+}
+
+macro_rules! sort {
+    () => {
+        arg!(-s --sort <SORT> "Sort items by a list of keys (e.g. 'date,priority:desc'). Use 'file-order' to keep them as written.")
+            .action(ArgAction::Set)
+    };
+}
+
 macro_rules! down_flag {
     () => {
-        arg!(-d --down "Search down through files instead of up.")
-            .action(ArgAction::SetTrue)
+        [
+            arg!(-d --down "Search down through files instead of up.")
+                .action(ArgAction::SetTrue),
+            arg!(-u --up "Search up through files, even if the config says to search down.")
+                .action(ArgAction::SetTrue)
+                .overrides_with("down"),
+        ]
     };
 }
 
@@ -54,22 +84,76 @@ pub fn build() -> Command {
 
 USAGE
     When called with no arguments, `todo` will open a TUI to edit your todo lists.
+    Use the arrow keys to move around and expand or collapse items, space to
+    toggle an item, `e`, `p` and `D` to edit its name, priority and date, `a` to
+    add an item, `m` to move an item to somewhere else, `s` to save and `q` to quit.
+
+ITEM PATHS
+    Items are found with paths like '#list/item/sub item'. Each part matches the
+    start of an item's name, ignoring case, and it is an error if it matches more
+    than one item. Use '\"name\"' to match a whole name exactly, a number like '3'
+    to match the third item, or an ID like '@x83r0' to match a specific item. If
+    more than one list has the same name, pick one by its directory, like
+    '#Todo@~/work/api/', or by the '# alias' in its file, like '#api/'.
+
+QUERIES
+    `list` and `next` can filter items with --query, e.g.
+        todo list -q 'priority>=3 and due<=friday and not completed and name~\"deploy\"'
+    Fields are priority, urgency, due, name, tag and id. They can be compared with
+    =, !=, <, <=, >, >=, ~ (contains) and !~ (doesn't contain). completed, archived,
+    repeating and due also work on their own, and everything can be combined with
+    and, or, not and parentheses.
+
+SORTING
+    `list`, `next`, `get` and `agenda` take --sort with keys separated by commas,
+    e.g. 'date,priority:desc'. The keys are priority, date, urgency, name, created,
+    completed and file-order. A '# sort' line sets a list's own order. Files are
+    always saved in the order that they are written.
+
+CONFIG
+    Settings are read from '$XDG_CONFIG_HOME/todo-rs/config.toml', and then from each
+    '.todo.toml' in the current directory and its parents, with the nearest one
+    winning. They set the default format, down, sort, date-format, urgency.days and
+    colors, and search (gitignore, hidden, include, exclude, max-depth and
+    same-filesystem), which sets the directories that are searched when searching
+    down. Directories in a '.todoignore' file are skipped too. Use -u to search up
+    when the config sets down = true. files sets the names of the files that lists
+    are kept in, e.g. files = [\"TODO.md\", \".todo\"], and '.md' files are read and
+    written as Markdown task lists, with metadata like {priority=3 due=2026-06-13}.
+
+DRY RUN
+    Any command can be run with --dry-run to see what it would change, e.g.
+        todo --dry-run prune -d
+    Instead of being saved, each list that would change is shown as a unified diff.
+
+CHECKING LISTS
+    Lines that can't be read are left out, and the list isn't changed until they are
+    fixed. `todo check` (or `todo lint`) shows where every problem is, e.g.
+        todo check -d
 
 TIPS
     If you are looking to itegrate todo-rs into something else (like waybar), you
-    may want to look at the --format option.
+    may want to look at the --format option. Lists can be moved to and from todo.txt
+    with `todo export --to todotxt` and `todo import --from todotxt FILE`, and dated
+    items can be put in a calendar app with `todo export --to ics`.
 
 FILE FORMAT
     * Prefix metadata lines with a hash(#).
     * There is one line gap between the metadata and the items.
     * Todo items start with \"- [ ]\", with an \"x\" for a completed item, and an
       \"a\" for an archived item, which is hidden by default.
+    * Lines that are indented under an item (without a checkbox) are its note.
+    * In names, a backslash is written as \"\\\\\", and tabs and line breaks are
+      written as their code, like \"\\u{9}\".
+    * Other lines, like comments and blank lines, are kept as they are, and only the
+      lines for items that change are rewritten when saving.
 
     Example file:
     ┌──────────────────────────────────────────────────────────────────── .todo ─┐
     │# Todo list title                                                           │
     │# priority 3                                                                │
     │# date june 3 2026                                                          │
+    │# sort date,priority:desc                                                   │
     │# archived                                                                  │
     │                                                                            │
     │- [ ] Incomplete todo item                                                  │
@@ -77,12 +161,22 @@ FILE FORMAT
     │- [ ] \\2\\6/7/2026\\ Item with a priority and a date (July 6th, 2026)         │
     │ - [ ] \\5\\ Item with a priority of 5                                        │
     │ - [ ] \\4-aug-2025\\ Item that should be completed by August 4th, 2025       │
+    │ - [ ] \\@x83r0\\ Item with an ID, which can be used in paths as '@x83r0'     │
+    │ - [ ] \\every monday\\ Item that is added again when it is completed         │
+    │ - [ ] Item with tags +project @context                                     │
+    │    A note about the item, which can go on for more than one line           │
     │- [x] A completed todo                                                      │
     │- [a] An archived todo item (hidden by default)                             │
     └────────────────────────────────────────────────────────────────────────────┘
                 ",
         )
         .arg(file_path!("Specify an alternate path to open the TUI in."))
+        .args(down_flag!())
+        .arg(
+            arg!(--"dry-run" "Show what would be changed as a diff, without saving anything.")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
         /*
          * Main commands
          */
@@ -101,18 +195,34 @@ FILE FORMAT
                         .default_value("./")
                         .value_parser(value_parser!(std::path::PathBuf)),
                 )
-                .arg(down_flag!())
+                .args(down_flag!())
                 .arg(arg!(-c --children "Show the item's children.").action(ArgAction::SetTrue))
-                .arg(output_format!()),
+                .arg(output_format!())
+                .args(tag_filter!())
+                .arg(query!())
+                .arg(sort!()),
         )
         .subcommand(
             Command::new("list")
                 .about("List todo items for the current directory and its parents.")
-                .arg(down_flag!())
+                .args(down_flag!())
                 .arg(output_format!())
                 .arg(file_path!("Specify an alternate path to search from."))
                 .arg(arg!(-a --archived "Show archived items.").action(ArgAction::SetTrue))
-                .arg(arg!(-c --completed "Hide completed items.").action(ArgAction::SetTrue)),
+                .arg(arg!(-c --completed "Hide completed items.").action(ArgAction::SetTrue))
+                .args(tag_filter!())
+                .arg(query!())
+                .arg(sort!()),
+        )
+        .subcommand(
+            Command::new("agenda")
+                .about("Show the open items from every list, grouped by when they are due.")
+                .args(down_flag!())
+                .arg(output_format!())
+                .arg(file_path!("Specify an alternate path to search from."))
+                .args(tag_filter!())
+                .arg(query!())
+                .arg(sort!()),
         )
         .subcommand(
             Command::new("add")
@@ -122,19 +232,23 @@ FILE FORMAT
                     arg!(<ITEM_NAME> "The name of the item to add.")
                         .value_parser(value_parser!(String)),
                 )
-                .arg(down_flag!())
+                .args(down_flag!())
                 .arg(arg!(-D --date "Give the new item a due date.").action(ArgAction::Set))
                 .arg(
                     arg!(-p --priority "Set the priority of the new item.")
                         .action(ArgAction::Set)
                         .value_parser(value_parser!(i64)),
+                )
+                .arg(
+                    arg!(-r --repeat "Make the item repeat (e.g. \"every monday\" or \"every 2 weeks\").")
+                        .action(ArgAction::Set),
                 ),
         )
         .subcommand(
             Command::new("complete")
                 .about("Mark a todo item as completed.")
                 .arg(item_path!("The path of the todo item to complete."))
-                .arg(down_flag!()),
+                .args(down_flag!()),
         )
         /*
          * Maintenance Commands
@@ -144,7 +258,7 @@ FILE FORMAT
                 .about("Archive all completed todo items.")
                 .arg(file_path!("Alternate path to start from."))
                 .arg(arg!(-s --single "Prune only a single list.").action(ArgAction::SetTrue))
-                .arg(down_flag!()),
+                .args(down_flag!()),
         )
         /*
          * Editing Commands
@@ -155,42 +269,116 @@ FILE FORMAT
                 .visible_alias("delete")
                 .visible_alias("rm")
                 .arg(arg!(<ITEM_PATH> "The path of the todo item to remove."))
-                .arg(down_flag!()),
+                .args(down_flag!()),
         )
         .subcommand(
             Command::new("toggle")
                 .about("Toggle the completion of a todo item.")
                 .arg(item_path!("The path of the todo item to toggle."))
-                .arg(down_flag!()),
+                .args(down_flag!()),
         )
         .subcommand(
             Command::new("incomplete")
                 .about("Mark a todo item as incomplete.")
                 .arg(item_path!("The path of the todo item to mark."))
-                .arg(down_flag!()),
+                .args(down_flag!()),
         )
         .subcommand(
             Command::new("edit")
                 .about("Edit the properties of a todo item.")
                 .arg(item_path!("The path of the todo item to add."))
-                .arg(down_flag!())
+                .args(down_flag!())
                 .arg(arg!(-n --name "Set the name of the todo item.").action(ArgAction::Set))
                 .arg(arg!(-D --date "Set the date of the todo item.").action(ArgAction::Set))
                 .arg(
                     arg!(-p --priority "Set the priority of the todo item.").action(ArgAction::Set)
                         .value_parser(value_parser!(i64)),
                 )
+                .arg(
+                    arg!(-r --repeat "Set how often the todo item repeats, or \"never\".")
+                        .action(ArgAction::Set),
+                )
                 .arg(
                     arg!(-c --completed "Set whether the item is completed").action(ArgAction::Set),
                 )
-                .arg(arg!(-a --archived "Set whether the item is archived").action(ArgAction::Set)),
+                .arg(arg!(-a --archived "Set whether the item is archived").action(ArgAction::Set))
+                .arg(
+                    arg!(-N --note "Set the note under the todo item (an empty note removes it).")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    arg!(-e --"edit-note" "Edit the note under the todo item in $EDITOR.")
+                        .action(ArgAction::SetTrue),
+                ),
         )
         .subcommand(
             Command::new("get")
                 .about("Get the details of a specific todo item or list.")
                 .arg(arg!(<ITEM_PATH> "The path of the todo item to get."))
                 .arg(output_format!())
-                .arg(down_flag!()),
+                .args(down_flag!())
+                .args(tag_filter!())
+                .arg(sort!()),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Undo the last change to your todo lists.")
+                .arg(
+                    arg!(-f --force "Undo it even if the lists have been changed since.")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("redo")
+                .about("Redo the last change that was undone.")
+                .arg(
+                    arg!(-f --force "Redo it even if the lists have been changed since.")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("Show the recent changes to your todo lists, newest first.")
+                .arg(
+                    arg!(-n --limit <COUNT> "How many changes to show.")
+                        .default_value("20")
+                        .value_parser(value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            Command::new("register")
+                .about("Register a todo list, so that it can be found by name from anywhere.")
+                .arg(file_path!("The directory of the list to register (the current directory by default).")),
+        )
+        .subcommand(
+            Command::new("unregister")
+                .about("Stop a todo list from being found from anywhere.")
+                .arg(arg!(<LIST> "The list to unregister, as '#name' or its directory.")),
+        )
+        .subcommand(Command::new("lists").about("Show the registered todo lists."))
+        .subcommand(
+            Command::new("check")
+                .about("Look for problems in todo lists, and show where they are.")
+                .visible_alias("lint")
+                .args(down_flag!())
+                .arg(output_format!())
+                .arg(file_path!("Specify an alternate path to search from.")),
+        )
+        .subcommand(
+            Command::new("index")
+                .about("Manage the index of lists that is used to find them quickly when searching down.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("rebuild")
+                        .about("Read every list below a directory again, and forget the ones that are gone.")
+                        .arg(file_path!("The directory to index (the current directory by default).")),
+                ),
+        )
+        .subcommand(
+            Command::new("open")
+                .about("Open a todo list (or just one item) in $EDITOR, and save it if it is valid.")
+                .arg(arg!([ITEM_PATH] "The list or item to open. Without it, the list's file in the current directory is opened as it is."))
+                .args(down_flag!()),
         )
         .subcommand(
             Command::new("move")
@@ -208,4 +396,58 @@ FILE FORMAT
                         .action(ArgAction::SetTrue),
                 ),
         )
+        /*
+         * Interoperability Commands
+         */
+        .subcommand(
+            Command::new("import")
+                .about("Create a list from another format.")
+                .arg(
+                    arg!([INPUT] "The file to import. Reads from stdin if it is missing or '-'.")
+                        .value_parser(value_parser!(std::path::PathBuf)),
+                )
+                .arg(
+                    arg!(-f --format "The format of the input.")
+                        .visible_alias("from")
+                        .action(ArgAction::Set)
+                        .value_name("FORMAT")
+                        .default_value("json")
+                        .value_parser([
+                            PossibleValue::new("json")
+                                .help("The JSON that `--format json` outputs (default)."),
+                            PossibleValue::new("todotxt").help(
+                                "A todo.txt file. Items exported with `todo export` keep their nesting.",
+                            ),
+                            PossibleValue::new("ics").help(
+                                "An iCalendar file, with an item for each VTODO.",
+                            ),
+                        ]),
+                )
+                .arg(
+                    arg!(-o --output "The directory to create the list in.")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(std::path::PathBuf)),
+                )
+                .arg(arg!(--force "Overwrite an existing list.").action(ArgAction::SetTrue)),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write a list (or just one item and its children) in another format.")
+                .arg(arg!([ITEM_PATH] "The list or item to export. Without it, the list in the current directory is exported."))
+                .arg(
+                    arg!(-t --to "The format to write.")
+                        .action(ArgAction::Set)
+                        .value_name("FORMAT")
+                        .default_value("todotxt")
+                        .value_parser([
+                            PossibleValue::new("todotxt").help(
+                                "todo.txt, with nested items flattened and a path: to put them back (default).",
+                            ),
+                            PossibleValue::new("ics").help(
+                                "iCalendar, with a VTODO for each item that has a date, for calendar apps.",
+                            ),
+                        ]),
+                )
+                .args(down_flag!()),
+        )
 }
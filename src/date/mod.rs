@@ -7,8 +7,10 @@ use crate::error::Error;
 use crate::match_error;
 use crate::match_option;
 use crate::match_result;
+use crate::settings;
 
 pub mod parsing;
+pub mod recurrence;
 pub mod relative;
 
 /// Represents a date that a list or item might be due.
@@ -83,6 +85,36 @@ impl Date {
         );
     }
 
+    /// How the date is shown to people, which is `date-format` from the config if it is set. Files
+    /// always use `display`, so that they can be read again.
+    pub fn pretty(&self) -> String {
+        match (&settings::get().date_format, self.as_chrono()) {
+            (Some(format), Ok(date)) => date.format(format).to_string(),
+            _ => self.display(),
+        }
+    }
+
+    /// Formats the date as `YYYY-MM-DD`, for other programs to read.
+    pub fn iso(&self) -> String {
+        format!(
+            "{year:04}-{month:02}-{day:02}",
+            year = self.year,
+            month = self.month,
+            day = self.day
+        )
+    }
+
+    /// Parses a `YYYY-MM-DD` date. Unlike `Date::from`, this doesn't try to guess anything.
+    pub fn from_iso(date: &str) -> Result<Date, Error> {
+        let parsed = match_result!(
+            NaiveDate::parse_from_str(date, "%Y-%m-%d"),
+            CodeComponent::DateParser,
+            format!("Could not parse '{}' as a YYYY-MM-DD date.", date)
+        );
+
+        Date::from_date(parsed)
+    }
+
     pub fn as_chrono(&self) -> Result<NaiveDate, Error> {
         Ok(match_option!(
             NaiveDate::from_ymd_opt(
@@ -103,6 +135,17 @@ impl Date {
         ))
     }
 
+    /// How many days are left in this week after this date, where weeks end on Sunday.
+    pub fn days_until_end_of_week(&self) -> Result<i64, Error> {
+        let date = match_error!(
+            self.as_chrono(),
+            CodeComponent::Date,
+            format!("Could not convert date.")
+        );
+
+        Ok(6 - i64::from(date.weekday().num_days_from_monday()))
+    }
+
     pub fn distance(&self, relative: Date) -> Result<i64, Error> {
         let self_date = match_error!(
             self.as_chrono(),
@@ -0,0 +1,574 @@
+use chrono::Datelike;
+use chrono::Days;
+use chrono::Months;
+use chrono::NaiveDate;
+use chrono::Weekday;
+
+use crate::date::Date;
+use crate::error::CodeComponent;
+use crate::error::CodeComponent::Recurrence as RecurrenceComponent;
+use crate::error::Error;
+use crate::{match_error, match_option, match_result, propagate};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecurrenceUnit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// A rule for how often an item repeats, like "every monday" or "every 2 weeks".
+///
+/// It is written the same way in files and on the command line:
+///
+/// |------------------------------|-------------------------------------------|
+/// | rule                         | meaning                                   |
+/// |------------------------------|-------------------------------------------|
+/// | daily, every day             | the day after                             |
+/// | every 3 days                 | three days after                          |
+/// | weekly, every week           | a week after                              |
+/// | every monday, thursday       | the next Monday or Thursday               |
+/// | every weekday                | the next day from Monday to Friday        |
+/// | every 2 weeks on friday      | Friday, every other week                  |
+/// | monthly, every month         | the same day next month                   |
+/// | every month on the 1st       | the 1st of the next month                 |
+/// | yearly, every year           | the same day next year                    |
+/// |------------------------------|-------------------------------------------|
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    pub interval: u32,
+    pub unit: RecurrenceUnit,
+    /// Only used with weeks. If it is empty, it repeats on the same day of the week.
+    pub weekdays: Vec<Weekday>,
+    /// Only used with months. If it is `None`, it repeats on the same day of the month.
+    pub day_of_month: Option<u32>,
+}
+
+impl Recurrence {
+    pub fn parse(input: &str) -> Result<Recurrence, Error> {
+        let input = input.trim().to_ascii_lowercase();
+
+        let (shorthand, rest) = match input.split_once(" ") {
+            Some((first, rest)) => (first, rest.trim()),
+            None => (&input[..], ""),
+        };
+
+        let unit = match shorthand {
+            "daily" => Some(RecurrenceUnit::Day),
+            "weekly" => Some(RecurrenceUnit::Week),
+            "monthly" => Some(RecurrenceUnit::Month),
+            "yearly" | "annually" => Some(RecurrenceUnit::Year),
+            _ => None,
+        };
+        if let Some(unit) = unit {
+            return Recurrence::parse_rule(1, unit, rest, &input);
+        }
+
+        let Some(rule) = input.strip_prefix("every ") else {
+            return Err(propagate!(
+                RecurrenceComponent,
+                format!(
+                    "Repeating rules start with 'every', 'daily', 'weekly', 'monthly' or 'yearly'. Got '{input}'."
+                )
+            ));
+        };
+        let rule = rule.trim();
+
+        if rule == "weekday" || rule == "weekdays" {
+            return Ok(Recurrence {
+                interval: 1,
+                unit: RecurrenceUnit::Week,
+                weekdays: vec![
+                    Weekday::Mon,
+                    Weekday::Tue,
+                    Weekday::Wed,
+                    Weekday::Thu,
+                    Weekday::Fri,
+                ],
+                day_of_month: None,
+            });
+        }
+
+        let mut words = rule.splitn(2, " ");
+        let first = words.next().unwrap_or("");
+        let (interval, rule) = match first.parse::<u32>() {
+            Ok(interval) => (interval, words.next().unwrap_or("").trim()),
+            Err(_) => (1, rule),
+        };
+
+        if interval == 0 {
+            return Err(propagate!(
+                RecurrenceComponent,
+                format!("An item can't repeat every 0 units of time ('{input}').")
+            ));
+        }
+
+        let (unit_name, rest) = match rule.split_once(" ") {
+            Some((unit_name, rest)) => (unit_name, rest.trim()),
+            None => (rule, ""),
+        };
+
+        let unit = match unit_name {
+            "day" | "days" => Some(RecurrenceUnit::Day),
+            "week" | "weeks" => Some(RecurrenceUnit::Week),
+            "month" | "months" => Some(RecurrenceUnit::Month),
+            "year" | "years" => Some(RecurrenceUnit::Year),
+            _ => None,
+        };
+
+        match unit {
+            Some(unit) => Recurrence::parse_rule(interval, unit, rest, &input),
+            // Something like "every monday, thursday"
+            None if interval == 1 => Ok(Recurrence {
+                interval: 1,
+                unit: RecurrenceUnit::Week,
+                weekdays: match_error!(
+                    Recurrence::parse_weekdays(rule),
+                    RecurrenceComponent,
+                    format!("Could not parse the repeating rule '{input}'.")
+                ),
+                day_of_month: None,
+            }),
+            None => Err(propagate!(
+                RecurrenceComponent,
+                format!(
+                    "Expected days, weeks, months or years after 'every {interval}' in '{input}'."
+                )
+            )),
+        }
+    }
+
+    /// Parses what comes after the unit, like "on monday" or "on the 1st".
+    fn parse_rule(
+        interval: u32,
+        unit: RecurrenceUnit,
+        rest: &str,
+        input: &str,
+    ) -> Result<Recurrence, Error> {
+        let mut recurrence = Recurrence {
+            interval,
+            unit,
+            weekdays: vec![],
+            day_of_month: None,
+        };
+
+        if rest.is_empty() {
+            return Ok(recurrence);
+        }
+
+        let Some(on) = rest.strip_prefix("on ") else {
+            return Err(propagate!(
+                RecurrenceComponent,
+                format!("Did not understand '{rest}' in the repeating rule '{input}'.")
+            ));
+        };
+
+        match unit {
+            RecurrenceUnit::Week => {
+                recurrence.weekdays = match_error!(
+                    Recurrence::parse_weekdays(on),
+                    RecurrenceComponent,
+                    format!("Could not parse the days in the repeating rule '{input}'.")
+                );
+            }
+            RecurrenceUnit::Month => {
+                let day = on
+                    .trim_start_matches("the ")
+                    .trim_end_matches(|character: char| character.is_ascii_alphabetic());
+                let day = match_result!(
+                    day.parse::<u32>(),
+                    RecurrenceComponent,
+                    format!("Could not parse the day of the month in '{input}'.")
+                );
+                if day == 0 || day > 31 {
+                    return Err(propagate!(
+                        RecurrenceComponent,
+                        format!("There is no day {day} in a month.")
+                    ));
+                }
+                recurrence.day_of_month = Some(day);
+            }
+            _ => {
+                return Err(propagate!(
+                    RecurrenceComponent,
+                    format!(
+                        "Only weekly and monthly rules can say which day they are on ('{input}')."
+                    )
+                ));
+            }
+        }
+
+        Ok(recurrence)
+    }
+
+    fn parse_weekdays(input: &str) -> Result<Vec<Weekday>, Error> {
+        let mut weekdays = vec![];
+
+        for word in input.replace(",", " ").split_whitespace() {
+            if word == "and" {
+                continue;
+            }
+
+            let weekday = match_option!(
+                Date::parse_weekday(word),
+                RecurrenceComponent,
+                format!("'{word}' is not a day of the week.")
+            );
+            if !weekdays.contains(&weekday) {
+                weekdays.push(weekday);
+            }
+        }
+
+        if weekdays.is_empty() {
+            return Err(propagate!(
+                RecurrenceComponent,
+                format!("Expected at least one day of the week.")
+            ));
+        }
+
+        weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+        Ok(weekdays)
+    }
+
+    /// This formats it for saving, so it can be read back by `Recurrence::parse`.
+    pub fn display(&self) -> String {
+        let unit = match self.unit {
+            RecurrenceUnit::Day => "day",
+            RecurrenceUnit::Week => "week",
+            RecurrenceUnit::Month => "month",
+            RecurrenceUnit::Year => "year",
+        };
+        let weekdays = self
+            .weekdays
+            .iter()
+            .map(|weekday| Recurrence::weekday_name(*weekday))
+            .collect::<Vec<&str>>()
+            .join(", ");
+
+        let mut output = if self.interval == 1 {
+            if !weekdays.is_empty() {
+                return format!("every {weekdays}");
+            }
+            format!("every {unit}")
+        } else {
+            format!("every {} {unit}s", self.interval)
+        };
+
+        if !weekdays.is_empty() {
+            output += &format!(" on {weekdays}");
+        }
+        if let Some(day) = self.day_of_month {
+            output += &format!(" on the {}", Recurrence::ordinal(day));
+        }
+
+        output
+    }
+
+    fn weekday_name(weekday: Weekday) -> &'static str {
+        match weekday {
+            Weekday::Mon => "monday",
+            Weekday::Tue => "tuesday",
+            Weekday::Wed => "wednesday",
+            Weekday::Thu => "thursday",
+            Weekday::Fri => "friday",
+            Weekday::Sat => "saturday",
+            Weekday::Sun => "sunday",
+        }
+    }
+
+    fn ordinal(day: u32) -> String {
+        let suffix = match (day % 10, day % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        };
+        format!("{day}{suffix}")
+    }
+
+    /// The first date that the rule lands on after `date`.
+    pub fn next_after(&self, date: Date) -> Result<Date, Error> {
+        let start = match_error!(
+            date.as_chrono(),
+            RecurrenceComponent,
+            format!("Could not convert the date '{}'.", date.display())
+        );
+
+        let next = match self.unit {
+            RecurrenceUnit::Day => match_option!(
+                start.checked_add_days(Days::new(self.interval.into())),
+                RecurrenceComponent,
+                format!("Could not add {} days to {}.", self.interval, start)
+            ),
+            RecurrenceUnit::Week if self.weekdays.is_empty() => match_option!(
+                start.checked_add_days(Days::new(u64::from(self.interval) * 7)),
+                RecurrenceComponent,
+                format!("Could not add {} weeks to {}.", self.interval, start)
+            ),
+            RecurrenceUnit::Week => match_error!(
+                self.next_weekday(start),
+                RecurrenceComponent,
+                format!("Could not find the next matching day after {}.", start)
+            ),
+            RecurrenceUnit::Month => match_error!(
+                self.next_month(start, self.interval),
+                RecurrenceComponent,
+                format!("Could not add {} months to {}.", self.interval, start)
+            ),
+            RecurrenceUnit::Year => {
+                let months = match_option!(
+                    self.interval.checked_mul(12),
+                    RecurrenceComponent,
+                    format!("{} years is too far apart to repeat.", self.interval)
+                );
+                match_error!(
+                    self.next_month(start, months),
+                    RecurrenceComponent,
+                    format!("Could not add {} years to {}.", self.interval, start)
+                )
+            }
+        };
+
+        Date::from_date(next)
+    }
+
+    /// The date that the next occurrence of an item is due, after one that was due on `date` has
+    /// been completed. If that has already passed (because the item was completed late), it keeps
+    /// rolling forward so that the next one isn't overdue straight away. Items without a date
+    /// repeat from today.
+    pub fn next_occurrence(&self, date: Option<Date>) -> Result<Date, Error> {
+        let today = match_error!(
+            Date::today(),
+            RecurrenceComponent,
+            format!("Could not get today's date.")
+        );
+
+        let mut next = match_error!(
+            self.next_after(date.unwrap_or(today)),
+            RecurrenceComponent,
+            format!("Could not get the next occurrence.")
+        );
+        while match_error!(
+            next.distance(today),
+            RecurrenceComponent,
+            format!("Could not compare the next occurrence to today.")
+        ) <= 0
+        {
+            next = match_error!(
+                self.next_after(next),
+                RecurrenceComponent,
+                format!("Could not get the next occurrence.")
+            );
+        }
+
+        Ok(next)
+    }
+
+    fn next_weekday(&self, start: NaiveDate) -> Result<NaiveDate, Error> {
+        let week_start = start.week(Weekday::Mon).first_day();
+
+        // The longest possible gap is the rest of this week, plus the skipped weeks, plus the
+        // whole of the next matching week.
+        for offset in 1..=(7 * (u64::from(self.interval) + 1)) {
+            let candidate = match_option!(
+                start.checked_add_days(Days::new(offset)),
+                RecurrenceComponent,
+                format!("Could not add {offset} days to {start}.")
+            );
+            let weeks_later = candidate.signed_duration_since(week_start).num_days() / 7;
+
+            if self.weekdays.contains(&candidate.weekday())
+                && weeks_later % i64::from(self.interval) == 0
+            {
+                return Ok(candidate);
+            }
+        }
+
+        Err(propagate!(
+            RecurrenceComponent,
+            format!("None of the days in '{}' came up.", self.display())
+        ))
+    }
+
+    fn next_month(&self, start: NaiveDate, months: u32) -> Result<NaiveDate, Error> {
+        let Some(day) = self.day_of_month else {
+            return Ok(match_option!(
+                start.checked_add_months(Months::new(months)),
+                RecurrenceComponent,
+                format!("Could not add {months} months to {start}.")
+            ));
+        };
+
+        // If the day hasn't come up yet this month, that is the next one.
+        let this_month = match_error!(
+            Recurrence::day_in_month(start, day),
+            RecurrenceComponent,
+            format!("Could not find day {day} of the month of {start}.")
+        );
+        if this_month > start {
+            return Ok(this_month);
+        }
+
+        let later = match_option!(
+            start
+                .with_day(1)
+                .and_then(|first| first.checked_add_months(Months::new(months))),
+            RecurrenceComponent,
+            format!("Could not add {months} months to {start}.")
+        );
+        Recurrence::day_in_month(later, day)
+    }
+
+    /// The given day of the same month as `date`, or the last day of the month if it is too short.
+    fn day_in_month(date: NaiveDate, day: u32) -> Result<NaiveDate, Error> {
+        let first = match_option!(
+            date.with_day(1),
+            RecurrenceComponent,
+            format!("Could not get the start of the month of {date}.")
+        );
+        let length = match_option!(
+            first
+                .checked_add_months(Months::new(1))
+                .map(|next| next.signed_duration_since(first).num_days()),
+            RecurrenceComponent,
+            format!("Could not get the length of the month of {date}.")
+        );
+        let day = match_result!(
+            u64::try_from(i64::from(day).min(length) - 1),
+            RecurrenceComponent,
+            format!("Could not work out the day of the month.")
+        );
+
+        Ok(match_option!(
+            first.checked_add_days(Days::new(day)),
+            RecurrenceComponent,
+            format!("Could not add {day} days to {first}.")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(input: &str) -> Recurrence {
+        Recurrence::parse(input).expect("The rule should be read.")
+    }
+
+    fn date(iso: &str) -> Date {
+        Date::from_iso(iso).expect("The date is valid.")
+    }
+
+    fn next(input: &str, after: &str) -> String {
+        rule(input)
+            .next_after(date(after))
+            .expect("There should be a next date.")
+            .iso()
+    }
+
+    #[test]
+    fn rules_are_read() {
+        let every_other_friday = Recurrence {
+            interval: 2,
+            unit: RecurrenceUnit::Week,
+            weekdays: vec![Weekday::Fri],
+            day_of_month: None,
+        };
+        assert_eq!(rule("every 2 weeks on friday"), every_other_friday);
+        assert_eq!(rule("Every 2 Weeks on Friday"), every_other_friday);
+        assert_eq!(rule("daily"), rule("every day"));
+        assert_eq!(rule("weekly"), rule("every week"));
+        assert_eq!(rule("monthly"), rule("every month"));
+        assert_eq!(rule("yearly"), rule("every year"));
+        assert_eq!(rule("every 3 days").interval, 3);
+        assert_eq!(
+            rule("every monday, thursday").weekdays,
+            [Weekday::Mon, Weekday::Thu]
+        );
+        assert_eq!(rule("every weekday").weekdays.len(), 5);
+        assert_eq!(rule("every month on the 1st").day_of_month, Some(1));
+
+        for input in ["", "every", "every 0 days", "every fortnight", "sometimes"] {
+            assert!(Recurrence::parse(input).is_err(), "'{input}' was read");
+        }
+    }
+
+    #[test]
+    fn rules_are_displayed_so_they_can_be_read_back() {
+        for input in [
+            "every day",
+            "every 3 days",
+            "every week",
+            "every monday, thursday",
+            "every 2 weeks on friday",
+            "every month",
+            "every month on the 1st",
+            "every 2 months on the 22nd",
+            "every month on the 13th",
+            "every year",
+            "every 4 years",
+        ] {
+            assert_eq!(rule(input).display(), input);
+            assert_eq!(rule(&rule(input).display()), rule(input));
+        }
+    }
+
+    #[test]
+    fn days_and_weeks() {
+        assert_eq!(next("daily", "2026-12-31"), "2027-01-01");
+        assert_eq!(next("every 3 days", "2026-02-27"), "2026-03-02");
+        assert_eq!(next("weekly", "2026-10-18"), "2026-10-25");
+        // 2026-10-18 is a Sunday.
+        assert_eq!(next("every monday, thursday", "2026-10-18"), "2026-10-19");
+        assert_eq!(next("every monday, thursday", "2026-10-19"), "2026-10-22");
+        assert_eq!(next("every weekday", "2026-10-16"), "2026-10-19");
+    }
+
+    #[test]
+    fn months_end_on_the_last_day_when_they_are_too_short() {
+        assert_eq!(next("monthly", "2026-01-31"), "2026-02-28");
+        assert_eq!(next("monthly", "2028-01-31"), "2028-02-29");
+        assert_eq!(next("monthly", "2026-03-31"), "2026-04-30");
+        assert_eq!(next("every month on the 31st", "2026-01-31"), "2026-02-28");
+        assert_eq!(next("every month on the 31st", "2026-02-28"), "2026-03-31");
+        assert_eq!(next("every month on the 31st", "2026-04-15"), "2026-04-30");
+        assert_eq!(next("every month on the 1st", "2026-12-01"), "2027-01-01");
+        assert_eq!(next("every 2 months", "2026-12-31"), "2027-02-28");
+    }
+
+    #[test]
+    fn years_from_the_29th_of_february() {
+        assert_eq!(next("yearly", "2028-02-29"), "2029-02-28");
+        assert_eq!(next("every 4 years", "2028-02-29"), "2032-02-29");
+        assert_eq!(next("yearly", "2027-03-01"), "2028-03-01");
+    }
+
+    #[test]
+    fn huge_intervals_are_an_error() {
+        assert!(
+            rule("every 400000000 years")
+                .next_after(date("2026-10-18"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn occurrences_are_not_already_overdue() {
+        let today = Date::today().expect("Today is a date.");
+        let next = rule("daily")
+            .next_occurrence(Some(date("2000-01-01")))
+            .expect("There should be a next date.");
+        assert_eq!(next.distance(today).expect("The dates can be compared."), 1);
+
+        // Dates in the future just move on once.
+        assert_eq!(
+            rule("monthly")
+                .next_occurrence(Some(date("2100-01-31")))
+                .expect("There should be a next date.")
+                .iso(),
+            "2100-02-28"
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use chrono::Datelike;
 use chrono::Days;
 use chrono::Months;
+use chrono::Weekday;
 use chrono::offset::Local;
 
 use crate::date::Date;
@@ -210,6 +211,27 @@ impl Date {
         ))
     }
 
+    /// Parses the name of a day of the week, or the start of one (e.g. "mon" or "Monday").
+    pub fn parse_weekday(input: &str) -> Option<Weekday> {
+        if input.is_empty() {
+            return None;
+        }
+
+        let days = [
+            ("monday", Weekday::Mon),
+            ("tuesday", Weekday::Tue),
+            ("wednesday", Weekday::Wed),
+            ("thursday", Weekday::Thu),
+            ("friday", Weekday::Fri),
+            ("saturday", Weekday::Sat),
+            ("sunday", Weekday::Sun),
+        ];
+
+        days.into_iter()
+            .find(|(name, _)| name.starts_with(&input.to_ascii_lowercase()))
+            .map(|(_, weekday)| weekday)
+    }
+
     fn parse_day_of_the_week(input: &str) -> Result<Date, Error> {
         if input == "" {
             return Err(propagate!(
@@ -315,7 +337,6 @@ impl Date {
                 DateParser,
                 format!("Could not add the days.")
             );
-            println!("abt{delta}");
             return Ok(match_error!(
                 Date::from_date(new_date),
                 DateParser,
@@ -0,0 +1,202 @@
+/// How many unchanged lines are shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Makes a unified diff (like `diff -u`) between two versions of a file. If there is no difference,
+/// this is empty. A version that is `None` doesn't exist, like a list that is about to be created.
+pub fn unified(path: &str, before: Option<&str>, after: Option<&str>) -> String {
+    if before == after {
+        return String::new();
+    }
+
+    let old = before.map(|text| text.lines().collect()).unwrap_or(vec![]);
+    let new = after.map(|text| text.lines().collect()).unwrap_or(vec![]);
+    let lines = diff_lines(&old, &new);
+
+    let mut output = format!(
+        "--- {}\n+++ {}\n",
+        before.map_or("/dev/null", |_| path),
+        after.map_or("/dev/null", |_| path)
+    );
+
+    for (start, end) in hunks(&lines) {
+        // Line numbers start at 1, except for an empty range, which is numbered after the line
+        // before it.
+        let old_start = lines[..start]
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_start = lines[..start]
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        let old_length = lines[start..end]
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_length = lines[start..end]
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+
+        output.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_length),
+            range(new_start, new_length)
+        ));
+        for line in &lines[start..end] {
+            match line {
+                Line::Same(text) => output.push_str(&format!(" {text}\n")),
+                Line::Removed(text) => output.push_str(&format!("-{text}\n")),
+                Line::Added(text) => output.push_str(&format!("+{text}\n")),
+            }
+        }
+    }
+
+    // If the only difference is the newline at the end, there wouldn't be a hunk, so say so.
+    if !output.contains("@@") {
+        output.push_str("\\ The newline at the end of the file was changed.\n");
+    }
+
+    output
+}
+
+fn range(start: usize, length: usize) -> String {
+    if length == 1 {
+        format!("{}", start + 1)
+    } else if length == 0 {
+        format!("{start},0")
+    } else {
+        format!("{},{}", start + 1, length)
+    }
+}
+
+/// Finds the longest common subsequence of the lines, and turns it into a list of what happened to
+/// each line. Lists are small, so the simple quadratic table is fine.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let mut table = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i][j] = if old[i] == new[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|line| Line::Removed(line)));
+    lines.extend(new[j..].iter().map(|line| Line::Added(line)));
+
+    lines
+}
+
+/// Groups the changed lines (with their context) into the ranges that are shown as hunks.
+fn hunks(lines: &[Line]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = vec![];
+
+    for (i, line) in lines.iter().enumerate() {
+        if matches!(line, Line::Same(_)) {
+            continue;
+        }
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + 1 + CONTEXT).min(lines.len());
+        match hunks.last_mut() {
+            // Hunks that touch or overlap are shown as one.
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The numbers from 1 to 10, one on each line, with some of them replaced.
+    fn numbers(replaced: &[(usize, &str)]) -> String {
+        (1..=10)
+            .map(|number| {
+                replaced
+                    .iter()
+                    .find(|(other, _)| *other == number)
+                    .map_or(number.to_string(), |(_, text)| text.to_string())
+                    + "\n"
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hunks_have_context_up_to_the_ends_of_the_file() {
+        let before = numbers(&[]);
+        let after = numbers(&[(1, "one"), (10, "ten")]);
+
+        assert_eq!(
+            unified("list", Some(&before), Some(&after)),
+            "--- list\n+++ list\n\
+             @@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n 4\n\
+             @@ -7,4 +7,4 @@\n 7\n 8\n 9\n-10\n+ten\n"
+        );
+    }
+
+    #[test]
+    fn close_changes_are_in_one_hunk() {
+        let before = numbers(&[]);
+        let after = numbers(&[(4, "four"), (9, "nine")]).replace("6\n", "");
+
+        assert_eq!(
+            unified("list", Some(&before), Some(&after)),
+            "--- list\n+++ list\n\
+             @@ -1,10 +1,9 @@\n 1\n 2\n 3\n-4\n+four\n 5\n-6\n 7\n 8\n-9\n+nine\n 10\n"
+        );
+    }
+
+    #[test]
+    fn files_without_a_newline_at_the_end() {
+        assert_eq!(
+            unified("list", Some("a\nb"), Some("a\nc")),
+            "--- list\n+++ list\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n"
+        );
+        assert_eq!(
+            unified("list", Some("a\nb\n"), Some("a\nb")),
+            "--- list\n+++ list\n\\ The newline at the end of the file was changed.\n"
+        );
+        assert_eq!(unified("list", Some("a\nb"), Some("a\nb")), "");
+    }
+
+    #[test]
+    fn files_that_do_not_exist() {
+        assert_eq!(
+            unified("list", None, Some("# Todo\n\n- [ ] a\n")),
+            "--- /dev/null\n+++ list\n@@ -0,0 +1,3 @@\n+# Todo\n+\n+- [ ] a\n"
+        );
+        assert_eq!(
+            unified("list", Some("# Todo\n"), None),
+            "--- list\n+++ /dev/null\n@@ -1 +0,0 @@\n-# Todo\n"
+        );
+        assert_eq!(unified("list", None, None), "");
+    }
+}
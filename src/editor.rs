@@ -0,0 +1,49 @@
+use crate::error::{CodeComponent, Error};
+use crate::{match_result, propagate};
+
+use std::fs;
+use std::process::Command;
+
+/// Opens some text in the user's editor (`$VISUAL`, then `$EDITOR`, then `vi`), and returns what
+/// it was changed to. The name is used for the temporary file, so that editors can pick a syntax.
+pub fn edit_text(text: &str, name: &str) -> Result<String, Error> {
+    let path = std::env::temp_dir().join(format!("todo-{}-{}", std::process::id(), name));
+    match_result!(
+        fs::write(&path, text),
+        CodeComponent::Editor,
+        format!("Could not write the temporary file '{}'.", path.display())
+    );
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or("vi".to_string());
+    // Editors like "code --wait" need their arguments to be split off.
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    let status = Command::new(program).args(parts).arg(&path).status();
+    let result = match status {
+        Ok(status) if status.success() => match_result!(
+            fs::read_to_string(&path),
+            CodeComponent::Editor,
+            format!("Could not read the temporary file '{}'.", path.display())
+        ),
+        Ok(status) => {
+            let _ = fs::remove_file(&path);
+            return Err(propagate!(
+                CodeComponent::Editor,
+                format!("'{editor}' exited with {status}, so nothing was changed.")
+            ));
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&path);
+            return Err(propagate!(
+                CodeComponent::Editor,
+                format!("Could not start the editor '{editor}': {err}")
+            ));
+        }
+    };
+
+    let _ = fs::remove_file(&path);
+    Ok(result)
+}
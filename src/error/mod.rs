@@ -9,6 +9,13 @@ pub enum CodeComponent {
     Main,
     Executor,
     FileSearcher,
+    Tui,
+    Editor,
+    Journal,
+    Storage,
+    Settings,
+    Index,
+    Registry,
 
     DocumentPath,
     Document,
@@ -22,6 +29,17 @@ pub enum CodeComponent {
 
     Date,
     DateParser,
+    Recurrence,
+
+    Query,
+    QueryParser,
+
+    Agenda,
+    Sort,
+
+    Json,
+    TodoTxt,
+    Ics,
 }
 
 #[derive(Debug, Clone)]
@@ -45,6 +63,13 @@ impl CodeComponent {
             Self::Main => "MAIN",
             Self::Executor => "EXECUTOR",
             Self::FileSearcher => "FILE_SEARCH",
+            Self::Tui => "TUI",
+            Self::Editor => "EDITOR",
+            Self::Journal => "JOURNAL",
+            Self::Storage => "STORAGE",
+            Self::Settings => "SETTINGS",
+            Self::Index => "INDEX",
+            Self::Registry => "REGISTRY",
 
             Self::DocumentPath => "DOCUMENT:PATH",
             Self::Document => "DOCUMENT:MAIN",
@@ -58,6 +83,17 @@ impl CodeComponent {
 
             Self::Date => "DATE:MAIN",
             Self::DateParser => "DATE:PARSER",
+            Self::Recurrence => "DATE:RECURRENCE",
+
+            Self::Query => "QUERY:MAIN",
+            Self::QueryParser => "QUERY:PARSER",
+
+            Self::Agenda => "AGENDA",
+            Self::Sort => "SORT",
+
+            Self::Json => "JSON",
+            Self::TodoTxt => "TODOTXT",
+            Self::Ics => "ICS",
         }
     }
 }
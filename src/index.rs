@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CodeComponent, CodeComponent::Index as IndexComponent, Error};
+use crate::todo::document::Document;
+use crate::todo::item::Item;
+use crate::{match_error, match_result, propagate, search_paths, storage};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const INDEX_VERSION: u32 = 1;
+
+/// What is known about a list without reading it again. An entry is only used while the file's
+/// modification time and size are the same as when it was read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub alias: Option<String>,
+    /// Nanoseconds since the Unix epoch.
+    pub modified: u128,
+    pub size: u64,
+    pub archived: bool,
+    /// How many items aren't archived (or under an archived item).
+    pub items: usize,
+    /// How many of those aren't completed.
+    pub open: usize,
+}
+
+/// A cache of the lists that have been found, kept in the data directory, so that looking for a
+/// list by name doesn't have to read every list. It is only a cache, so if it can't be read, it
+/// starts again empty.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    /// Which version of the entries this has, so that an index from an older `todo` (whose entries
+    /// might be missing something) is started again.
+    #[serde(default)]
+    version: u32,
+    lists: BTreeMap<PathBuf, IndexEntry>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl Index {
+    pub fn load() -> Index {
+        index_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str::<Index>(&content).ok())
+            .filter(|index| index.version == INDEX_VERSION)
+            .unwrap_or(Index {
+                version: INDEX_VERSION,
+                ..Index::default()
+            })
+    }
+
+    /// Saves the index, if anything in it has changed.
+    pub fn save(&mut self) -> Result<(), Error> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let path = match_error!(
+            index_path(),
+            IndexComponent,
+            format!("Could not find the index.")
+        );
+        if let Some(parent) = path.parent() {
+            match_result!(
+                fs::create_dir_all(parent),
+                IndexComponent,
+                format!("Could not create the directory '{}'.", parent.display())
+            );
+        }
+        let content = match_result!(
+            serde_json::to_string(self),
+            IndexComponent,
+            format!("Could not serialize the index.")
+        );
+        match_error!(
+            storage::write_atomic(&path, &content),
+            IndexComponent,
+            format!("Could not write the index at '{}'.", path.display())
+        );
+        self.changed = false;
+
+        Ok(())
+    }
+
+    /// The entries for the lists in these directories, in the same order. Lists that have changed
+    /// since they were indexed (or that haven't been yet) are read again, all at once, and are
+    /// given back too, so that they don't have to be read twice.
+    pub fn read(
+        &mut self,
+        paths: &[PathBuf],
+    ) -> Result<Vec<(IndexEntry, Option<Document>)>, Error> {
+        let mut stale = vec![];
+        for path in paths {
+            let stamp = match_error!(
+                stamp(path),
+                IndexComponent,
+                format!("Could not check '{}'.", path.display())
+            );
+            if self
+                .lists
+                .get(path)
+                .is_none_or(|entry| (entry.modified, entry.size) != stamp)
+            {
+                stale.push((path.clone(), stamp));
+            }
+        }
+
+        let documents = match_error!(
+            Document::from_paths(
+                &stale
+                    .iter()
+                    .map(|(path, _)| path.clone())
+                    .collect::<Vec<PathBuf>>()
+            ),
+            IndexComponent,
+            format!("Could not read the lists that have changed.")
+        );
+        let mut read = BTreeMap::new();
+        for ((path, (modified, size)), document) in stale.into_iter().zip(documents) {
+            self.lists
+                .insert(path.clone(), IndexEntry::new(&document, modified, size));
+            read.insert(path, document);
+            self.changed = true;
+        }
+
+        let mut entries = vec![];
+        for path in paths {
+            match self.lists.get(path) {
+                Some(entry) => entries.push((entry.clone(), read.remove(path))),
+                None => {
+                    return Err(propagate!(
+                        IndexComponent,
+                        format!("'{}' should have been indexed.", path.display())
+                    ));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Throws away what is known about the lists under a path, and reads them all again. Lists that
+    /// don't exist anymore are forgotten too.
+    pub fn rebuild(&mut self, path: &PathBuf) -> Result<usize, Error> {
+        let paths = match_error!(
+            search_paths::search_down(path),
+            IndexComponent,
+            format!("Could not search down from '{}'.", path.display())
+        );
+
+        let root = match_result!(
+            path.canonicalize(),
+            IndexComponent,
+            format!("Could not get the canonical path of '{}'", path.display())
+        );
+        self.lists.retain(|other, _| {
+            !other.starts_with(&root) && search_paths::has_todo_list(other).unwrap_or(false)
+        });
+        self.changed = true;
+
+        match_error!(
+            self.read(&paths),
+            IndexComponent,
+            format!("Could not index the lists under '{}'.", path.display())
+        );
+
+        Ok(paths.len())
+    }
+}
+
+impl IndexEntry {
+    fn new(document: &Document, modified: u128, size: u64) -> IndexEntry {
+        let (items, open) = count(&document.items);
+        IndexEntry {
+            name: document.name.clone(),
+            alias: document.alias.clone(),
+            modified,
+            size,
+            archived: document.archived,
+            items,
+            open,
+        }
+    }
+}
+
+/// Counts the items that aren't archived, and how many of them aren't completed.
+fn count(items: &[Item]) -> (usize, usize) {
+    items
+        .iter()
+        .filter(|item| !item.archived)
+        .fold((0, 0), |(total, open), item| {
+            let (child_total, child_open) = count(&item.items);
+            (
+                total + 1 + child_total,
+                open + usize::from(!item.completed) + child_open,
+            )
+        })
+}
+
+/// When a list's file was last changed, and how big it is.
+fn stamp(path: &Path) -> Result<(u128, u64), Error> {
+    let file_name = match_error!(
+        search_paths::list_file(path),
+        IndexComponent,
+        format!("Could not find the list in '{}'.", path.display())
+    )
+    .unwrap_or_else(search_paths::default_list_file);
+    let metadata = match_result!(
+        fs::metadata(path.join(file_name)),
+        IndexComponent,
+        format!("Could not read the metadata of '{}'.", path.display())
+    );
+    let modified = match_result!(
+        metadata.modified(),
+        IndexComponent,
+        format!("Could not get when '{}' was changed.", path.display())
+    );
+
+    Ok((
+        modified
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos())
+            .unwrap_or(0),
+        metadata.len(),
+    ))
+}
+
+fn index_path() -> Result<PathBuf, Error> {
+    Ok(match_error!(
+        search_paths::data_dir(),
+        IndexComponent,
+        format!("Could not find the data directory.")
+    )
+    .join("index.json"))
+}
@@ -0,0 +1,499 @@
+use chrono::offset::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CodeComponent, CodeComponent::Journal, Error};
+use crate::{match_error, match_result, propagate, search_paths, storage};
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Every save in one run of `todo` is part of the same change, so that (for example) a `prune` over
+/// a whole tree of lists is undone all at once.
+static TRANSACTION: OnceLock<String> = OnceLock::new();
+
+/// A line of the journal. The journal is only ever appended to, and undoing or redoing a change is
+/// recorded as a line of its own.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Entry {
+    /// A file was saved.
+    Save {
+        transaction: String,
+        command: String,
+        time: String,
+        path: PathBuf,
+        /// There was no file before, like when a list is imported.
+        before: Option<String>,
+        after: String,
+    },
+    Undo {
+        transaction: String,
+    },
+    Redo {
+        transaction: String,
+    },
+}
+
+/// Everything that one run of `todo` saved.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub id: String,
+    pub command: String,
+    pub time: String,
+    /// Each file that was changed, with what it was before the first save and after the last one.
+    pub files: Vec<(PathBuf, Option<String>, String)>,
+}
+
+/// Records that a file is about to be replaced, so that it can be undone later.
+pub fn record(path: &Path, before: Option<String>, after: &str) -> Result<(), Error> {
+    let transaction = TRANSACTION.get_or_init(|| {
+        format!(
+            "{}-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis())
+                .unwrap_or(0),
+            std::process::id()
+        )
+    });
+    record_in(
+        &match_error!(
+            journal_path(),
+            Journal,
+            format!("Could not find the journal.")
+        ),
+        transaction,
+        path,
+        before,
+        after,
+    )
+}
+
+/// Records a save in a journal, as part of a transaction.
+fn record_in(
+    journal: &Path,
+    transaction: &str,
+    path: &Path,
+    before: Option<String>,
+    after: &str,
+) -> Result<(), Error> {
+    if before.as_deref() == Some(after) {
+        return Ok(());
+    }
+
+    // Leave out the path to the executable, so that it reads like what was typed.
+    let command = std::iter::once("todo".to_string())
+        .chain(std::env::args().skip(1))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    append(
+        journal,
+        &Entry::Save {
+            transaction: transaction.to_string(),
+            command,
+            time: Local::now().format("%d-%b-%Y %H:%M").to_string(),
+            path: path.to_path_buf(),
+            before,
+            after: after.to_string(),
+        },
+    )
+}
+
+/// The changes that can be undone (oldest first), and the ones that can be redone (most recently
+/// undone last).
+pub fn history() -> Result<(Vec<Transaction>, Vec<Transaction>), Error> {
+    history_in(&match_error!(
+        journal_path(),
+        Journal,
+        format!("Could not find the journal.")
+    ))
+}
+
+fn history_in(path: &Path) -> Result<(Vec<Transaction>, Vec<Transaction>), Error> {
+    if !fs::exists(path).unwrap_or(false) {
+        return Ok((vec![], vec![]));
+    }
+    let content = match_result!(
+        fs::read_to_string(path),
+        Journal,
+        format!("Could not read the journal at '{}'.", path.display())
+    );
+
+    let mut done: Vec<Transaction> = vec![];
+    let mut undone: Vec<Transaction> = vec![];
+
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry = match_result!(
+            serde_json::from_str(line),
+            Journal,
+            format!(
+                "Could not read line {} of the journal at '{}'.",
+                i + 1,
+                path.display()
+            )
+        );
+
+        match entry {
+            Entry::Save {
+                transaction,
+                command,
+                time,
+                path,
+                before,
+                after,
+            } => {
+                if done.last().is_none_or(|last| last.id != transaction) {
+                    // Redoing only makes sense until something else is changed.
+                    undone.clear();
+                    done.push(Transaction {
+                        id: transaction,
+                        command,
+                        time,
+                        files: vec![],
+                    });
+                }
+                if let Some(last) = done.last_mut() {
+                    match last.files.iter_mut().find(|(other, _, _)| *other == path) {
+                        Some((_, _, last_after)) => *last_after = after,
+                        None => last.files.push((path, before, after)),
+                    }
+                }
+            }
+            Entry::Undo { transaction } => {
+                if let Some(position) = done.iter().rposition(|other| other.id == transaction) {
+                    undone.push(done.remove(position));
+                }
+            }
+            Entry::Redo { transaction } => {
+                if let Some(position) = undone.iter().rposition(|other| other.id == transaction) {
+                    done.push(undone.remove(position));
+                }
+            }
+        }
+    }
+
+    Ok((done, undone))
+}
+
+/// Puts the files back to how they were before the most recent change.
+pub fn undo(force: bool) -> Result<Option<Transaction>, Error> {
+    undo_in(
+        &match_error!(
+            journal_path(),
+            Journal,
+            format!("Could not find the journal.")
+        ),
+        force,
+    )
+}
+
+fn undo_in(journal: &Path, force: bool) -> Result<Option<Transaction>, Error> {
+    let (done, _) = match_error!(
+        history_in(journal),
+        Journal,
+        "Could not read the history.".to_string()
+    );
+    let Some(transaction) = done.last() else {
+        return Ok(None);
+    };
+
+    // Every file is checked first, so that nothing is changed unless all of it can be undone.
+    if !force {
+        for (path, _, after) in &transaction.files {
+            match_error!(
+                check_unchanged(path, Some(after)),
+                Journal,
+                format!("Could not undo the change to '{}'.", path.display())
+            );
+        }
+    }
+    if storage::is_dry_run() {
+        for (path, before, after) in &transaction.files {
+            storage::print_diff(path, Some(after.as_str()), before.as_deref());
+        }
+        return Ok(Some(transaction.clone()));
+    }
+    for (path, before, _) in &transaction.files {
+        match_error!(
+            restore(path, before.as_deref()),
+            Journal,
+            format!("Could not undo the change to '{}'.", path.display())
+        );
+    }
+
+    match_error!(
+        append(
+            journal,
+            &Entry::Undo {
+                transaction: transaction.id.clone(),
+            }
+        ),
+        Journal,
+        format!("Could not record the undo.")
+    );
+
+    Ok(Some(transaction.clone()))
+}
+
+/// Makes the most recently undone change again.
+pub fn redo(force: bool) -> Result<Option<Transaction>, Error> {
+    redo_in(
+        &match_error!(
+            journal_path(),
+            Journal,
+            format!("Could not find the journal.")
+        ),
+        force,
+    )
+}
+
+fn redo_in(journal: &Path, force: bool) -> Result<Option<Transaction>, Error> {
+    let (_, undone) = match_error!(
+        history_in(journal),
+        Journal,
+        "Could not read the history.".to_string()
+    );
+    let Some(transaction) = undone.last() else {
+        return Ok(None);
+    };
+
+    if !force {
+        for (path, before, _) in &transaction.files {
+            match_error!(
+                check_unchanged(path, before.as_deref()),
+                Journal,
+                format!("Could not redo the change to '{}'.", path.display())
+            );
+        }
+    }
+    if storage::is_dry_run() {
+        for (path, before, after) in &transaction.files {
+            storage::print_diff(path, before.as_deref(), Some(after.as_str()));
+        }
+        return Ok(Some(transaction.clone()));
+    }
+    for (path, _, after) in &transaction.files {
+        match_error!(
+            restore(path, Some(after)),
+            Journal,
+            format!("Could not redo the change to '{}'.", path.display())
+        );
+    }
+
+    match_error!(
+        append(
+            journal,
+            &Entry::Redo {
+                transaction: transaction.id.clone(),
+            }
+        ),
+        Journal,
+        format!("Could not record the redo.")
+    );
+
+    Ok(Some(transaction.clone()))
+}
+
+/// Makes sure that a file is still what the journal expects, so that undoing a change doesn't throw
+/// away anything that was done after it.
+fn check_unchanged(path: &Path, expected: Option<&str>) -> Result<(), Error> {
+    if fs::read_to_string(path).ok().as_deref() != expected {
+        return Err(propagate!(
+            Journal,
+            format!(
+                "'{}' has been changed since then, so nothing was changed. Use --force to replace it anyway.",
+                path.display()
+            )
+        ));
+    }
+
+    Ok(())
+}
+
+/// Replaces a file with what it should be. If it should not exist, it is removed.
+fn restore(path: &Path, content: Option<&str>) -> Result<(), Error> {
+    match content {
+        Some(content) => match_error!(
+            storage::write_atomic(path, content),
+            Journal,
+            format!("Could not write to '{}'.", path.display())
+        ),
+        None => {
+            if fs::exists(path).unwrap_or(false) {
+                match_result!(
+                    fs::remove_file(path),
+                    Journal,
+                    format!("Could not remove '{}'.", path.display())
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn append(path: &Path, entry: &Entry) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        match_result!(
+            fs::create_dir_all(parent),
+            Journal,
+            format!("Could not create the directory '{}'.", parent.display())
+        );
+    }
+
+    let line = match_result!(
+        serde_json::to_string(entry),
+        Journal,
+        format!("Could not serialize a journal entry.")
+    );
+    let mut file = match_result!(
+        fs::OpenOptions::new().create(true).append(true).open(path),
+        Journal,
+        format!("Could not open the journal at '{}'.", path.display())
+    );
+    match_result!(
+        writeln!(file, "{line}"),
+        Journal,
+        format!("Could not write to the journal at '{}'.", path.display())
+    );
+
+    Ok(())
+}
+
+fn journal_path() -> Result<PathBuf, Error> {
+    Ok(match_error!(
+        search_paths::data_dir(),
+        Journal,
+        format!("Could not find the data directory.")
+    )
+    .join("journal.jsonl"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for a test's journal and lists.
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("todo-journal-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).expect("The directory should be made.");
+        directory
+    }
+
+    /// Saves a file like a list would be, recording it first.
+    fn save(journal: &Path, transaction: &str, path: &Path, content: &str) {
+        record_in(
+            journal,
+            transaction,
+            path,
+            fs::read_to_string(path).ok(),
+            content,
+        )
+        .expect("The save should be recorded.");
+        fs::write(path, content).expect("The file should be written.");
+    }
+
+    fn ids(transactions: &[Transaction]) -> Vec<&str> {
+        transactions
+            .iter()
+            .map(|transaction| transaction.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn changes_are_undone_and_redone() {
+        let directory = directory("undo");
+        let journal = directory.join("journal.jsonl");
+        let list = directory.join("list");
+        let new_list = directory.join("new");
+        fs::write(&list, "before").expect("The file should be written.");
+
+        save(&journal, "1", &list, "middle");
+        save(&journal, "1", &list, "after");
+        save(&journal, "1", &new_list, "new");
+
+        let (done, undone) = history_in(&journal).expect("The history should be read.");
+        assert_eq!((ids(&done), ids(&undone)), (vec!["1"], vec![]));
+        assert_eq!(
+            done[0].files,
+            [
+                (
+                    list.clone(),
+                    Some("before".to_string()),
+                    "after".to_string()
+                ),
+                (new_list.clone(), None, "new".to_string()),
+            ]
+        );
+
+        undo_in(&journal, false).expect("The change should be undone.");
+        assert_eq!(fs::read_to_string(&list).ok().as_deref(), Some("before"));
+        assert!(!new_list.exists());
+        let (done, undone) = history_in(&journal).expect("The history should be read.");
+        assert_eq!((ids(&done), ids(&undone)), (vec![], vec!["1"]));
+
+        redo_in(&journal, false).expect("The change should be redone.");
+        assert_eq!(fs::read_to_string(&list).ok().as_deref(), Some("after"));
+        assert_eq!(fs::read_to_string(&new_list).ok().as_deref(), Some("new"));
+        let (done, undone) = history_in(&journal).expect("The history should be read.");
+        assert_eq!((ids(&done), ids(&undone)), (vec!["1"], vec![]));
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn new_changes_cannot_be_redone_over() {
+        let directory = directory("redo");
+        let journal = directory.join("journal.jsonl");
+        let list = directory.join("list");
+        fs::write(&list, "before").expect("The file should be written.");
+
+        save(&journal, "1", &list, "first");
+        undo_in(&journal, false).expect("The change should be undone.");
+        save(&journal, "2", &list, "second");
+
+        let (done, undone) = history_in(&journal).expect("The history should be read.");
+        assert_eq!((ids(&done), ids(&undone)), (vec!["2"], vec![]));
+        assert!(
+            redo_in(&journal, false)
+                .expect("Nothing should be redone.")
+                .is_none()
+        );
+        assert_eq!(fs::read_to_string(&list).ok().as_deref(), Some("second"));
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn files_changed_since_are_left_alone() {
+        let directory = directory("changed");
+        let journal = directory.join("journal.jsonl");
+        let list = directory.join("list");
+        fs::write(&list, "before").expect("The file should be written.");
+
+        save(&journal, "1", &list, "after");
+        fs::write(&list, "edited by hand").expect("The file should be written.");
+
+        assert!(undo_in(&journal, false).is_err());
+        assert_eq!(
+            fs::read_to_string(&list).ok().as_deref(),
+            Some("edited by hand")
+        );
+        let (done, _) = history_in(&journal).expect("The history should be read.");
+        assert_eq!(ids(&done), ["1"]);
+
+        undo_in(&journal, true).expect("The change should be undone anyway.");
+        assert_eq!(fs::read_to_string(&list).ok().as_deref(), Some("before"));
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
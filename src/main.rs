@@ -5,17 +5,30 @@ use std::path::PathBuf;
 mod command_executors;
 pub mod commands;
 mod date;
+mod diff;
+mod editor;
 mod error;
+mod index;
+mod journal;
 mod output;
+mod query;
+mod registry;
 mod search_paths;
+mod settings;
+mod storage;
 mod todo;
+mod tui;
 mod version;
 
 use crate::command_executors::*;
 use crate::date::Date;
+use crate::date::recurrence::Recurrence;
 use crate::error::{CodeComponent, Error};
 use crate::output::RenderFormat;
+use crate::query::Query;
 use crate::todo::path::ItemPath;
+use crate::todo::sort::SortSpec;
+use crate::todo::tags::TagFilter;
 use crate::version::{LONG_VERSION, VERSION};
 
 fn main() {
@@ -33,20 +46,41 @@ fn main() {
 }
 
 fn match_commands(matches: clap::ArgMatches) -> Result<(), Error> {
+    storage::set_dry_run(matches.get_flag("dry-run"));
+    match_error!(
+        settings::load(),
+        CodeComponent::Main,
+        format!("Could not load the settings.")
+    );
+
     match matches.subcommand() {
         Some(("init", sub_matches)) => init(parse_file_path(sub_matches)?),
         Some(("next", sub_matches)) => next(
             parse_file_path(sub_matches)?,
             sub_matches.get_flag("children"),
-            sub_matches.get_flag("down"),
+            parse_down(sub_matches),
             parse_output_format(sub_matches)?,
+            parse_tag_filter(sub_matches),
+            parse_query(sub_matches)?,
+            parse_sort(sub_matches)?,
         ),
         Some(("list", sub_matches)) => list(
-            sub_matches.get_flag("down"),
+            parse_down(sub_matches),
             parse_output_format(sub_matches)?,
             parse_file_path(sub_matches)?,
             sub_matches.get_flag("archived"),
             !sub_matches.get_flag("completed"),
+            parse_tag_filter(sub_matches),
+            parse_query(sub_matches)?,
+            parse_sort(sub_matches)?,
+        ),
+        Some(("agenda", sub_matches)) => agenda(
+            parse_file_path(sub_matches)?,
+            parse_down(sub_matches),
+            parse_output_format(sub_matches)?,
+            parse_tag_filter(sub_matches),
+            parse_query(sub_matches)?,
+            parse_sort(sub_matches)?,
         ),
         Some(("add", sub_matches)) => add(
             parse_item_path_arg(sub_matches)?,
@@ -56,42 +90,78 @@ fn match_commands(matches: clap::ArgMatches) -> Result<(), Error> {
                 .to_string(),
             parse_date(sub_matches)?,
             sub_matches.get_one::<i64>("priority"),
-            sub_matches.get_flag("down"),
-        ),
-        Some(("remove", sub_matches)) => remove(
-            parse_item_path_arg(sub_matches)?,
-            sub_matches.get_flag("down"),
+            parse_recurrence(sub_matches)?.flatten(),
+            parse_down(sub_matches),
         ),
+        Some(("remove", sub_matches)) => {
+            remove(parse_item_path_arg(sub_matches)?, parse_down(sub_matches))
+        }
         Some(("prune", sub_matches)) => prune(
             parse_file_path(sub_matches)?,
             sub_matches.get_flag("single"),
-            sub_matches.get_flag("down"),
-        ),
-        Some(("complete", sub_matches)) => complete(
-            parse_item_path_arg(sub_matches)?,
-            sub_matches.get_flag("down"),
-        ),
-        Some(("toggle", sub_matches)) => toggle(
-            parse_item_path_arg(sub_matches)?,
-            sub_matches.get_flag("down"),
-        ),
-        Some(("incomplete", sub_matches)) => incomplete(
-            parse_item_path_arg(sub_matches)?,
-            sub_matches.get_flag("down"),
+            parse_down(sub_matches),
         ),
+        Some(("complete", sub_matches)) => {
+            complete(parse_item_path_arg(sub_matches)?, parse_down(sub_matches))
+        }
+        Some(("toggle", sub_matches)) => {
+            toggle(parse_item_path_arg(sub_matches)?, parse_down(sub_matches))
+        }
+        Some(("incomplete", sub_matches)) => {
+            incomplete(parse_item_path_arg(sub_matches)?, parse_down(sub_matches))
+        }
         Some(("edit", sub_matches)) => edit(
             parse_item_path_arg(sub_matches)?,
-            sub_matches.get_flag("down"),
+            parse_down(sub_matches),
             sub_matches.get_one("name"),
             parse_date(sub_matches)?,
             sub_matches.get_one("priority"),
+            parse_recurrence(sub_matches)?,
             sub_matches.get_one("completed"),
             sub_matches.get_one("archived"),
+            sub_matches.get_one("note"),
+            sub_matches.get_flag("edit-note"),
         ),
         Some(("get", sub_matches)) => get(
             parse_item_path_arg(sub_matches)?,
             parse_output_format(sub_matches)?,
-            sub_matches.get_flag("down"),
+            parse_down(sub_matches),
+            parse_tag_filter(sub_matches),
+            parse_sort(sub_matches)?,
+        ),
+        Some(("undo", sub_matches)) => undo(sub_matches.get_flag("force")),
+        Some(("redo", sub_matches)) => redo(sub_matches.get_flag("force")),
+        Some(("register", sub_matches)) => register(parse_file_path(sub_matches)?),
+        Some(("unregister", sub_matches)) => unregister(
+            sub_matches
+                .get_one::<String>("LIST")
+                .expect("Expected a list.")
+                .to_string(),
+        ),
+        Some(("lists", _)) => lists(),
+        Some(("check", sub_matches)) => check(
+            parse_down(sub_matches),
+            parse_output_format(sub_matches)?,
+            parse_file_path(sub_matches)?,
+        ),
+        Some(("index", sub_matches)) => match sub_matches.subcommand() {
+            Some(("rebuild", rebuild_matches)) => index_rebuild(parse_file_path(rebuild_matches)?),
+            _ => Err(propagate!(
+                CodeComponent::Main,
+                format!("Unknown index command.")
+            )),
+        },
+        Some(("history", sub_matches)) => history(
+            *sub_matches
+                .get_one::<usize>("limit")
+                .expect("The limit should have a default value."),
+        ),
+        Some(("open", sub_matches)) => open(
+            match sub_matches.get_one::<String>("ITEM_PATH") {
+                Some(_) => Some(parse_item_path_arg(sub_matches)?),
+                None => None,
+            },
+            parse_down(sub_matches),
         ),
         Some(("move", sub_matches)) => move_item(
             ItemPath::try_from(
@@ -121,7 +191,28 @@ fn match_commands(matches: clap::ArgMatches) -> Result<(), Error> {
             )),
             sub_matches.get_flag("down2"),
         ),
-        _ => panic!("The TUI editor has not been implemented yet."),
+        Some(("import", sub_matches)) => import(
+            sub_matches.get_one::<PathBuf>("INPUT").cloned(),
+            parse_import_format(sub_matches)?,
+            match sub_matches.get_one::<PathBuf>("output") {
+                Some(path) => path.clone(),
+                None => match_result!(
+                    std::env::current_dir(),
+                    CodeComponent::DocumentPath,
+                    format!("Couldn't read your current directory.")
+                ),
+            },
+            sub_matches.get_flag("force"),
+        ),
+        Some(("export", sub_matches)) => export(
+            match sub_matches.get_one::<String>("ITEM_PATH") {
+                Some(_) => Some(parse_item_path_arg(sub_matches)?),
+                None => None,
+            },
+            parse_export_format(sub_matches)?,
+            parse_down(sub_matches),
+        ),
+        _ => tui(parse_file_path(&matches)?, parse_down(&matches)),
     }
 }
 
@@ -141,19 +232,55 @@ fn parse_item_path_arg(matches: &clap::ArgMatches) -> Result<ItemPath, Error> {
 }
 
 fn parse_output_format(matches: &clap::ArgMatches) -> Result<RenderFormat, Error> {
+    let Some(format) = matches.get_one::<String>("format") else {
+        return Ok(settings::get().format.clone());
+    };
+
+    match RenderFormat::from_name(format) {
+        Some(format) => Ok(format),
+        None => Err(propagate!(
+            CodeComponent::Main,
+            format!("Unrecognized vale for --format: '{}'", format)
+        )),
+    }
+}
+
+/// Whether to search down, which is the config's default unless `--down` or `--up` is given.
+fn parse_down(matches: &clap::ArgMatches) -> bool {
+    if matches.get_flag("up") {
+        false
+    } else {
+        matches.get_flag("down") || settings::get().down
+    }
+}
+
+fn parse_import_format(matches: &clap::ArgMatches) -> Result<ImportFormat, Error> {
     let format = &matches
         .get_one::<String>("format")
         .expect("Format must be specified, but there should have been a default value.")[..];
 
     match format {
-        "html" => Ok(RenderFormat::HTML),
-        "html-class" => Ok(RenderFormat::HtmlClass),
-        "pango" => Ok(RenderFormat::Pango),
-        "plain" => Ok(RenderFormat::Plain),
-        "ansi" => Ok(RenderFormat::ANSI),
+        "json" => Ok(ImportFormat::Json),
+        "todotxt" => Ok(ImportFormat::TodoTxt),
+        "ics" => Ok(ImportFormat::Ics),
         _ => Err(propagate!(
             CodeComponent::Main,
-            format!("Unrecognized vale for --format: '{}'", format)
+            format!("Unrecognized value for --format: '{}'", format)
+        )),
+    }
+}
+
+fn parse_export_format(matches: &clap::ArgMatches) -> Result<ExportFormat, Error> {
+    let format = &matches
+        .get_one::<String>("to")
+        .expect("Format must be specified, but there should have been a default value.")[..];
+
+    match format {
+        "todotxt" => Ok(ExportFormat::TodoTxt),
+        "ics" => Ok(ExportFormat::Ics),
+        _ => Err(propagate!(
+            CodeComponent::Main,
+            format!("Unrecognized value for --to: '{}'", format)
         )),
     }
 }
@@ -171,6 +298,54 @@ fn parse_date(matches: &clap::ArgMatches) -> Result<Option<Date>, Error> {
     }
 }
 
+/// `Some(None)` means that the user asked for the item to not repeat.
+fn parse_recurrence(matches: &clap::ArgMatches) -> Result<Option<Option<Recurrence>>, Error> {
+    let value = matches.get_one::<String>("repeat");
+
+    match value {
+        Some(val) if val == "never" => Ok(Some(None)),
+        Some(val) => Ok(Some(Some(match_error!(
+            Recurrence::parse(val),
+            CodeComponent::Main,
+            format!("Couldn't parse input as a repeating rule. Got '{}'", val)
+        )))),
+        _ => Ok(None),
+    }
+}
+
+fn parse_tag_filter(matches: &clap::ArgMatches) -> TagFilter {
+    let tags = |id: &str| {
+        matches
+            .get_many::<String>(id)
+            .map(|values| values.cloned().collect::<Vec<String>>())
+            .unwrap_or_default()
+    };
+
+    TagFilter::new(tags("tag"), tags("exclude-tag"))
+}
+
+fn parse_query(matches: &clap::ArgMatches) -> Result<Option<Query>, Error> {
+    match matches.get_one::<String>("query") {
+        Some(query) => Ok(Some(match_error!(
+            Query::parse(query),
+            CodeComponent::Main,
+            format!("Couldn't parse the query '{}'.", query)
+        ))),
+        None => Ok(None),
+    }
+}
+
+fn parse_sort(matches: &clap::ArgMatches) -> Result<Option<SortSpec>, Error> {
+    match matches.get_one::<String>("sort") {
+        Some(sort) => Ok(Some(match_error!(
+            SortSpec::parse(sort),
+            CodeComponent::Main,
+            format!("Couldn't parse the sort order '{}'.", sort)
+        ))),
+        None => Ok(None),
+    }
+}
+
 fn parse_file_path(matches: &clap::ArgMatches) -> Result<PathBuf, Error> {
     Ok(matches
         .get_one::<PathBuf>("FILE_PATH")
@@ -16,10 +16,28 @@ pub enum Color {
     Default,
 }
 
+impl Color {
+    /// Finds a color by the name that is used for it in the config file, like `red` or `default`.
+    pub fn from_name(name: &str) -> Option<Color> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "black" => Some(Color::Black),
+            "red" => Some(Color::Red),
+            "green" => Some(Color::Green),
+            "yellow" => Some(Color::Yellow),
+            "blue" => Some(Color::Blue),
+            "magenta" => Some(Color::Magenta),
+            "cyan" => Some(Color::Cyan),
+            "white" => Some(Color::White),
+            "default" | "none" => Some(Color::Default),
+            _ => None,
+        }
+    }
+}
+
 impl Render for Color {
     fn render(self, format: &RenderFormat) -> String {
         match format {
-            RenderFormat::Plain => "".to_string(),
+            RenderFormat::Plain | RenderFormat::Json => "".to_string(),
             RenderFormat::ANSI => {
                 let code = match self {
                     Color::Black => 30,
@@ -19,8 +19,10 @@ impl OutputLine {
 
     pub fn newline(format: &RenderFormat) -> &'static str {
         match format {
-            RenderFormat::Plain | RenderFormat::ANSI | RenderFormat::Pango => return "\n",
-            RenderFormat::HTML | RenderFormat::HtmlClass => return "<br>",
+            RenderFormat::Plain | RenderFormat::ANSI | RenderFormat::Pango | RenderFormat::Json => {
+                "\n"
+            }
+            RenderFormat::HTML | RenderFormat::HtmlClass => "<br>",
         }
     }
 }
@@ -11,6 +11,24 @@ pub enum RenderFormat {
     HTML,
     HtmlClass,
     Pango,
+    /// Structured output for other programs. Anything that doesn't have a JSON representation is
+    /// rendered as plain text.
+    Json,
+}
+
+impl RenderFormat {
+    /// Finds a format by the name that is used for it in `--format` and the config file.
+    pub fn from_name(name: &str) -> Option<RenderFormat> {
+        match name {
+            "html" => Some(RenderFormat::HTML),
+            "html-class" => Some(RenderFormat::HtmlClass),
+            "pango" => Some(RenderFormat::Pango),
+            "plain" => Some(RenderFormat::Plain),
+            "ansi" => Some(RenderFormat::ANSI),
+            "json" => Some(RenderFormat::Json),
+            _ => None,
+        }
+    }
 }
 
 pub trait Render {
@@ -35,7 +35,7 @@ impl OutputSegment {
 impl Render for OutputSegment {
     fn render(self, format: &RenderFormat) -> String {
         match format {
-            RenderFormat::ANSI | RenderFormat::Plain => format!(
+            RenderFormat::ANSI | RenderFormat::Plain | RenderFormat::Json => format!(
                 "{style}{color}{content}{nostyle}{nocolor}",
                 style = self.style.render(&format),
                 color = self.color.render(&format),
@@ -69,7 +69,7 @@ impl Style {
 impl Render for Style {
     fn render(self, format: &RenderFormat) -> String {
         match format {
-            RenderFormat::Plain => "".to_string(),
+            RenderFormat::Plain | RenderFormat::Json => "".to_string(),
             RenderFormat::ANSI => {
                 let mut output = String::new();
 
@@ -0,0 +1,261 @@
+use crate::date::Date;
+use crate::todo::item::Item;
+use crate::todo::tags::TagFilter;
+
+pub mod parsing;
+
+/// A parsed query, like `priority>=3 and due<=friday and not completed and name~"deploy"`.
+///
+/// |------------------|----------------------------------|-----------------------|
+/// | field            | meaning                          | operators             |
+/// |------------------|----------------------------------|-----------------------|
+/// | priority         | the priority written on the item | = != < <= > >=        |
+/// | urgency          | how close the item's date is     | = != < <= > >=        |
+/// | due, date        | the item's date                  | = != < <= > >=        |
+/// | name             | the item's name                  | = != ~ !~             |
+/// | tag              | one of the item's tags           | = != ~ !~             |
+/// | id               | the item's ID                    | = !=                  |
+/// |------------------|----------------------------------|-----------------------|
+///
+/// `completed`, `archived`, `repeating` and `due` can also be used on their own as flags, and
+/// everything can be combined with `and`, `or`, `not` and parentheses.
+#[derive(Debug, Clone)]
+pub struct Query {
+    expression: Expression,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expression {
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Comparison(Field, Operator, Value),
+    Flag(Flag),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Priority,
+    Urgency,
+    Due,
+    Name,
+    Tag,
+    Id,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Contains,
+    NotContains,
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(i64),
+    Date(Date),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flag {
+    Completed,
+    Archived,
+    Repeating,
+    Due,
+}
+
+impl Query {
+    /// Whether the item itself matches the query, ignoring its children.
+    pub fn matches(&self, item: &Item) -> bool {
+        self.expression.matches(item)
+    }
+
+    /// Whether a filter should remove the item. Items are kept if they match, or if one of their
+    /// children does, so that the matching items still show up in context.
+    pub fn removes(&self, item: &Item) -> bool {
+        !self.matches(item) && item.items.iter().all(|child| self.removes(child))
+    }
+}
+
+impl Expression {
+    fn matches(&self, item: &Item) -> bool {
+        match self {
+            Expression::And(left, right) => left.matches(item) && right.matches(item),
+            Expression::Or(left, right) => left.matches(item) || right.matches(item),
+            Expression::Not(expression) => !expression.matches(item),
+            Expression::Flag(flag) => match flag {
+                Flag::Completed => item.completed,
+                Flag::Archived => item.archived,
+                Flag::Repeating => item.recurrence.is_some(),
+                Flag::Due => item.date.is_some(),
+            },
+            Expression::Comparison(field, operator, value) => {
+                Expression::compare(item, *field, *operator, value)
+            }
+        }
+    }
+
+    fn compare(item: &Item, field: Field, operator: Operator, value: &Value) -> bool {
+        match (field, value) {
+            (Field::Priority, Value::Number(number)) => {
+                operator.compare_ordering(item.priority.cmp(number))
+            }
+            (Field::Urgency, Value::Number(number)) => match item.urgency() {
+                Ok(Some(urgency)) => operator.compare_ordering(urgency.cmp(number)),
+                // Items without a date (or that aren't close) aren't urgent at all.
+                Ok(None) => operator.compare_ordering(0.cmp(number)),
+                Err(_) => false,
+            },
+            (Field::Due, Value::Date(date)) => match item.date.map(|due| due.distance(*date)) {
+                Some(Ok(distance)) => operator.compare_ordering(distance.cmp(&0)),
+                // Items without a date never match a date comparison.
+                _ => false,
+            },
+            (Field::Name, Value::Text(text)) => operator.compare_text(&item.full_name(), text),
+            (Field::Tag, Value::Text(text)) => {
+                let has_tag = match operator {
+                    Operator::Contains | Operator::NotContains => item
+                        .tags
+                        .iter()
+                        .any(|tag| tag.to_lowercase().contains(&text.to_lowercase())),
+                    _ => TagFilter::has_tag(item, text),
+                };
+                match operator {
+                    Operator::NotEqual | Operator::NotContains => !has_tag,
+                    _ => has_tag,
+                }
+            }
+            (Field::Id, Value::Text(text)) => {
+                let id = item.id.clone().unwrap_or_default();
+                operator.compare_text(&id, text.trim_start_matches('@'))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Operator {
+    fn compare_ordering(&self, ordering: std::cmp::Ordering) -> bool {
+        match self {
+            Operator::Equal => ordering.is_eq(),
+            Operator::NotEqual => ordering.is_ne(),
+            Operator::Less => ordering.is_lt(),
+            Operator::LessOrEqual => ordering.is_le(),
+            Operator::Greater => ordering.is_gt(),
+            Operator::GreaterOrEqual => ordering.is_ge(),
+            Operator::Contains | Operator::NotContains => false,
+        }
+    }
+
+    /// Text is compared without caring about case.
+    fn compare_text(&self, text: &str, value: &str) -> bool {
+        let text = text.to_lowercase();
+        let value = value.to_lowercase();
+
+        match self {
+            Operator::Equal => text == value,
+            Operator::NotEqual => text != value,
+            Operator::Contains => text.contains(&value),
+            Operator::NotContains => !text.contains(&value),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings;
+    use crate::todo::markdown::ListFormat;
+
+    fn item(line: &str) -> Item {
+        Item::parse(line, ListFormat::Todo)
+            .expect("The item should be read.")
+            .0
+    }
+
+    fn matches(query: &str, item: &Item) -> bool {
+        Query::parse(query)
+            .expect("The query should be parsed.")
+            .matches(item)
+    }
+
+    #[test]
+    fn items_without_a_date() {
+        let item = item("- [ ] Water the plants");
+
+        for query in [
+            "due<today",
+            "due<=today",
+            "due=today",
+            "due>today",
+            "due!=today",
+            "due",
+        ] {
+            assert!(!matches(query, &item), "{query}");
+        }
+        assert!(matches("not due<today", &item));
+        assert!(matches("not due", &item));
+
+        // They aren't urgent at all.
+        assert!(matches("urgency=0", &item));
+        assert!(matches("urgency<1", &item));
+        assert!(!matches("urgency>0", &item));
+    }
+
+    #[test]
+    fn dates_and_urgency() {
+        let days = settings::get().urgency_days;
+        let mut today = item("- [ ] Water the plants");
+        today.date = Some(Date::today().expect("Today should be known."));
+
+        assert!(matches("due=today and due<=today and due>=today", &today));
+        assert!(!matches("due<today or due>today or due!=today", &today));
+        assert!(matches(&format!("urgency={days}"), &today));
+
+        let overdue = item("- [ ] \\1-Jan-2020\\ Renew the passport");
+        assert!(matches("due<today", &overdue));
+        assert!(matches(&format!("urgency>{days}"), &overdue));
+
+        // Items that aren't close yet aren't urgent either.
+        let later = item("- [ ] \\1-Jan-2100\\ Plant a tree");
+        assert!(matches("due>today and due=1-Jan-2100", &later));
+        assert!(matches("urgency=0", &later));
+    }
+
+    #[test]
+    fn other_fields() {
+        let item = item("- [x] \\3\\@x83r0\\ Deploy the API +work @office");
+
+        assert!(matches("priority=3 and priority>=2 and priority<4", &item));
+        assert!(!matches("priority>3", &item));
+        assert!(matches("name~deploy and name!~install", &item));
+        assert!(matches("name=\"deploy the api +work @office\"", &item));
+        assert!(matches("tag=work and tag=+work and tag~OFF", &item));
+        assert!(matches("tag!=home and tag!~ho", &item));
+        assert!(matches("id=@X83R0 and id=x83r0 and id!=other", &item));
+        assert!(matches(
+            "completed and not archived and not repeating",
+            &item
+        ));
+    }
+
+    #[test]
+    fn parents_of_matching_items_are_kept() {
+        let mut parent = item("- [ ] Garden");
+        parent.items.push(item("- [ ] \\5\\ Replant it"));
+        parent.items.push(item("- [ ] Water it"));
+        let query = Query::parse("priority=5").expect("The query should be parsed.");
+
+        assert!(!query.matches(&parent));
+        assert!(!query.removes(&parent));
+        assert!(!query.removes(&parent.items[0]));
+        assert!(query.removes(&parent.items[1]));
+    }
+}
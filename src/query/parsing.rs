@@ -0,0 +1,474 @@
+use crate::date::Date;
+use crate::error::{CodeComponent::QueryParser, Error};
+use crate::query::{Expression, Field, Flag, Operator, Query, Value};
+use crate::{match_error, propagate};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    OpenParen,
+    CloseParen,
+    Operator(String),
+    Word(String),
+    Quoted(String),
+}
+
+impl Token {
+    fn display(&self) -> String {
+        match self {
+            Token::OpenParen => "(".to_string(),
+            Token::CloseParen => ")".to_string(),
+            Token::Operator(operator) => operator.clone(),
+            Token::Word(word) => word.clone(),
+            Token::Quoted(text) => format!("\"{}\"", text),
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, Error> {
+        let tokens = match_error!(
+            tokenize(input),
+            QueryParser,
+            format!("Could not split up the query '{input}'.")
+        );
+
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expression = match_error!(
+            parser.parse_or(),
+            QueryParser,
+            format!("Could not parse the query '{input}'.")
+        );
+
+        if let Some(token) = parser.peek() {
+            return Err(propagate!(
+                QueryParser,
+                format!(
+                    "Did not expect '{}' in the query '{input}'. Use 'and' or 'or' to combine conditions.",
+                    token.display()
+                )
+            ));
+        }
+
+        Ok(Query { expression })
+    }
+}
+
+fn is_operator_character(character: char) -> bool {
+    matches!(character, '=' | '!' | '<' | '>' | '~')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut characters = input.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match character {
+            ' ' | '\t' | '\n' => {}
+            '(' => tokens.push(Token::OpenParen),
+            ')' => tokens.push(Token::CloseParen),
+            '"' => {
+                let mut text = String::new();
+                let mut closed = false;
+                while let Some(character) = characters.next() {
+                    match character {
+                        '"' => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' => {
+                            if let Some(escaped) = characters.next() {
+                                text.push(escaped);
+                            }
+                        }
+                        _ => text.push(character),
+                    }
+                }
+                if !closed {
+                    return Err(propagate!(
+                        QueryParser,
+                        format!("The quote before '{text}' is never closed.")
+                    ));
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            _ if is_operator_character(character) => {
+                let mut operator = character.to_string();
+                while let Some(next) = characters.peek()
+                    && is_operator_character(*next)
+                {
+                    operator.push(*next);
+                    characters.next();
+                }
+                tokens.push(Token::Operator(operator));
+            }
+            _ => {
+                let mut word = character.to_string();
+                while let Some(next) = characters.peek()
+                    && !next.is_whitespace()
+                    && !is_operator_character(*next)
+                    && !matches!(next, '(' | ')' | '"')
+                {
+                    word.push(*next);
+                    characters.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn next_is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.parse_and()?;
+
+        while self.next_is_keyword("or") {
+            self.next();
+            let right = match_error!(
+                self.parse_and(),
+                QueryParser,
+                format!("Could not parse the right side of an 'or'.")
+            );
+            expression = Expression::Or(Box::new(expression), Box::new(right));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.parse_not()?;
+
+        while self.next_is_keyword("and") {
+            self.next();
+            let right = match_error!(
+                self.parse_not(),
+                QueryParser,
+                format!("Could not parse the right side of an 'and'.")
+            );
+            expression = Expression::And(Box::new(expression), Box::new(right));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, Error> {
+        if self.next_is_keyword("not") {
+            self.next();
+            let expression = match_error!(
+                self.parse_not(),
+                QueryParser,
+                format!("Could not parse what comes after 'not'.")
+            );
+            return Ok(Expression::Not(Box::new(expression)));
+        }
+
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, Error> {
+        match self.next() {
+            Some(Token::OpenParen) => {
+                let expression = match_error!(
+                    self.parse_or(),
+                    QueryParser,
+                    format!("Could not parse the inside of the parentheses.")
+                );
+                match self.next() {
+                    Some(Token::CloseParen) => Ok(expression),
+                    _ => Err(propagate!(
+                        QueryParser,
+                        format!("Expected a ')' to close the parentheses.")
+                    )),
+                }
+            }
+            Some(Token::Word(word)) => {
+                if let Some(Token::Operator(_)) = self.peek() {
+                    return self.parse_comparison(&word);
+                }
+
+                match word.to_ascii_lowercase().as_str() {
+                    "completed" | "done" => Ok(Expression::Flag(Flag::Completed)),
+                    "archived" => Ok(Expression::Flag(Flag::Archived)),
+                    "repeating" | "recurring" => Ok(Expression::Flag(Flag::Repeating)),
+                    "due" | "date" => Ok(Expression::Flag(Flag::Due)),
+                    _ => Err(propagate!(
+                        QueryParser,
+                        format!(
+                            "'{word}' is not a condition. Use a comparison like 'priority>=3', or one of 'completed', 'archived', 'repeating' or 'due'."
+                        )
+                    )),
+                }
+            }
+            Some(token) => Err(propagate!(
+                QueryParser,
+                format!("Expected a condition, but got '{}'.", token.display())
+            )),
+            None => Err(propagate!(
+                QueryParser,
+                format!("Expected a condition, but the query ended.")
+            )),
+        }
+    }
+
+    fn parse_comparison(&mut self, field_name: &str) -> Result<Expression, Error> {
+        let field = match field_name.to_ascii_lowercase().as_str() {
+            "priority" => Field::Priority,
+            "urgency" => Field::Urgency,
+            "due" | "date" => Field::Due,
+            "name" => Field::Name,
+            "tag" => Field::Tag,
+            "id" => Field::Id,
+            _ => {
+                return Err(propagate!(
+                    QueryParser,
+                    format!(
+                        "Unknown field '{field_name}'. Use one of 'priority', 'urgency', 'due', 'name', 'tag' or 'id'."
+                    )
+                ));
+            }
+        };
+
+        let operator_text = match self.next() {
+            Some(Token::Operator(operator)) => operator,
+            _ => {
+                return Err(propagate!(
+                    QueryParser,
+                    format!("Expected an operator after '{field_name}'.")
+                ));
+            }
+        };
+        let operator = match operator_text.as_str() {
+            "=" | "==" => Operator::Equal,
+            "!=" => Operator::NotEqual,
+            "<" => Operator::Less,
+            "<=" => Operator::LessOrEqual,
+            ">" => Operator::Greater,
+            ">=" => Operator::GreaterOrEqual,
+            "~" => Operator::Contains,
+            "!~" => Operator::NotContains,
+            _ => {
+                return Err(propagate!(
+                    QueryParser,
+                    format!("Unknown operator '{operator_text}'.")
+                ));
+            }
+        };
+
+        let text = match self.next() {
+            Some(Token::Word(word)) => word,
+            Some(Token::Quoted(text)) => text,
+            _ => {
+                return Err(propagate!(
+                    QueryParser,
+                    format!("Expected a value after '{field_name}{operator_text}'.")
+                ));
+            }
+        };
+
+        let ordered = !matches!(operator, Operator::Contains | Operator::NotContains);
+        let value = match field {
+            Field::Priority | Field::Urgency if ordered => match text.parse::<i64>() {
+                Ok(number) => Value::Number(number),
+                Err(_) => {
+                    return Err(propagate!(
+                        QueryParser,
+                        format!("'{field_name}' needs to be compared to a number, not '{text}'.")
+                    ));
+                }
+            },
+            Field::Due if ordered => Value::Date(match_error!(
+                parse_date(&text),
+                QueryParser,
+                format!("'{field_name}' needs to be compared to a date, not '{text}'.")
+            )),
+            Field::Name | Field::Tag
+                if matches!(
+                    operator,
+                    Operator::Equal
+                        | Operator::NotEqual
+                        | Operator::Contains
+                        | Operator::NotContains
+                ) =>
+            {
+                Value::Text(text)
+            }
+            Field::Id if matches!(operator, Operator::Equal | Operator::NotEqual) => {
+                Value::Text(text)
+            }
+            _ => {
+                return Err(propagate!(
+                    QueryParser,
+                    format!("'{field_name}' can't be compared with '{operator_text}'.")
+                ));
+            }
+        };
+
+        Ok(Expression::Comparison(field, operator, value))
+    }
+}
+
+/// The same dates that work everywhere else, plus "today".
+fn parse_date(text: &str) -> Result<Date, Error> {
+    if text.eq_ignore_ascii_case("today") {
+        return Date::today();
+    }
+
+    Ok(match_error!(
+        Date::from(text),
+        QueryParser,
+        format!("Could not parse the date '{text}'.")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An expression written out with parentheses around everything that is combined, so that
+    /// which parts go together can be checked.
+    fn shape(expression: &Expression) -> String {
+        match expression {
+            Expression::And(left, right) => format!("({} and {})", shape(left), shape(right)),
+            Expression::Or(left, right) => format!("({} or {})", shape(left), shape(right)),
+            Expression::Not(expression) => format!("not {}", shape(expression)),
+            Expression::Comparison(field, operator, value) => format!(
+                "{field:?} {operator:?} {}",
+                match value {
+                    Value::Number(number) => number.to_string(),
+                    Value::Date(date) => date.iso(),
+                    Value::Text(text) => format!("{text:?}"),
+                }
+            ),
+            Expression::Flag(flag) => format!("{flag:?}"),
+        }
+    }
+
+    fn parse(input: &str) -> String {
+        shape(
+            &Query::parse(input)
+                .expect("The query should be parsed.")
+                .expression,
+        )
+    }
+
+    #[test]
+    fn not_comes_before_and_which_comes_before_or() {
+        assert_eq!(
+            parse("completed or archived and not repeating"),
+            "(Completed or (Archived and not Repeating))"
+        );
+        assert_eq!(
+            parse("not completed and archived or repeating and due"),
+            "((not Completed and Archived) or (Repeating and Due))"
+        );
+        assert_eq!(
+            parse("(completed or archived) and not (repeating or due)"),
+            "((Completed or Archived) and not (Repeating or Due))"
+        );
+        assert_eq!(
+            parse("not not done AND archived Or recurring"),
+            "((not not Completed and Archived) or Repeating)"
+        );
+        assert_eq!(
+            parse("done or archived or due"),
+            "((Completed or Archived) or Due)"
+        );
+    }
+
+    #[test]
+    fn quoted_values() {
+        assert_eq!(
+            parse("name~\"deploy (the api) and or\""),
+            "Name Contains \"deploy (the api) and or\""
+        );
+        assert_eq!(
+            parse("name=\"say \\\"hi\\\" \\\\ bye\""),
+            "Name Equal \"say \\\"hi\\\" \\\\ bye\""
+        );
+        assert_eq!(parse("tag=\"\""), "Tag Equal \"\"");
+        assert!(Query::parse("name=\"never closed").is_err());
+    }
+
+    #[test]
+    fn each_field_takes_its_operators() {
+        let operators = ["=", "==", "!=", "<", "<=", ">", ">=", "~", "!~"];
+        let fields = [
+            ("priority", "3", vec!["=", "==", "!=", "<", "<=", ">", ">="]),
+            ("urgency", "3", vec!["=", "==", "!=", "<", "<=", ">", ">="]),
+            (
+                "due",
+                "6/13/2026",
+                vec!["=", "==", "!=", "<", "<=", ">", ">="],
+            ),
+            (
+                "date",
+                "6/13/2026",
+                vec!["=", "==", "!=", "<", "<=", ">", ">="],
+            ),
+            ("name", "deploy", vec!["=", "==", "!=", "~", "!~"]),
+            ("tag", "+work", vec!["=", "==", "!=", "~", "!~"]),
+            ("id", "@x83r0", vec!["=", "==", "!="]),
+        ];
+
+        for (field, value, allowed) in fields {
+            for operator in operators {
+                let query = format!("{field}{operator}{value}");
+                assert_eq!(
+                    Query::parse(&query).is_ok(),
+                    allowed.contains(&operator),
+                    "{query}"
+                );
+            }
+        }
+
+        assert_eq!(parse("PRIORITY>=3"), "Priority GreaterOrEqual 3");
+        assert_eq!(parse("urgency < 2"), "Urgency Less 2");
+        assert_eq!(parse("date==6/13/2026"), "Due Equal 2026-06-13");
+        assert_eq!(parse("tag!~work"), "Tag NotContains \"work\"");
+        assert_eq!(parse("id!=@x83r0"), "Id NotEqual \"@x83r0\"");
+    }
+
+    #[test]
+    fn mistakes_are_errors() {
+        for query in [
+            "",
+            "priority",
+            "priority>=",
+            "priority>=high",
+            "due<someday",
+            "size>3",
+            "priority=>3",
+            "completed archived",
+            "(completed or archived",
+            "completed)",
+            "not",
+            "completed and",
+            "soon",
+        ] {
+            assert!(Query::parse(query).is_err(), "{query}");
+        }
+    }
+}
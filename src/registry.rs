@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CodeComponent, CodeComponent::Registry as RegistryComponent, Error};
+use crate::index::{Index, IndexEntry};
+use crate::todo::path::{ItemPath, ListQualifier};
+use crate::{match_error, match_result, propagate, search_paths, storage};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The lists that have been registered with `todo register`, so that they can be found by name from
+/// anywhere. It is kept in the data directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Registry {
+    lists: Vec<PathBuf>,
+}
+
+impl Registry {
+    pub fn load() -> Result<Registry, Error> {
+        let path = match_error!(
+            registry_path(),
+            RegistryComponent,
+            format!("Could not find the registry.")
+        );
+        if !fs::exists(&path).unwrap_or(false) {
+            return Ok(Registry::default());
+        }
+
+        let content = match_result!(
+            fs::read_to_string(&path),
+            RegistryComponent,
+            format!("Could not read the registry at '{}'.", path.display())
+        );
+        Ok(match_result!(
+            serde_json::from_str(&content),
+            RegistryComponent,
+            format!("Could not parse the registry at '{}'.", path.display())
+        ))
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let path = match_error!(
+            registry_path(),
+            RegistryComponent,
+            format!("Could not find the registry.")
+        );
+        let content = match_result!(
+            serde_json::to_string_pretty(self),
+            RegistryComponent,
+            format!("Could not serialize the registry.")
+        ) + "\n";
+
+        if storage::is_dry_run() {
+            storage::print_diff(
+                &path,
+                fs::read_to_string(&path).ok().as_deref(),
+                Some(&content),
+            );
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            match_result!(
+                fs::create_dir_all(parent),
+                RegistryComponent,
+                format!("Could not create the directory '{}'.", parent.display())
+            );
+        }
+        match_error!(
+            storage::write_atomic(&path, &content),
+            RegistryComponent,
+            format!("Could not write the registry at '{}'.", path.display())
+        );
+
+        Ok(())
+    }
+
+    /// Adds the list in a directory, and gives back its name. Two registered lists can't have the
+    /// same name and alias, since then only their directories could tell them apart.
+    pub fn register(&mut self, path: &Path) -> Result<String, Error> {
+        let path = match_result!(
+            path.canonicalize(),
+            RegistryComponent,
+            format!("Could not get the canonical path of '{}'", path.display())
+        );
+        if !match_error!(
+            search_paths::has_todo_list(&path),
+            RegistryComponent,
+            format!("Could not check for a list in '{}'.", path.display())
+        ) {
+            return Err(propagate!(
+                RegistryComponent,
+                format!("There isn't a list in '{}'.", path.display())
+            ));
+        }
+
+        let entry = match_error!(
+            entries(std::slice::from_ref(&path)),
+            RegistryComponent,
+            format!("Could not read the list in '{}'.", path.display())
+        )
+        .remove(0);
+
+        if self.lists.contains(&path) {
+            return Ok(entry.name);
+        }
+        for (other_path, other) in match_error!(
+            self.lists(),
+            RegistryComponent,
+            format!("Could not read the registered lists.")
+        ) {
+            if other.is_some_and(|other| other.name == entry.name && other.alias == entry.alias) {
+                return Err(propagate!(
+                    RegistryComponent,
+                    format!(
+                        "The list at '{}' is already registered as '#{}'. Give one of them an '# alias', or unregister the other one first.",
+                        other_path.display(),
+                        entry.name
+                    )
+                ));
+            }
+        }
+
+        self.lists.push(path);
+        Ok(entry.name)
+    }
+
+    /// Removes a list by its directory, and says whether it was registered.
+    pub fn unregister(&mut self, path: &Path) -> bool {
+        let path = path.canonicalize().unwrap_or(path.to_path_buf());
+        let count = self.lists.len();
+        self.lists.retain(|other| *other != path);
+        self.lists.len() != count
+    }
+
+    /// Every registered directory, with what the index knows about its list. It is `None` if the
+    /// list doesn't exist anymore.
+    pub fn lists(&self) -> Result<Vec<(PathBuf, Option<IndexEntry>)>, Error> {
+        let existing = self
+            .lists
+            .iter()
+            .filter(|path| search_paths::has_todo_list(path).unwrap_or(false))
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        let mut existing_entries = match_error!(
+            entries(&existing),
+            RegistryComponent,
+            format!("Could not read the registered lists.")
+        )
+        .into_iter();
+
+        Ok(self
+            .lists
+            .iter()
+            .map(|path| {
+                let entry = if existing.contains(path) {
+                    existing_entries.next()
+                } else {
+                    None
+                };
+                (path.clone(), entry)
+            })
+            .collect())
+    }
+
+    /// Finds the registered list that a path means. It is an error if there is more than one.
+    pub fn find(&self, path: &ItemPath) -> Result<Option<PathBuf>, Error> {
+        let matches = match_error!(
+            self.lists(),
+            RegistryComponent,
+            format!("Could not read the registered lists.")
+        )
+        .into_iter()
+        .filter(|(directory, entry)| {
+            entry.as_ref().is_some_and(|entry| {
+                path.matches_list(&entry.name, entry.alias.as_deref())
+                    && match &path.qualifier {
+                        Some(ListQualifier::Directory(qualifier)) => qualifier == directory,
+                        _ => true,
+                    }
+            })
+        })
+        .map(|(directory, _)| directory)
+        .collect::<Vec<PathBuf>>();
+
+        match &matches[..] {
+            [] => Ok(None),
+            [path] => Ok(Some(path.clone())),
+            _ => Err(propagate!(
+                RegistryComponent,
+                format!(
+                    "More than one registered list is called '#{}':\n{}\nUse '#{}@<directory>/' or give one of them an '# alias' to pick one.",
+                    path.document,
+                    matches
+                        .iter()
+                        .map(|path| format!("  {}", path.display()))
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                    path.document
+                )
+            )),
+        }
+    }
+
+    /// The registered list that is closest above a directory (or in it), for paths that don't
+    /// name a list.
+    pub fn containing(&self, directory: &Path) -> Option<PathBuf> {
+        self.lists
+            .iter()
+            .filter(|path| directory.starts_with(path))
+            .max_by_key(|path| path.components().count())
+            .cloned()
+    }
+}
+
+/// What the index knows about the lists in some directories.
+fn entries(paths: &[PathBuf]) -> Result<Vec<IndexEntry>, Error> {
+    let mut index = Index::load();
+    let entries = match_error!(
+        index.read(paths),
+        RegistryComponent,
+        format!("Could not read the lists.")
+    );
+    // It is only a cache, so it doesn't matter if it can't be saved.
+    let _ = index.save();
+
+    Ok(entries.into_iter().map(|(entry, _)| entry).collect())
+}
+
+fn registry_path() -> Result<PathBuf, Error> {
+    Ok(match_error!(
+        search_paths::data_dir(),
+        RegistryComponent,
+        format!("Could not find the data directory.")
+    )
+    .join("registry.json"))
+}
@@ -1,33 +1,68 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{WalkBuilder, WalkState};
+
 use crate::error::{CodeComponent, Error};
+use crate::index::{Index, IndexEntry};
+use crate::registry::Registry;
+use crate::settings::SearchSettings;
 use crate::todo::document::Document;
-use crate::{match_error, match_result, propagate};
+use crate::todo::path::{ItemPath, ListQualifier};
+use crate::{match_error, match_result, propagate, settings};
 
+use std::sync::Mutex;
 use std::{fs, path::Path, path::PathBuf};
 
 pub fn has_todo_list(path: &Path) -> Result<bool, Error> {
-    Ok(match_result!(
-        fs::exists(path.join(".todo")),
+    Ok(match_error!(
+        list_file(path),
         CodeComponent::FileSearcher,
-        format!(
-            "Could not check for '.todo' file at path '{}'.",
-            path.display()
-        )
-    ))
+        format!("Could not check for a list at path '{}'.", path.display())
+    )
+    .is_some())
+}
+
+/// The name of the file that the list in a directory is kept in, if there is one. If there is more
+/// than one, the one that is first in the config's `files` is used.
+pub fn list_file(path: &Path) -> Result<Option<String>, Error> {
+    for name in &settings::get().files {
+        if match_result!(
+            fs::exists(path.join(name)),
+            CodeComponent::FileSearcher,
+            format!(
+                "Could not check for '{}' file at path '{}'.",
+                name,
+                path.display()
+            )
+        ) {
+            return Ok(Some(name.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The name of the file that new lists are created in.
+pub fn default_list_file() -> String {
+    settings::get()
+        .files
+        .first()
+        .cloned()
+        .unwrap_or(".todo".to_string())
 }
 
 /// Search up through the path's ancestors
 pub fn search_up(path: &PathBuf) -> Result<Vec<PathBuf>, Error> {
     let mut lists: Vec<PathBuf> = vec![];
 
-    for ancestor in path.clone().ancestors() {
-        let full_path = match_result!(
-            PathBuf::from(ancestor).canonicalize(),
-            CodeComponent::FileSearcher,
-            format!(
-                "Could not get the canonical path of '{}'",
-                ancestor.display()
-            )
-        );
+    // Relative paths like './' run out of ancestors before reaching the root.
+    let start = match_result!(
+        path.canonicalize(),
+        CodeComponent::FileSearcher,
+        format!("Could not get the canonical path of '{}'", path.display())
+    );
+
+    for ancestor in start.ancestors() {
+        let full_path = PathBuf::from(ancestor);
         if match_error!(
             has_todo_list(&full_path),
             CodeComponent::FileSearcher,
@@ -40,73 +75,217 @@ pub fn search_up(path: &PathBuf) -> Result<Vec<PathBuf>, Error> {
     Ok(lists.into_iter().rev().collect::<Vec<PathBuf>>())
 }
 
-/// Search recursively down the file tree.
+/// Search recursively down the file tree, with a few threads at once. Which directories are
+/// searched is set in the config, and by `.gitignore` and `.todoignore` files.
 pub fn search_down(path: &PathBuf) -> Result<Vec<PathBuf>, Error> {
-    match fs::read_dir(path) {
-        Ok(contents) => {
-            let mut lists: Vec<PathBuf> = vec![];
-
-            for item in contents {
-                if let Ok(item) = item {
-                    let metadata = match fs::metadata(item.path()) {
-                        Ok(val) => val,
-                        Err(_) => {
-                            continue;
-                        }
-                    };
-
-                    let file_type = metadata.file_type();
-
-                    if file_type.is_symlink() {
-                        continue;
-                    }
-
-                    /*
-                    Exclude directories like `.local` or `.config`. Otherwise, the search takes a really
-                    long time. I should come up with a better heuristic at some point.
-                    */
-                    if !(item.file_name() == ".todo")
-                        && item
-                            .file_name()
-                            .into_string()
-                            .unwrap_or("".to_string()) // Don't love this, but it works
-                            .starts_with(".")
-                    {
-                        continue;
-                    }
-
-                    if file_type.is_dir() {
-                        lists.append(&mut match_error!(
-                            search_down(&item.path()),
-                            CodeComponent::FileSearcher,
-                            format!("Could not read dir at path '{}'", item.path().display())
-                        ));
-                    }
-                }
+    let root = match_result!(
+        path.canonicalize(),
+        CodeComponent::FileSearcher,
+        format!("Could not read directory '{}'.", path.display())
+    );
+    if !root.is_dir() {
+        return Err(propagate!(
+            CodeComponent::FileSearcher,
+            format!("Could not read directory '{}'.", path.display())
+        ));
+    }
+
+    let lists = Mutex::new(vec![]);
+    match_error!(
+        walker(&root, &settings::get().search),
+        CodeComponent::FileSearcher,
+        format!("Could not search down from '{}'.", path.display())
+    )
+    .build_parallel()
+    .run(|| {
+        let lists = &lists;
+        Box::new(move |entry| {
+            // Directories that can't be read are skipped, like they would be by `find`.
+            if let Ok(entry) = entry
+                && has_todo_list(entry.path()).unwrap_or(false)
+            {
+                lists
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push(entry.into_path());
+            }
+            WalkState::Continue
+        })
+    });
+
+    // The threads find them in any order, so they are sorted to keep parents before children.
+    let mut lists = lists
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    lists.sort();
+    Ok(lists)
+}
+
+/// Sets up a walk over the directories under a path, following the search settings.
+fn walker(path: &Path, search: &SearchSettings) -> Result<WalkBuilder, Error> {
+    let include = match_error!(
+        Patterns::new(&search.include),
+        CodeComponent::FileSearcher,
+        format!("Could not read the include patterns.")
+    );
+    let exclude = match_error!(
+        Patterns::new(&search.exclude),
+        CodeComponent::FileSearcher,
+        format!("Could not read the exclude patterns.")
+    );
+    let hidden = search.hidden;
+    let root = path.to_path_buf();
+
+    let mut walker = WalkBuilder::new(path);
+    walker
+        .standard_filters(false)
+        .parents(true)
+        .git_ignore(search.gitignore)
+        .git_global(search.gitignore)
+        .git_exclude(search.gitignore)
+        // A `.gitignore` should work the same whether or not the directory is a repository.
+        .require_git(false)
+        .add_custom_ignore_filename(".todoignore")
+        .max_depth(search.max_depth)
+        .same_file_system(search.same_filesystem)
+        .filter_entry(move |entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+            // Only directories matter, since the `.todo` files are looked for directly (so that
+            // ignoring `.todo` files in git doesn't hide them).
+            if !entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_dir())
+            {
+                return false;
+            }
+
+            let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            if include.matches(relative) {
+                return true;
             }
+            // Hidden directories (like `.local` or `.config`) take a really long time to search.
+            // They're only gone into far enough to reach what is included in them.
+            if !hidden
+                && relative
+                    .components()
+                    .any(|part| part.as_os_str().to_string_lossy().starts_with("."))
+                && !include.leads_to(relative)
+                && !relative
+                    .ancestors()
+                    .any(|ancestor| include.matches(ancestor))
+            {
+                return false;
+            }
+            !exclude.matches(relative)
+        });
+
+    Ok(walker)
+}
 
-            if match_error!(
-                has_todo_list(path),
+/// Globs for directories, like `node_modules` or `packages/*/build`. A pattern without a slash
+/// matches a directory with that name anywhere, and one with a slash matches the path from where
+/// the search started.
+pub struct Patterns {
+    by_name: GlobSet,
+    by_path: GlobSet,
+    /// The directories that the patterns with a slash are in, like `.config` for `.config/notes`.
+    ancestors: GlobSet,
+}
+
+impl Patterns {
+    pub fn new(patterns: &[String]) -> Result<Patterns, Error> {
+        let mut by_name = GlobSetBuilder::new();
+        let mut by_path = GlobSetBuilder::new();
+        let mut ancestors = GlobSetBuilder::new();
+
+        for pattern in patterns {
+            let pattern = pattern.trim_end_matches("/");
+            let glob = match_result!(
+                GlobBuilder::new(pattern).literal_separator(true).build(),
                 CodeComponent::FileSearcher,
-                format!("Could not chech for '.todo' at path '{}'.", path.display())
-            ) {
-                lists.push(path.clone());
+                format!("'{}' isn't a valid pattern.", pattern)
+            );
+            if pattern.contains("/") {
+                by_path.add(glob);
+
+                let parts = pattern.split("/").collect::<Vec<&str>>();
+                for length in 1..parts.len() {
+                    let ancestor = parts[..length].join("/");
+                    ancestors.add(match_result!(
+                        GlobBuilder::new(&ancestor).literal_separator(true).build(),
+                        CodeComponent::FileSearcher,
+                        format!("'{}' isn't a valid pattern.", pattern)
+                    ));
+                }
+            } else {
+                by_name.add(glob);
             }
-            return Ok(lists.into_iter().rev().collect::<Vec<PathBuf>>());
         }
-        Err(err) => {
+
+        Ok(Patterns {
+            by_name: match_result!(
+                by_name.build(),
+                CodeComponent::FileSearcher,
+                format!("Could not build the patterns.")
+            ),
+            by_path: match_result!(
+                by_path.build(),
+                CodeComponent::FileSearcher,
+                format!("Could not build the patterns.")
+            ),
+            ancestors: match_result!(
+                ancestors.build(),
+                CodeComponent::FileSearcher,
+                "Could not build the patterns.".to_string()
+            ),
+        })
+    }
+
+    /// Whether a directory has something that a pattern matches in it, so it has to be searched
+    /// to find it.
+    pub fn leads_to(&self, relative: &Path) -> bool {
+        self.ancestors.is_match(relative)
+    }
+
+    pub fn matches(&self, relative: &Path) -> bool {
+        relative
+            .file_name()
+            .is_some_and(|name| self.by_name.is_match(name))
+            || self.by_path.is_match(relative)
+    }
+}
+
+/// Finds the list that a path means. A path with a directory (like `#Todo@~/work/api/`) always
+/// means the list in that directory. Otherwise the lists found by searching (and then the
+/// registered lists) are checked, and it is an error if more than one of them matches.
+pub fn find_list(path: &ItemPath, down: bool) -> Result<Document, Error> {
+    let name = match &path.qualifier {
+        Some(qualifier) => format!("#{}@{}", path.document, qualifier.display()),
+        None => format!("#{}", path.document),
+    };
+
+    if let Some(ListQualifier::Directory(directory)) = &path.qualifier {
+        let document = match_error!(
+            Document::from_path(directory),
+            CodeComponent::FileSearcher,
+            format!("Could not parse document at path '{}'", directory.display())
+        );
+        if !path.matches_list(&document.name, document.alias.as_deref()) {
             return Err(propagate!(
                 CodeComponent::FileSearcher,
                 format!(
-                    "Could not read directory '{dir_path}': '{err}'",
-                    dir_path = path.display()
+                    "The list in '{}' is called '#{}', not '#{}'.",
+                    directory.display(),
+                    document.name,
+                    path.document
                 )
             ));
         }
+        return Ok(document);
     }
-}
 
-pub fn find_list(name: &String, down: bool) -> Result<Document, Error> {
     let search_start = match_result!(
         std::fs::canonicalize(match_result!(
             std::env::current_dir(),
@@ -136,19 +315,183 @@ pub fn find_list(name: &String, down: bool) -> Result<Document, Error> {
         );
     }
 
-    for path in paths {
-        let list = match_error!(
-            Document::from_path(&path),
+    // The index knows the names of the lists that haven't changed, so only the one that is found
+    // has to be read.
+    let mut index = Index::load();
+    let entries = match_error!(
+        index.read(&paths),
+        CodeComponent::FileSearcher,
+        format!("Could not get the names of the lists.")
+    );
+    // It is only a cache, so it doesn't matter if it can't be saved.
+    let _ = index.save();
+
+    let mut matches = paths
+        .into_iter()
+        .zip(entries)
+        .filter(|(_, (entry, _))| path.matches_list(&entry.name, entry.alias.as_deref()))
+        .collect::<Vec<(PathBuf, (IndexEntry, Option<Document>))>>();
+
+    if matches.len() > 1 {
+        return Err(propagate!(
             CodeComponent::FileSearcher,
-            format!("Could not parse document at path '{}'", path.display())
-        );
-        if list.name == *name {
-            return Ok(list);
-        }
+            format!(
+                "More than one list is called '{}':\n{}\nUse '#{}@<directory>/' or give one of them an '# alias' to pick one.",
+                name,
+                matches
+                    .iter()
+                    .map(|(path, _)| format!("  {}", path.display()))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+                path.document
+            )
+        ));
+    }
+
+    if let Some((list_path, (_, document))) = matches.pop() {
+        return match document {
+            Some(document) => Ok(document),
+            None => Ok(match_error!(
+                Document::from_path(&list_path),
+                CodeComponent::FileSearcher,
+                format!("Could not parse document at path '{}'", list_path.display())
+            )),
+        };
+    }
+
+    // Lists that have been registered can be found from anywhere.
+    let registry = match_error!(
+        Registry::load(),
+        CodeComponent::FileSearcher,
+        format!("Could not load the registered lists.")
+    );
+    if let Some(list_path) = match_error!(
+        registry.find(path),
+        CodeComponent::FileSearcher,
+        format!("Could not look for '{name}' in the registered lists.")
+    ) {
+        return Ok(match_error!(
+            Document::from_path(&list_path),
+            CodeComponent::FileSearcher,
+            format!("Could not parse document at path '{}'", list_path.display())
+        ));
+    }
+
+    // A name like `#me@work` can be a list with an `@` in its name, rather than one with an alias.
+    if let Some(literal) = path.literal() {
+        return find_list(&literal, down);
     }
 
     Err(propagate!(
         CodeComponent::FileSearcher,
-        format!("No list called '#{name}'")
+        format!("No list called '{name}'")
     ))
 }
+
+/// Where `todo` keeps its own files, like the undo journal. This is `$XDG_DATA_HOME/todo-rs`, or
+/// `~/.local/share/todo-rs` if that isn't set.
+pub fn data_dir() -> Result<PathBuf, Error> {
+    if let Ok(data_home) = std::env::var("XDG_DATA_HOME")
+        && !data_home.is_empty()
+    {
+        return Ok(PathBuf::from(data_home).join("todo-rs"));
+    }
+
+    let home = match_result!(
+        std::env::var("HOME"),
+        CodeComponent::FileSearcher,
+        format!("Could not find your home directory.")
+    );
+    Ok(PathBuf::from(home).join(".local/share/todo-rs"))
+}
+
+/// Where `todo` looks for its config, which is `$XDG_CONFIG_HOME/todo-rs`, or `~/.config/todo-rs`
+/// if that isn't set.
+pub fn config_dir() -> Result<PathBuf, Error> {
+    if let Ok(config_home) = std::env::var("XDG_CONFIG_HOME")
+        && !config_home.is_empty()
+    {
+        return Ok(PathBuf::from(config_home).join("todo-rs"));
+    }
+
+    let home = match_result!(
+        std::env::var("HOME"),
+        CodeComponent::FileSearcher,
+        format!("Could not find your home directory.")
+    );
+    Ok(PathBuf::from(home).join(".config/todo-rs"))
+}
+
+/// Finds the `.todo.toml` project configs in the path's ancestors, like `search_up`, with the
+/// furthest one first.
+pub fn project_configs(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let start = match_result!(
+        path.canonicalize(),
+        CodeComponent::FileSearcher,
+        format!("Could not get the canonical path of '{}'", path.display())
+    );
+
+    let mut configs = vec![];
+    for ancestor in start.ancestors() {
+        let config = ancestor.join(".todo.toml");
+        if match_result!(
+            fs::exists(&config),
+            CodeComponent::FileSearcher,
+            format!("Could not check for '{}'.", config.display())
+        ) {
+            configs.push(config);
+        }
+    }
+
+    Ok(configs.into_iter().rev().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The directories with lists that a search from `root` finds, relative to it.
+    fn found(root: &Path, search: &SearchSettings) -> Vec<String> {
+        let mut lists = walker(root, search)
+            .expect("The walker should be set up.")
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| has_todo_list(entry.path()).unwrap_or(false))
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(root)
+                    .unwrap_or(entry.path())
+                    .display()
+                    .to_string()
+            })
+            .collect::<Vec<String>>();
+        lists.sort();
+        lists
+    }
+
+    #[test]
+    fn includes_in_hidden_directories_are_found() {
+        let root = std::env::temp_dir().join(format!("todo-search-{}", std::process::id()));
+        for directory in [
+            ".config/notes",
+            ".config/other",
+            ".notes",
+            ".hidden",
+            "visible",
+        ] {
+            fs::create_dir_all(root.join(directory)).expect("The directory should be made.");
+            fs::write(root.join(directory).join(".todo"), "# Todo\n")
+                .expect("The list should be written.");
+        }
+
+        let search = SearchSettings {
+            include: vec![".config/notes".to_string(), ".notes".to_string()],
+            ..SearchSettings::default()
+        };
+        let lists = found(&root, &search);
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(lists, [".config/notes", ".notes", "visible"]);
+    }
+}
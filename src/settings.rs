@@ -0,0 +1,346 @@
+use chrono::format::{Item as FormatItem, StrftimeItems};
+use serde::Deserialize;
+
+use crate::error::{CodeComponent, CodeComponent::Settings as SettingsComponent, Error};
+use crate::output::RenderFormat;
+use crate::output::color::Color;
+use crate::search_paths::Patterns;
+use crate::todo::sort::SortSpec;
+use crate::{match_error, match_result, propagate, search_paths};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// The settings for this run of `todo`, once they have been read.
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Everything that can be changed in the config files. Every part of `todo` reads these through
+/// `settings::get()`, rather than looking for the files itself.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// The output format when `--format` isn't given.
+    pub format: RenderFormat,
+    /// Whether to search down instead of up when `--down` isn't given.
+    pub down: bool,
+    /// The names of the files that lists are kept in, like `.todo` or `TODO.md`. If a directory
+    /// has more than one of them, the first one is used, and new lists use the first one too. Files
+    /// whose names end in `.md` are Markdown task lists.
+    pub files: Vec<String>,
+    pub search: SearchSettings,
+    /// The order for lists that don't have a `# sort` line, when `--sort` isn't given.
+    pub sort: SortSpec,
+    pub theme: Theme,
+    /// A `strftime` format (like `%Y-%m-%d`) for showing dates. Dates in files are always written
+    /// the same way, so that they can be read again.
+    pub date_format: Option<String>,
+    /// How many days before an item's date it starts getting more urgent.
+    pub urgency_days: i64,
+}
+
+/// Which directories are searched when searching down.
+#[derive(Debug, Clone)]
+pub struct SearchSettings {
+    /// Whether to skip what `.gitignore` files (and git's other ignore files) ignore.
+    pub gitignore: bool,
+    /// Whether to search directories whose names start with a dot.
+    pub hidden: bool,
+    /// Directories that are searched even if they are hidden or excluded.
+    pub include: Vec<String>,
+    /// Directories that aren't searched, like `node_modules` or `target`.
+    pub exclude: Vec<String>,
+    /// How many directories down to go, where the starting directory is 0.
+    pub max_depth: Option<usize>,
+    /// Whether to stay on the same filesystem as the starting directory.
+    pub same_filesystem: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub tags: Color,
+    /// The colors for priorities (including urgency). An item gets the color of the threshold
+    /// furthest from 0 that its priority reaches, so `3` is used for 3 and up, and `-3` for -3 and
+    /// down.
+    pub priorities: BTreeMap<i64, Color>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            format: RenderFormat::ANSI,
+            down: false,
+            files: vec![".todo".to_string()],
+            search: SearchSettings::default(),
+            sort: SortSpec::default(),
+            theme: Theme::default(),
+            date_format: None,
+            urgency_days: 7,
+        }
+    }
+}
+
+impl Default for SearchSettings {
+    fn default() -> SearchSettings {
+        SearchSettings {
+            gitignore: true,
+            hidden: false,
+            include: vec![],
+            exclude: vec![],
+            max_depth: None,
+            same_filesystem: true,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            tags: Color::Cyan,
+            priorities: BTreeMap::from([
+                (-5, Color::Green),
+                (-3, Color::Blue),
+                (-1, Color::Cyan),
+                (0, Color::Default),
+                (1, Color::Yellow),
+                (3, Color::Magenta),
+                (5, Color::Red),
+            ]),
+        }
+    }
+}
+
+impl Theme {
+    pub fn priority_color(&self, priority: i64) -> Color {
+        let threshold = if priority >= 0 {
+            self.priorities.range(0..=priority).next_back()
+        } else {
+            self.priorities.range(priority..0).next()
+        };
+
+        threshold
+            .map(|(_, color)| color.clone())
+            .unwrap_or(Color::Default)
+    }
+}
+
+/// What can be written in a config file. Everything is optional, so that a project's config only
+/// has to set what it changes.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct SettingsFile {
+    format: Option<String>,
+    down: Option<bool>,
+    files: Option<Vec<String>>,
+    search: Option<SearchFile>,
+    sort: Option<String>,
+    date_format: Option<String>,
+    urgency: Option<UrgencyFile>,
+    colors: Option<ThemeFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct SearchFile {
+    gitignore: Option<bool>,
+    hidden: Option<bool>,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    max_depth: Option<usize>,
+    same_filesystem: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct UrgencyFile {
+    days: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct ThemeFile {
+    tags: Option<String>,
+    priorities: Option<BTreeMap<String, String>>,
+}
+
+/// Reads the config files. The user's config (in `$XDG_CONFIG_HOME/todo-rs/config.toml`) is read
+/// first, and then each `.todo.toml` from the root down to the current directory, so that the
+/// nearest one wins.
+pub fn load() -> Result<&'static Settings, Error> {
+    if let Some(settings) = SETTINGS.get() {
+        return Ok(settings);
+    }
+
+    let mut settings = Settings::default();
+
+    if let Ok(config_dir) = search_paths::config_dir() {
+        let path = config_dir.join("config.toml");
+        if fs::exists(&path).unwrap_or(false) {
+            match_error!(
+                settings.apply_file(&path),
+                SettingsComponent,
+                format!("Could not read your config at '{}'.", path.display())
+            );
+        }
+    }
+
+    let current_dir = match_result!(
+        std::env::current_dir(),
+        SettingsComponent,
+        format!("Could not get the current directory.")
+    );
+    for path in match_error!(
+        search_paths::project_configs(&current_dir),
+        SettingsComponent,
+        format!("Could not look for project configs.")
+    ) {
+        match_error!(
+            settings.apply_file(&path),
+            SettingsComponent,
+            format!("Could not read the project config at '{}'.", path.display())
+        );
+    }
+
+    Ok(SETTINGS.get_or_init(|| settings))
+}
+
+/// The settings, or the defaults if they haven't been loaded (like if the config couldn't be read).
+pub fn get() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+impl Settings {
+    fn apply_file(&mut self, path: &Path) -> Result<(), Error> {
+        let content = match_result!(
+            fs::read_to_string(path),
+            SettingsComponent,
+            format!("Could not read '{}'.", path.display())
+        );
+        let file: SettingsFile = match_result!(
+            toml::from_str(&content),
+            SettingsComponent,
+            format!("'{}' isn't a valid config.", path.display())
+        );
+
+        if let Some(format) = file.format {
+            self.format = match RenderFormat::from_name(&format) {
+                Some(format) => format,
+                None => {
+                    return Err(propagate!(
+                        SettingsComponent,
+                        format!(
+                            "Unknown format '{format}'. Use ansi, plain, html, html-class, pango or json."
+                        )
+                    ));
+                }
+            };
+        }
+        if let Some(down) = file.down {
+            self.down = down;
+        }
+        if let Some(files) = file.files {
+            if files.is_empty() {
+                return Err(propagate!(
+                    SettingsComponent,
+                    "There has to be at least one file name in files.".to_string()
+                ));
+            }
+            if let Some(name) = files
+                .iter()
+                .find(|name| name.is_empty() || name.contains(['/', '\\']))
+            {
+                return Err(propagate!(
+                    SettingsComponent,
+                    format!("'{name}' isn't a file name, so lists can't be kept in it.")
+                ));
+            }
+            self.files = files;
+        }
+        if let Some(search) = file.search {
+            let settings = &mut self.search;
+            if let Some(gitignore) = search.gitignore {
+                settings.gitignore = gitignore;
+            }
+            if let Some(hidden) = search.hidden {
+                settings.hidden = hidden;
+            }
+            if let Some(include) = search.include {
+                match_error!(
+                    Patterns::new(&include),
+                    SettingsComponent,
+                    format!("Could not read search.include.")
+                );
+                settings.include = include;
+            }
+            if let Some(exclude) = search.exclude {
+                match_error!(
+                    Patterns::new(&exclude),
+                    SettingsComponent,
+                    format!("Could not read search.exclude.")
+                );
+                settings.exclude = exclude;
+            }
+            if let Some(max_depth) = search.max_depth {
+                settings.max_depth = Some(max_depth);
+            }
+            if let Some(same_filesystem) = search.same_filesystem {
+                settings.same_filesystem = same_filesystem;
+            }
+        }
+        if let Some(sort) = file.sort {
+            self.sort = match_error!(
+                SortSpec::parse(&sort),
+                SettingsComponent,
+                format!("Could not parse the sort order '{}'.", sort)
+            );
+        }
+        if let Some(date_format) = file.date_format {
+            // Formatting with a bad format would panic, so it is checked now.
+            if StrftimeItems::new(&date_format).any(|item| matches!(item, FormatItem::Error)) {
+                return Err(propagate!(
+                    SettingsComponent,
+                    format!("'{}' isn't a valid date format.", date_format)
+                ));
+            }
+            self.date_format = Some(date_format);
+        }
+        if let Some(days) = file.urgency.and_then(|urgency| urgency.days) {
+            if days < 0 {
+                return Err(propagate!(
+                    SettingsComponent,
+                    format!("The urgency can't start {} days before the date.", days)
+                ));
+            }
+            self.urgency_days = days;
+        }
+        if let Some(colors) = file.colors {
+            if let Some(tags) = colors.tags {
+                self.theme.tags = parse_color(&tags)?;
+            }
+            for (threshold, color) in colors.priorities.unwrap_or_default() {
+                let threshold = match_result!(
+                    threshold.trim().parse::<i64>(),
+                    SettingsComponent,
+                    format!("The priority '{}' isn't a number.", threshold)
+                );
+                self.theme
+                    .priorities
+                    .insert(threshold, parse_color(&color)?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_color(name: &str) -> Result<Color, Error> {
+    match Color::from_name(name) {
+        Some(color) => Ok(color),
+        None => Err(propagate!(
+            SettingsComponent,
+            format!(
+                "Unknown color '{name}'. Use black, red, green, yellow, blue, magenta, cyan, white or default."
+            )
+        )),
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::{CodeComponent, CodeComponent::Storage, Error};
+use crate::{diff, match_error, match_result, search_paths};
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// How many `Lock`s this process is holding, so that taking the lock again (like when an executor
+/// that holds it saves a document) doesn't wait for itself.
+static HELD: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether `--dry-run` was passed. When it is, nothing is written, and what would have been written
+/// is shown as a diff instead.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::SeqCst);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// Prints what writing a file would change, for `--dry-run`.
+pub fn print_diff(path: &Path, before: Option<&str>, after: Option<&str>) {
+    print!(
+        "{}",
+        diff::unified(&path.display().to_string(), before, after)
+    );
+}
+
+/// An advisory lock that is held while a command reads, changes and saves lists, so that two `todo`s
+/// that run at the same time don't overwrite each other's changes. It is let go when it is dropped.
+pub struct Lock {
+    _file: Option<File>,
+}
+
+impl Lock {
+    pub fn acquire() -> Result<Lock, Error> {
+        if HELD.load(Ordering::SeqCst) > 0 {
+            HELD.fetch_add(1, Ordering::SeqCst);
+            return Ok(Lock { _file: None });
+        }
+
+        // If there isn't anywhere to keep it, the lock still works from the temporary directory.
+        let directory = search_paths::data_dir().unwrap_or(std::env::temp_dir());
+        let path = directory.join("lock");
+        let file = match_result!(
+            fs::create_dir_all(&directory)
+                .and_then(|_| File::options().create(true).append(true).open(&path)),
+            Storage,
+            format!("Could not open the lock file '{}'.", path.display())
+        );
+
+        if file.try_lock().is_err() {
+            eprintln!("Waiting for another `todo` to finish...");
+            match_result!(
+                file.lock(),
+                Storage,
+                format!("Could not lock '{}'.", path.display())
+            );
+        }
+
+        HELD.fetch_add(1, Ordering::SeqCst);
+        Ok(Lock { _file: Some(file) })
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Closing the file lets go of the lock.
+        HELD.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Runs `change` while holding the lock, so that nothing else can change the lists until it is done.
+pub fn with_lock<T>(change: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+    let _lock = match_error!(
+        Lock::acquire(),
+        Storage,
+        "Could not lock the lists.".to_string()
+    );
+    change()
+}
+
+/// Writes a file by writing a temporary file next to it and moving it into place, so that the file
+/// is never left half-written, even if `todo` is stopped partway through.
+pub fn write_atomic(path: &Path, content: &str) -> Result<(), Error> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temporary = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let written = File::create(&temporary).and_then(|mut file| {
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    match_result!(
+        written,
+        Storage,
+        format!("Could not write to '{}'.", temporary.display())
+    );
+
+    let renamed = fs::rename(&temporary, path);
+    if renamed.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    match_result!(
+        renamed,
+        Storage,
+        format!(
+            "Could not move '{}' to '{}'.",
+            temporary.display(),
+            path.display()
+        )
+    );
+
+    Ok(())
+}
@@ -0,0 +1,300 @@
+use serde::Serialize;
+
+use crate::date::Date;
+use crate::error::{CodeComponent, CodeComponent::Agenda as AgendaComponent, Error};
+use crate::output::buffer::OutputBuffer;
+use crate::output::color::Color;
+use crate::output::line::OutputLine;
+use crate::output::segment::OutputSegment;
+use crate::output::style::Style;
+use crate::todo::document::Document;
+use crate::todo::item::Item;
+use crate::todo::json::JsonItem;
+use crate::todo::list::List;
+use crate::{match_error, match_result, settings};
+
+/// The headings that the agenda groups items under, in the order that they are shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AgendaGroup {
+    Overdue,
+    Today,
+    Tomorrow,
+    ThisWeek,
+    Later,
+    NoDate,
+}
+
+impl AgendaGroup {
+    const ALL: [AgendaGroup; 6] = [
+        AgendaGroup::Overdue,
+        AgendaGroup::Today,
+        AgendaGroup::Tomorrow,
+        AgendaGroup::ThisWeek,
+        AgendaGroup::Later,
+        AgendaGroup::NoDate,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            AgendaGroup::Overdue => "Overdue",
+            AgendaGroup::Today => "Today",
+            AgendaGroup::Tomorrow => "Tomorrow",
+            AgendaGroup::ThisWeek => "This week",
+            AgendaGroup::Later => "Later",
+            AgendaGroup::NoDate => "No date",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            AgendaGroup::Overdue => Color::Red,
+            AgendaGroup::Today => Color::Yellow,
+            AgendaGroup::Tomorrow => Color::Magenta,
+            _ => Color::Default,
+        }
+    }
+
+    /// Which heading a date goes under. "This week" runs until the end of Sunday.
+    fn for_date(date: Option<Date>, today: Date) -> Result<AgendaGroup, Error> {
+        let Some(date) = date else {
+            return Ok(AgendaGroup::NoDate);
+        };
+
+        let distance = match_error!(
+            date.distance(today),
+            AgendaComponent,
+            format!("Could not get the distance to '{}'.", date.display())
+        );
+        let days_left_in_week = match_error!(
+            today.days_until_end_of_week(),
+            AgendaComponent,
+            format!("Could not work out how much of the week is left.")
+        );
+
+        Ok(match distance {
+            i64::MIN..=-1 => AgendaGroup::Overdue,
+            0 => AgendaGroup::Today,
+            1 => AgendaGroup::Tomorrow,
+            distance if distance <= days_left_in_week => AgendaGroup::ThisWeek,
+            _ => AgendaGroup::Later,
+        })
+    }
+}
+
+/// An item from one of the documents, without its children, along with where it came from.
+#[derive(Debug, Clone)]
+pub struct AgendaEntry {
+    pub path: String,
+    pub item: Item,
+    /// The priority including urgency, which is what the entries are sorted by.
+    pub priority: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonAgendaGroup {
+    title: &'static str,
+    items: Vec<JsonAgendaEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonAgendaEntry {
+    path: String,
+    #[serde(flatten)]
+    item: JsonItem,
+}
+
+/// Every open item from a set of documents, grouped by when they are due.
+pub struct Agenda {
+    pub groups: Vec<(AgendaGroup, Vec<AgendaEntry>)>,
+}
+
+impl Agenda {
+    pub fn from_documents(documents: &[Document]) -> Result<Agenda, Error> {
+        let today = match_error!(
+            Date::today(),
+            AgendaComponent,
+            format!("Could not get today's date.")
+        );
+
+        let mut entries = vec![];
+        for document in documents {
+            match_error!(
+                Agenda::flatten(
+                    &document.items,
+                    &format!("#{}", document.name),
+                    &mut entries
+                ),
+                AgendaComponent,
+                format!("Could not collect the items of '#{}'.", document.name)
+            );
+        }
+
+        let mut groups = AgendaGroup::ALL
+            .iter()
+            .map(|group| (*group, vec![]))
+            .collect::<Vec<(AgendaGroup, Vec<AgendaEntry>)>>();
+        for entry in entries {
+            let group = match_error!(
+                AgendaGroup::for_date(entry.item.date, today),
+                AgendaComponent,
+                format!("Could not work out when '{}' is due.", entry.path)
+            );
+            if let Some((_, group_entries)) = groups.iter_mut().find(|(other, _)| *other == group) {
+                group_entries.push(entry);
+            }
+        }
+
+        // Soonest first, and then the most important.
+        for (_, entries) in &mut groups {
+            entries.sort_by(|a, b| {
+                let a_date = a.item.date.map(|date| date.iso());
+                let b_date = b.item.date.map(|date| date.iso());
+                a_date.cmp(&b_date).then(b.priority.cmp(&a.priority))
+            });
+        }
+
+        Ok(Agenda { groups })
+    }
+
+    /// Collects the open items in a list, along with their paths. Archived items are skipped
+    /// along with their children, but the children of completed items can still be open.
+    fn flatten(items: &List, path: &str, entries: &mut Vec<AgendaEntry>) -> Result<(), Error> {
+        for item in items {
+            if item.archived {
+                continue;
+            }
+
+            let item_path = format!("{}/{}", path, item.name);
+
+            if !item.completed {
+                let urgency = match_error!(
+                    item.urgency(),
+                    AgendaComponent,
+                    format!("Could not get the urgency of '{}'.", item_path)
+                );
+                let mut entry_item = item.clone();
+                entry_item.items = vec![];
+
+                entries.push(AgendaEntry {
+                    path: item_path.clone(),
+                    priority: item.priority + urgency.unwrap_or(0),
+                    item: entry_item,
+                });
+            }
+
+            match_error!(
+                Agenda::flatten(&item.items, &item_path, entries),
+                AgendaComponent,
+                format!("Could not collect the children of '{}'.", item_path)
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.iter().all(|(_, entries)| entries.is_empty())
+    }
+
+    pub fn format(&self) -> OutputBuffer {
+        let mut output = OutputBuffer::new();
+
+        if self.is_empty() {
+            output.add(
+                OutputLine::new()
+                    .add(OutputSegment::new(
+                        "Nothing to do.",
+                        Color::Default,
+                        *Style::new().dim(),
+                    ))
+                    .clone(),
+            );
+            return output;
+        }
+
+        let mut first = true;
+        for (group, entries) in &self.groups {
+            if entries.is_empty() {
+                continue;
+            }
+
+            if !first {
+                output.add(OutputLine::new());
+            }
+            first = false;
+
+            output.add(
+                OutputLine::new()
+                    .add(OutputSegment::new(
+                        &format!("{} ({})", group.title(), entries.len()),
+                        group.color(),
+                        *Style::new().bright().underline(),
+                    ))
+                    .clone(),
+            );
+
+            for entry in entries {
+                output.add(Agenda::format_entry(entry));
+            }
+        }
+
+        output
+    }
+
+    fn format_entry(entry: &AgendaEntry) -> OutputLine {
+        let mut line = OutputLine::new();
+
+        let date = match entry.item.date {
+            Some(date) => format!(" ({})", date.pretty()),
+            None => String::new(),
+        };
+        line.add(OutputSegment::new(
+            &format!("  □ {}{} ", entry.priority, date),
+            settings::get().theme.priority_color(entry.priority),
+            Style::normal(),
+        ));
+        line.add(OutputSegment::new(
+            &entry.path,
+            Color::Default,
+            Style::normal(),
+        ));
+        for tag in &entry.item.tags {
+            line.add(OutputSegment::new(
+                &format!(" {}", tag),
+                settings::get().theme.tags.clone(),
+                Style::normal(),
+            ));
+        }
+
+        line
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        let mut groups = vec![];
+
+        for (group, entries) in &self.groups {
+            let mut items = vec![];
+            for entry in entries {
+                items.push(JsonAgendaEntry {
+                    path: entry.path.clone(),
+                    item: match_error!(
+                        entry.item.to_json_value(),
+                        AgendaComponent,
+                        format!("Could not convert '{}' to JSON.", entry.path)
+                    ),
+                });
+            }
+
+            groups.push(JsonAgendaGroup {
+                title: group.title(),
+                items,
+            });
+        }
+
+        Ok(match_result!(
+            serde_json::to_string_pretty(&groups),
+            AgendaComponent,
+            format!("Could not serialize the agenda.")
+        ))
+    }
+}
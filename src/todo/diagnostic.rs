@@ -0,0 +1,167 @@
+use crate::output::buffer::OutputBuffer;
+use crate::output::color::Color;
+use crate::output::line::OutputLine;
+use crate::output::segment::OutputSegment;
+use crate::output::style::Style;
+
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Part of the file couldn't be read. The list can still be shown, but it can't be changed
+    /// until it is fixed, since saving it could lose what wasn't read.
+    Error,
+    /// Something that was read, but probably isn't what was meant.
+    Warning,
+}
+
+/// A problem somewhere in a list's file. Lines and columns start at 1, and columns count
+/// characters rather than bytes.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    /// How many characters the problem covers, so that they can be underlined.
+    pub length: usize,
+    pub message: String,
+    /// The whole line that the problem is on.
+    pub text: String,
+}
+
+impl Diagnostic {
+    pub fn error(column: usize, length: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            line: 0,
+            column,
+            length,
+            message,
+            text: String::new(),
+        }
+    }
+
+    pub fn warning(column: usize, length: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(column, length, message)
+        }
+    }
+
+    /// Says which line the problem is on, once that is known.
+    pub fn on_line(mut self, line: usize, text: &str) -> Diagnostic {
+        self.line = line;
+        self.text = text.to_string();
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The problem on one line, like `3:5: error: ...`, for error messages.
+    pub fn summary(&self) -> String {
+        format!(
+            "{}:{}: {}: {}",
+            self.line,
+            self.column,
+            self.severity_name(),
+            self.message
+        )
+    }
+
+    /// Formats the problem the way a compiler would, with the line that it is on and the part of
+    /// the line that is wrong underlined.
+    pub fn format(&self, path: &Path) -> OutputBuffer {
+        let mut output = OutputBuffer::new();
+        let color = match self.severity {
+            Severity::Error => Color::Red,
+            Severity::Warning => Color::Yellow,
+        };
+        let gutter = " ".repeat(self.line.to_string().len());
+
+        output.add(
+            OutputLine::new()
+                .add(OutputSegment::new(
+                    &format!("{}: ", self.severity_name()),
+                    color.clone(),
+                    *Style::new().bright(),
+                ))
+                .add(OutputSegment::new(
+                    &self.message,
+                    Color::Default,
+                    *Style::new().bright(),
+                ))
+                .clone(),
+        );
+        output.add(
+            OutputLine::new()
+                .add(OutputSegment::new(
+                    &format!("{gutter}--> "),
+                    Color::Blue,
+                    Style::normal(),
+                ))
+                .add(OutputSegment::new(
+                    &format!("{}:{}:{}", path.display(), self.line, self.column),
+                    Color::Default,
+                    Style::normal(),
+                ))
+                .clone(),
+        );
+        output.add(
+            OutputLine::new()
+                .add(OutputSegment::new(
+                    &format!("{gutter} |"),
+                    Color::Blue,
+                    Style::normal(),
+                ))
+                .clone(),
+        );
+        output.add(
+            OutputLine::new()
+                .add(OutputSegment::new(
+                    &format!("{} | ", self.line),
+                    Color::Blue,
+                    Style::normal(),
+                ))
+                .add(OutputSegment::new(
+                    &self.text,
+                    Color::Default,
+                    Style::normal(),
+                ))
+                .clone(),
+        );
+        output.add(
+            OutputLine::new()
+                .add(OutputSegment::new(
+                    &format!("{gutter} | "),
+                    Color::Blue,
+                    Style::normal(),
+                ))
+                .add(OutputSegment::new(
+                    &format!(
+                        "{}{}",
+                        " ".repeat(self.column.saturating_sub(1)),
+                        "^".repeat(self.length.max(1))
+                    ),
+                    color,
+                    *Style::new().bright(),
+                ))
+                .clone(),
+        );
+
+        output
+    }
+
+    fn severity_name(&self) -> &'static str {
+        match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// The column (counting characters, from 1) of a byte offset in a line.
+pub fn column(line: &str, offset: usize) -> usize {
+    line.get(..offset).unwrap_or(line).chars().count() + 1
+}
@@ -1,104 +1,274 @@
 use crate::date::Date;
 use crate::error::{CodeComponent, Error};
+use crate::journal;
 use crate::output::buffer::OutputBuffer;
 use crate::output::color::Color;
 use crate::output::line::OutputLine;
 use crate::output::segment::OutputSegment;
 use crate::output::style::Style;
+use crate::storage::{self, Lock};
+use crate::todo::diagnostic::{Diagnostic, column};
+use crate::todo::item::Item;
 use crate::todo::list;
 use crate::todo::list::TodoList;
-use crate::{match_error, match_option, match_result, propagate};
+use crate::todo::markdown::{self, ListFormat};
+use crate::todo::path::ItemPath;
+use crate::todo::sort::SortSpec;
+use crate::{match_error, match_result, propagate, search_paths};
+
+/// The fields that a list can have in the braces of its title in a Markdown list.
+const TITLE_KEYS: &[&str] = &["priority", "due", "date", "archived", "alias", "sort"];
 
 #[derive(Debug, Clone)]
 pub struct Document {
     pub name: String,
     pub path: std::path::PathBuf,
+    /// The name of the list's file in its directory, like `.todo` or `TODO.md`. It decides how the
+    /// list is written.
+    pub file_name: String,
     pub priority: i32,
     pub date: Option<Date>,
     pub items: list::List,
     pub archived: bool,
+    /// A short name from an `# alias` line, which can be used instead of the name to find the list
+    /// (like `#api`), or to tell apart lists with the same name (like `#Todo@api`).
+    pub alias: Option<String>,
+    /// The order that this list's items are shown in, from a `# sort` line. The items are always
+    /// saved in the order that they are written.
+    pub sort: Option<SortSpec>,
+    /// The metadata lines as they were written, so that they can be saved the same way.
+    pub header_lines: Vec<String>,
+    /// The lines between the metadata and the first item, which is usually one blank line.
+    pub preamble: Vec<String>,
+    pub final_newline: bool,
+    /// What the file was when it was read, so that saving doesn't overwrite changes that were made
+    /// by something else in the meantime. Documents that weren't read from a file don't have one.
+    pub loaded: Option<String>,
+    /// The problems that were found while reading the file. If any of them are errors, some of
+    /// it wasn't read, so the document can't be saved.
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl Document {
-    pub fn from(file: String, path: std::path::PathBuf) -> Result<Document, Error> {
-        let lines = file.lines();
+    /// Parses a document, and fails if any part of it can't be read.
+    pub fn from(
+        file: String,
+        path: std::path::PathBuf,
+        file_name: String,
+    ) -> Result<Document, Error> {
+        let document = Document::parse(file, path, file_name);
+
+        let errors = document
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .map(|diagnostic| diagnostic.summary())
+            .collect::<Vec<String>>();
+        if !errors.is_empty() {
+            return Err(propagate!(
+                CodeComponent::DocumentParser,
+                format!(
+                    "Some of the document at path {} couldn't be read:\n{}",
+                    document.path.display(),
+                    errors.join("\n")
+                )
+            ));
+        }
+
+        Ok(document)
+    }
+
+    /// Parses a document, keeping everything that can be read. Lines that can't be read are kept
+    /// as they are, and what was wrong with them is in `diagnostics`.
+    pub fn parse(file: String, path: std::path::PathBuf, file_name: String) -> Document {
+        let lines = file.lines().collect::<Vec<&str>>();
+        let format = ListFormat::of(&file_name);
 
         let mut name = "Unnamed Todo List".to_string();
         let mut priority = 0;
         let mut date = None;
         let mut archived = false;
-        let mut lines_to_skip = 0;
+        let mut alias = None;
+        let mut sort = None;
+        let mut diagnostics = vec![];
+
+        let header_count = match format {
+            // The metadata is every line at the top that starts with a hash.
+            ListFormat::Todo => lines
+                .iter()
+                .take_while(|line| line.starts_with("#"))
+                .count(),
+            // Other headings are a part of the text, so only the first one is the list's.
+            ListFormat::Markdown => lines
+                .first()
+                .filter(|line| **line == "#" || line.starts_with("# "))
+                .map_or(0, |_| 1),
+        };
 
-        for (i, line) in lines.clone().enumerate() {
-            if !line.starts_with("#") {
+        if header_count == 0 && !lines.is_empty() {
+            diagnostics.push(
+                Diagnostic::warning(
+                    1,
+                    1,
+                    format!(
+                        "The list doesn't have a name, so it is called '{name}'. Start the file with a line like '# Todo'."
+                    ),
+                )
+                .on_line(1, lines[0]),
+            );
+        }
+
+        // Each property with its value, the line that it is on, and where the part that would be
+        // wrong is in the line.
+        let mut properties = vec![];
+        for (i, line) in lines[..header_count].iter().enumerate() {
+            if i == 0 && format == ListFormat::Todo {
+                name = Document::title(line).to_string();
+                continue;
+            }
+            if i == 0 {
+                let (title, fields) = Document::markdown_title(line);
+                name = title;
+                for field in fields {
+                    let property_name = if field.key == "due" {
+                        "date"
+                    } else {
+                        field.key
+                    };
+                    properties.push((
+                        i,
+                        property_name,
+                        field.value.unwrap_or_default(),
+                        column(line, field.start),
+                        field.length,
+                    ));
+                }
                 continue;
             }
 
-            lines_to_skip += 1;
+            let (property_name, rest) = Document::split_property(line);
+            properties.push((
+                i,
+                property_name,
+                rest,
+                column(line, line.len() - rest.len()),
+                rest.chars().count(),
+            ));
+        }
 
-            if i == 0 {
-                name = line[2..].to_string();
+        for (i, property_name, rest, value_column, value_length) in properties {
+            let line = lines[i];
+            if let Some(problem) = Document::check_property(property_name, rest) {
+                diagnostics.push(
+                    Diagnostic::error(value_column, value_length, problem).on_line(i + 1, line),
+                );
                 continue;
             }
 
-            let mut parts = line.split(" ").skip(1);
-            // Find the name of the property, if there is one.
-            let property_name = match_option!(
-                parts.next(),
-                CodeComponent::DocumentParser,
-                format!("Found malformed metadata line: '{}'", line)
-            );
             match property_name {
-                "priority" => {
-                    let rest = parts.clone().collect::<Vec<&str>>().join(" ");
-                    priority = match_result!(
-                        rest.parse::<i32>(),
-                        CodeComponent::DocumentParser,
+                "priority" => priority = rest.parse::<i32>().unwrap_or_default(),
+                "date" => date = Date::from(rest).ok(),
+                "archived" => archived = true,
+                "alias" => alias = Some(rest.to_string()),
+                "sort" => sort = SortSpec::parse(rest).ok(),
+                // There is nowhere to keep anything else in a Markdown title.
+                _ if format == ListFormat::Markdown => diagnostics.push(
+                    Diagnostic::error(
+                        value_column,
+                        value_length,
                         format!(
-                            "Could not parse property value to i32. Got value '{}'.",
-                            rest
-                        )
+                            "'{property_name}' isn't something that a list can have. Use priority, due, archived, alias or sort."
+                        ),
                     )
-                }
-                "date" => {
-                    date = Date::from(&parts.clone().collect::<Vec<&str>>().join(" ")).ok();
-                }
-                "archived" => archived = true,
-                _ => {
-                    return Err(propagate!(
-                        CodeComponent::DocumentParser,
+                    .on_line(i + 1, line),
+                ),
+                // Anything else is kept as it is, and written back when saving.
+                _ => diagnostics.push(
+                    Diagnostic::warning(
+                        column(line, line.len() - line.trim_start_matches(['#', ' ']).len()),
+                        property_name.chars().count(),
                         format!(
-                            "Found unknown property '{}' on line {}.",
-                            property_name,
-                            i + 1
-                        )
-                    ));
-                }
+                            "'{property_name}' isn't a property that todo knows about, so it is kept as it is."
+                        ),
+                    )
+                    .on_line(i + 1, line),
+                ),
             }
         }
 
-        let remaining_lines = lines
-            .clone()
-            .skip(lines_to_skip + 1)
-            .collect::<Vec<&str>>()
-            .join("\n");
-        let items = match_error!(
-            list::List::parse(remaining_lines),
-            CodeComponent::DocumentParser,
-            format!(
-                "Could not parse the list of items in the document at path {}",
-                path.display()
-            )
-        );
+        let body = &lines[header_count..];
+        let (items, list_diagnostics) = list::List::parse_tolerant(body, header_count + 1, format);
+        diagnostics.extend(list_diagnostics);
+        // Lines before the first item that can be read stay where they are.
+        let first_item = body
+            .iter()
+            .position(|line| Item::is_item_line(line) && Item::parse(line, format).is_ok())
+            .unwrap_or(body.len());
 
-        Ok(Document {
+        Document {
             name: name,
             path: path,
+            file_name,
             priority: priority,
             date: date,
             items: items,
             archived: archived,
-        })
+            alias,
+            sort,
+            header_lines: lines[..header_count]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+            preamble: body[..first_item]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+            final_newline: file.is_empty() || file.ends_with("\n"),
+            loaded: None,
+            diagnostics,
+        }
+    }
+
+    /// What is wrong with the value of a metadata line, if anything. Lines like this can't be
+    /// read, so they are kept as they are.
+    fn check_property(property_name: &str, rest: &str) -> Option<String> {
+        match property_name {
+            "priority" if rest.parse::<i32>().is_err() => {
+                Some(format!("The priority '{rest}' isn't a whole number."))
+            }
+            "date" if Date::from(rest).is_err() => Some(format!("'{rest}' isn't a date.")),
+            "alias" if rest.is_empty() => Some("The alias is empty.".to_string()),
+            "alias" if rest.contains(['/', '@', '#']) => Some(format!(
+                "The alias '{rest}' can't have '/', '@' or '#' in it, since it is used in paths."
+            )),
+            "sort" if SortSpec::parse(rest).is_err() => Some(format!(
+                "'{rest}' isn't a sort order, like 'date,priority:desc'."
+            )),
+            _ => None,
+        }
+    }
+
+    /// The name of the list from its first line, like `# Todo`.
+    fn title(line: &str) -> &str {
+        let line = line.strip_prefix("#").unwrap_or(line);
+        line.strip_prefix(" ").unwrap_or(line)
+    }
+
+    /// The name of a Markdown list from its first line, and the fields in braces after it, like
+    /// `# Todo {priority=3 alias=todo}`.
+    fn markdown_title(line: &str) -> (String, Vec<markdown::Field<'_>>) {
+        let title = Document::title(line);
+        let (title, fields) = markdown::split_fields(title, line.len() - title.len(), TITLE_KEYS);
+        (title.trim_end().to_string(), fields)
+    }
+
+    /// Splits a metadata line like `# priority 3` into its property name and value.
+    fn split_property(line: &str) -> (&str, &str) {
+        let line = line.strip_prefix("#").unwrap_or(line).trim_start();
+        match line.split_once(" ") {
+            Some((property_name, rest)) => (property_name, rest.trim()),
+            None => (line, ""),
+        }
     }
 
     pub fn from_path(path: &std::path::PathBuf) -> Result<Document, Error> {
@@ -107,7 +277,13 @@ impl Document {
             CodeComponent::DocumentParser,
             format!("Could not normalize the path '{}'.", path.display())
         );
-        normalized_path.push(".todo");
+        let file_name = match_error!(
+            search_paths::list_file(&normalized_path),
+            CodeComponent::DocumentParser,
+            format!("Could not find the list at path '{}'.", path.display())
+        )
+        .unwrap_or_else(search_paths::default_list_file);
+        normalized_path.push(&file_name);
 
         let content = match_result!(
             std::fs::read_to_string(&normalized_path),
@@ -117,39 +293,306 @@ impl Document {
                 path = normalized_path.display()
             )
         );
-        Document::from(content, path.clone())
+        // Problems in the file don't stop it from being read, but it can't be saved until they are
+        // fixed.
+        let mut document = Document::parse(content.clone(), path.clone(), file_name);
+        document.loaded = Some(content);
+        Ok(document)
     }
 
+    /// Reads a lot of lists at once, spread over a few threads. They are in the same order as the
+    /// paths.
+    pub fn from_paths(paths: &[std::path::PathBuf]) -> Result<Vec<Document>, Error> {
+        let threads = std::thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(1);
+        let chunk_size = paths.len().div_ceil(threads).max(1);
+
+        let chunks = std::thread::scope(|scope| {
+            paths
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(|| {
+                        chunk
+                            .iter()
+                            .map(Document::from_path)
+                            .collect::<Result<Vec<Document>, Error>>()
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|thread| thread.join())
+                .collect::<Vec<_>>()
+        });
+
+        let mut documents = vec![];
+        for chunk in chunks {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => {
+                    return Err(propagate!(
+                        CodeComponent::DocumentParser,
+                        format!("Something went wrong while reading the lists.")
+                    ));
+                }
+            };
+            // The error says which list couldn't be read.
+            documents.append(&mut match_error!(
+                chunk,
+                CodeComponent::DocumentParser,
+                format!("Could not read the lists.")
+            ));
+        }
+
+        Ok(documents)
+    }
+
+    /// This formats it for saving. Metadata lines that haven't changed (and ones that aren't
+    /// understood) are written exactly as they were, along with the lines before the items.
     pub fn to_string(&self) -> String {
+        if self.list_format() == ListFormat::Markdown {
+            return self.to_markdown();
+        }
+
         let mut output = String::new();
 
-        output += &format!("# {title}\n", title = &self.name);
-        if self.priority != 0 {
-            output += &format!("# priority {priority}\n", priority = &self.priority);
+        match self.header_lines.first() {
+            Some(line) if Document::title(line) == self.name => {
+                output += &format!("{line}\n");
+            }
+            _ => output += &format!("# {title}\n", title = &self.name),
+        }
+
+        let mut written = vec![];
+        for line in self.header_lines.iter().skip(1) {
+            let (property_name, rest) = Document::split_property(line);
+            if Document::check_property(property_name, rest).is_some() {
+                output += &format!("{line}\n");
+                continue;
+            }
+            let unchanged = match property_name {
+                "priority" => rest.parse::<i32>().ok() == Some(self.priority),
+                "date" => {
+                    Date::from(rest).ok().map(|date| date.iso()) == self.date.map(|date| date.iso())
+                }
+                "archived" => self.archived,
+                "alias" => Some(rest) == self.alias.as_deref(),
+                "sort" => SortSpec::parse(rest).ok() == self.sort,
+                _ => {
+                    output += &format!("{line}\n");
+                    continue;
+                }
+            };
+
+            if unchanged {
+                output += &format!("{line}\n");
+                written.push(property_name);
+            } else if let Some(property) = self.property_line(property_name) {
+                output += &property;
+                written.push(property_name);
+            }
         }
-        if let Some(date) = self.date {
-            output += &format!("# date {date}\n", date = date.display());
+
+        for property_name in ["priority", "date", "archived", "alias", "sort"] {
+            if !written.contains(&property_name)
+                && let Some(property) = self.property_line(property_name)
+            {
+                output += &property;
+            }
         }
-        if self.archived {
-            output += &format!("# archived\n");
+
+        output + &self.body()
+    }
+
+    /// This formats a Markdown list for saving. Its metadata is all in its title, like
+    /// `# Todo {priority=3}`, which is written as it was unless something in it has changed.
+    fn to_markdown(&self) -> String {
+        let unchanged = self.header_lines.first().filter(|line| {
+            let title =
+                Document::parse(line.to_string(), self.path.clone(), self.file_name.clone());
+            title
+                .diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.is_error())
+                || (title.name == self.name
+                    && title.priority == self.priority
+                    && title.date.map(|date| date.iso()) == self.date.map(|date| date.iso())
+                    && title.archived == self.archived
+                    && title.alias == self.alias
+                    && title.sort == self.sort)
+        });
+
+        let output = match unchanged {
+            Some(line) => format!("{line}\n"),
+            None => {
+                let mut fields = vec![];
+                if self.priority != 0 {
+                    fields.push(("priority", Some(self.priority.to_string())));
+                }
+                if let Some(date) = self.date {
+                    fields.push(("due", Some(date.iso())));
+                }
+                if self.archived {
+                    fields.push(("archived", None));
+                }
+                if let Some(alias) = &self.alias {
+                    fields.push(("alias", Some(alias.clone())));
+                }
+                if let Some(sort) = &self.sort {
+                    fields.push(("sort", Some(sort.display())));
+                }
+                format!("# {}{}\n", self.name, markdown::write_fields(&fields))
+            }
+        };
+
+        output + &self.body()
+    }
+
+    /// The lines before the items, and the items, for saving.
+    fn body(&self) -> String {
+        let mut output = String::new();
+
+        if self.preamble.is_empty() && !self.items.is_empty() {
+            output += "\n";
+        }
+        for line in &self.preamble {
+            output += &format!("{line}\n");
         }
 
-        output += "\n";
+        output += &self.items.to_save(self.list_format());
 
-        output += &self.items.to_save();
+        if !self.final_newline {
+            output.pop();
+        }
 
         output
     }
 
-    pub fn save(&self) -> Result<(), Error> {
-        match_result!(
-            std::fs::write(self.path.as_path().join(".todo"), self.to_string()),
+    /// How the list's file is written.
+    pub fn list_format(&self) -> ListFormat {
+        ListFormat::of(&self.file_name)
+    }
+
+    /// The list's file.
+    pub fn file_path(&self) -> std::path::PathBuf {
+        self.path.join(&self.file_name)
+    }
+
+    /// The metadata line for a property, or nothing if it isn't set.
+    fn property_line(&self, property_name: &str) -> Option<String> {
+        match property_name {
+            "priority" if self.priority != 0 => Some(format!(
+                "# priority {priority}\n",
+                priority = &self.priority
+            )),
+            "date" => self
+                .date
+                .map(|date| format!("# date {date}\n", date = date.display())),
+            "archived" if self.archived => Some("# archived\n".to_string()),
+            "alias" => self
+                .alias
+                .as_ref()
+                .map(|alias| format!("# alias {alias}\n")),
+            "sort" => self
+                .sort
+                .as_ref()
+                .map(|sort| format!("# sort {}\n", sort.display())),
+            _ => None,
+        }
+    }
+
+    /// Removes the item at a path. The lines after it that aren't a part of an item stay where they
+    /// were, so if it was the first item, they are now before the items.
+    pub fn remove_item(&mut self, path: &ItemPath) -> Result<Item, Error> {
+        let (item, lines) = match_error!(
+            self.items.remove_by_path(path),
+            CodeComponent::Document,
+            format!("Could not remove the item at path '{}'.", path.display())
+        );
+        self.preamble.extend(lines);
+        Ok(item)
+    }
+
+    /// Removes the item at some indices, like `remove_item`.
+    pub fn remove_at(&mut self, indices: &[usize]) -> Option<Item> {
+        let (item, lines) = self.items.remove_at(indices)?;
+        self.preamble.extend(lines);
+        Some(item)
+    }
+
+    /// Checks that the document can be saved: everything in it was read, and its file is still what
+    /// it was when the document was read. The file as it is now is returned. Hold the lock while
+    /// this is checked, or the file could change before it is saved.
+    pub fn check_saveable(&self) -> Result<Option<String>, Error> {
+        let todo_path = self.file_path();
+        if self
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.is_error())
+        {
+            return Err(propagate!(
+                CodeComponent::Document,
+                format!(
+                    "Some of '{}' couldn't be read, so it wasn't changed. Run `todo check` to see what is wrong, and `todo open` to fix it.",
+                    todo_path.display()
+                )
+            ));
+        }
+
+        let current = std::fs::read_to_string(&todo_path).ok();
+        if let Some(loaded) = &self.loaded
+            && current.as_ref() != Some(loaded)
+        {
+            return Err(propagate!(
+                CodeComponent::Document,
+                format!(
+                    "'{}' was changed by something else after it was read, so it wasn't saved. Try again.",
+                    todo_path.display()
+                )
+            ));
+        }
+
+        Ok(current)
+    }
+
+    /// Saves the document, and records the change in the journal so that it can be undone.
+    /// The file is replaced all at once while holding the lock, and only if it is still what it was
+    /// when the document was read.
+    pub fn save(&mut self) -> Result<(), Error> {
+        let todo_path = self.file_path();
+        let content = self.to_string();
+
+        let _lock = match_error!(
+            Lock::acquire(),
+            CodeComponent::Document,
+            format!("Could not lock the lists to save '#{}'.", self.name)
+        );
+        let current = self.check_saveable()?;
+
+        if storage::is_dry_run() {
+            storage::print_diff(&todo_path, current.as_deref(), Some(&content));
+            self.loaded = Some(content);
+            return Ok(());
+        }
+
+        match_error!(
+            journal::record(&todo_path, current, &content),
             CodeComponent::Document,
             format!(
-                "should have been able to save the '.todo' file at {}",
+                "Could not record the change to '{}', so it wasn't saved.",
+                todo_path.display()
+            )
+        );
+        match_error!(
+            storage::write_atomic(&todo_path, &content),
+            CodeComponent::Document,
+            format!(
+                "should have been able to save the '{}' file at {}",
+                self.file_name,
                 self.path.display()
             )
         );
+        self.loaded = Some(content);
 
         Ok(())
     }
@@ -157,6 +600,28 @@ impl Document {
     pub fn format(&self) -> Result<OutputBuffer, Error> {
         let mut output = OutputBuffer::new();
 
+        output.add(self.format_title());
+
+        output.add(
+            OutputLine::new()
+                .add(OutputSegment::new("│", Color::Default, *Style::new().dim()))
+                .clone(),
+        );
+
+        output.append(match_error!(
+            self.items.format(vec![]),
+            CodeComponent::Document,
+            format!(
+                "Could not format the items of the document at path '{}'",
+                self.path.display()
+            )
+        ));
+
+        Ok(output)
+    }
+
+    /// Formats the first line of the document, with its name, date and path.
+    pub fn format_title(&self) -> OutputLine {
         let mut first_line = OutputLine::new();
 
         first_line.add(OutputSegment::new(
@@ -167,7 +632,7 @@ impl Document {
 
         if let Some(date) = self.date {
             first_line.add(OutputSegment::new(
-                &format!("{name} - {date} ", name = self.name, date = date.display()),
+                &format!("{name} - {date} ", name = self.name, date = date.pretty()),
                 Color::Default,
                 Style::normal(),
             ));
@@ -185,23 +650,205 @@ impl Document {
             *Style::new().dim(),
         ));
 
-        output.add(first_line);
+        first_line
+    }
+}
 
-        output.add(
-            OutputLine::new()
-                .add(OutputSegment::new("│", Color::Default, *Style::new().dim()))
-                .clone(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::markdown::ListFormat;
+    use crate::todo::path::ItemPath;
+
+    /// The example lists in the README, with the name of the file that each one would be in.
+    fn readme_examples() -> Vec<(String, &'static str)> {
+        let readme = include_str!("../../README.md");
+        let mut examples = vec![];
+        let mut current: Option<(String, &str)> = None;
+
+        for line in readme.lines() {
+            match (&mut current, line.strip_prefix("```")) {
+                (None, Some(language)) => current = Some((String::new(), language)),
+                (Some((text, language)), Some(_)) => {
+                    // Only the blocks that are lists, not the commands and the output.
+                    if text.starts_with("# ") && matches!(*language, "" | "markdown") {
+                        let file_name = if *language == "markdown" {
+                            "TODO.md"
+                        } else {
+                            ".todo"
+                        };
+                        examples.push((text.clone(), file_name));
+                    }
+                    current = None;
+                }
+                (Some((text, _)), None) => *text += &format!("{line}\n"),
+                (None, None) => {}
+            }
+        }
+
+        assert!(examples.len() >= 3, "The README examples weren't found.");
+        examples
+    }
+
+    fn parse(text: &str, file_name: &str) -> Document {
+        let document = Document::parse(
+            text.to_string(),
+            std::path::PathBuf::from("/tmp"),
+            file_name.to_string(),
+        );
+        assert!(
+            document.diagnostics.is_empty(),
+            "The example had problems: {:?}",
+            document.diagnostics
         );
+        document
+    }
 
-        output.append(match_error!(
-            self.items.format(vec![]),
-            CodeComponent::Document,
-            format!(
-                "Could not format the items of the document at path '{}'",
-                self.path.display()
-            )
-        ));
+    /// The line that `changed` has where `original` doesn't, if that is the only difference.
+    fn inserted_line(original: &str, changed: &str) -> Option<String> {
+        let original = original.lines().collect::<Vec<&str>>();
+        let changed = changed.lines().collect::<Vec<&str>>();
+        let position = original
+            .iter()
+            .zip(&changed)
+            .position(|(a, b)| a != b)
+            .unwrap_or(original.len());
 
-        Ok(output)
+        (changed.len() == original.len() + 1 && changed[position + 1..] == original[position..])
+            .then(|| changed[position].to_string())
+    }
+
+    #[test]
+    fn readme_examples_round_trip() {
+        for (text, file_name) in readme_examples() {
+            assert_eq!(parse(&text, file_name).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn completing_an_item_only_changes_its_line() {
+        for (text, file_name) in readme_examples() {
+            let document = parse(&text, file_name);
+
+            for index in 0..document.items.len() {
+                if document.items[index].completed || document.items[index].recurrence.is_some() {
+                    continue;
+                }
+                let mut changed = document.clone();
+                changed
+                    .items
+                    .complete_at(&[index])
+                    .expect("The item should be completed.");
+                let output = changed.to_string();
+
+                let differences = text
+                    .lines()
+                    .zip(output.lines())
+                    .filter(|(a, b)| a != b)
+                    .collect::<Vec<(&str, &str)>>();
+                assert_eq!(text.lines().count(), output.lines().count());
+                assert_eq!(differences.len(), 1, "{output}");
+                assert!(differences[0].0.contains(&document.items[index].name));
+            }
+        }
+    }
+
+    #[test]
+    fn adding_an_item_only_adds_its_line() {
+        for (text, file_name) in readme_examples() {
+            let mut document = parse(&text, file_name);
+            let format = ListFormat::of(file_name);
+            let (item, _) =
+                Item::parse("- [ ] A new item", format).expect("The item should be read.");
+            document.items.push(item);
+
+            let output = document.to_string();
+            assert_eq!(
+                inserted_line(&text, &output).as_deref(),
+                Some("- [ ] A new item"),
+                "{output}"
+            );
+        }
+    }
+
+    /// The list after removing the item at `from`, and adding it at `to` if there is one.
+    fn moved(text: &str, file_name: &str, from: &str, to: Option<&str>) -> String {
+        let path = |path: &str| ItemPath::try_from(&path.to_string()).expect("The path is valid.");
+        let mut document = parse(text, file_name);
+        let item = document
+            .remove_item(&path(from))
+            .expect("The item should be removed.");
+        if let Some(to) = to {
+            document
+                .items
+                .add_item(item, path(to))
+                .expect("The item should be added.");
+        }
+        document.to_string()
+    }
+
+    const COMMENTED: &str =
+        "# Todo\n\n- [ ] a\n# a comment about b\n- [ ] b\n  b's note\n\n- [ ] c\n";
+
+    #[test]
+    fn removing_an_item_keeps_the_lines_around_it() {
+        assert_eq!(
+            moved(COMMENTED, ".todo", "#Todo/a", None),
+            "# Todo\n\n# a comment about b\n- [ ] b\n  b's note\n\n- [ ] c\n"
+        );
+        assert_eq!(
+            moved(COMMENTED, ".todo", "#Todo/b", None),
+            "# Todo\n\n- [ ] a\n# a comment about b\n\n- [ ] c\n"
+        );
+    }
+
+    #[test]
+    fn moving_an_item_leaves_the_lines_around_it() {
+        assert_eq!(
+            moved(COMMENTED, ".todo", "#Todo/a", Some("#Todo/c")),
+            "# Todo\n\n# a comment about b\n- [ ] b\n  b's note\n\n- [ ] c\n - [ ] a\n"
+        );
+    }
+
+    const SECTIONS: &str = "# Todo\n\n## Work\n\n- [ ] Review PR\n  - [ ] Read it\n- [ ] Deploy\n\n## Home\n\nSome prose.\n\n- [ ] Laundry\n- [ ] Dishes\n";
+
+    #[test]
+    fn sections_stay_in_place_in_markdown() {
+        assert_eq!(
+            moved(SECTIONS, "TODO.md", "#Todo/Deploy", None),
+            "# Todo\n\n## Work\n\n- [ ] Review PR\n  - [ ] Read it\n\n## Home\n\nSome prose.\n\n- [ ] Laundry\n- [ ] Dishes\n"
+        );
+        assert_eq!(
+            moved(SECTIONS, "TODO.md", "#Todo/Review/Read", None),
+            "# Todo\n\n## Work\n\n- [ ] Review PR\n- [ ] Deploy\n\n## Home\n\nSome prose.\n\n- [ ] Laundry\n- [ ] Dishes\n"
+        );
+        assert_eq!(
+            moved(SECTIONS, "TODO.md", "#Todo/Review", None),
+            "# Todo\n\n## Work\n\n- [ ] Deploy\n\n## Home\n\nSome prose.\n\n- [ ] Laundry\n- [ ] Dishes\n"
+        );
+        assert_eq!(
+            moved(SECTIONS, "TODO.md", "#Todo/Review", Some("#Todo/Laundry")),
+            "# Todo\n\n## Work\n\n- [ ] Deploy\n\n## Home\n\nSome prose.\n\n- [ ] Laundry\n  - [ ] Review PR\n    - [ ] Read it\n- [ ] Dishes\n"
+        );
+        assert_eq!(
+            moved(SECTIONS, "TODO.md", "#Todo/Deploy", Some("#Todo/Dishes")),
+            "# Todo\n\n## Work\n\n- [ ] Review PR\n  - [ ] Read it\n\n## Home\n\nSome prose.\n\n- [ ] Laundry\n- [ ] Dishes\n  - [ ] Deploy\n"
+        );
+    }
+
+    #[test]
+    fn the_copy_of_a_repeating_item_stays_in_its_section() {
+        let text = "# Todo\n\n## Work\n\n- [ ] Standup {due=4-Jan-2100 repeat=daily}\n  Say what you did.\n\n## Home\n\n- [ ] Laundry\n";
+        let mut document = parse(text, "TODO.md");
+        let done = document
+            .items
+            .complete_at(&[0])
+            .expect("The item should be completed.");
+        assert_eq!(done, Some(vec![1]));
+
+        assert_eq!(
+            document.to_string(),
+            "# Todo\n\n## Work\n\n- [ ] Standup {due=2100-01-05 repeat=\"every day\"}\n  Say what you did.\n- [x] Standup {due=2100-01-04 archived}\n  Say what you did.\n\n## Home\n\n- [ ] Laundry\n"
+        );
     }
 }
@@ -0,0 +1,593 @@
+use std::path::PathBuf;
+
+use crate::date::Date;
+use crate::error::{CodeComponent, CodeComponent::Ics, Error};
+use crate::todo::document::Document;
+use crate::todo::item::Item;
+use crate::todo::list::{List, TodoList};
+use crate::todo::tags;
+use crate::{match_error, match_option, match_result, search_paths};
+
+/// The end of a UID that todo made from an item's ID, so that the ID can be read back.
+const UID_SUFFIX: &str = "@todo-rs";
+
+impl Document {
+    /// Formats the items that have a date as an iCalendar file, with a VTODO for each one. It
+    /// is only the items at some indices (and their children), or the whole list if there aren't
+    /// any. Items are linked to their parent with `RELATED-TO`, even if the parent doesn't have a
+    /// date.
+    pub fn to_ics(&self, indices: &[usize]) -> Result<String, Error> {
+        let stamp = format!(
+            "{}T000000Z",
+            match_error!(
+                Date::today(),
+                Ics,
+                "Could not get today's date.".to_string()
+            )
+            .iso()
+            .replace("-", "")
+        );
+
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//todo-rs//todo-rs//EN".to_string(),
+            format!("X-WR-CALNAME:{}", escape(&self.name)),
+        ];
+
+        let parent = match indices.split_last() {
+            Some((_, parent)) if !parent.is_empty() => {
+                let item = match_option!(
+                    self.items.item_at(parent),
+                    Ics,
+                    format!("There is no item at {:?}.", parent)
+                );
+                Some(self.uid(item, parent))
+            }
+            _ => None,
+        };
+        if indices.is_empty() {
+            for (i, item) in self.items.iter().enumerate() {
+                self.write_item(item, &[i], None, &stamp, &mut lines);
+            }
+        } else {
+            let item = match_option!(
+                self.items.item_at(indices),
+                Ics,
+                format!("There is no item at {:?}.", indices)
+            );
+            self.write_item(item, indices, parent.as_deref(), &stamp, &mut lines);
+        }
+
+        lines.push("END:VCALENDAR".to_string());
+
+        Ok(lines
+            .iter()
+            .map(|line| fold(line) + "\r\n")
+            .collect::<String>())
+    }
+
+    /// Create a document from an iCalendar file, with an item for each VTODO. Items are put under
+    /// the item that their `RELATED-TO` points to, if it is in the file, and the list is named after
+    /// the calendar.
+    pub fn from_ics(input: &str, path: PathBuf) -> Result<Document, Error> {
+        let mut name = None;
+        let mut entries: Vec<Entry> = vec![];
+        let mut current: Option<Entry> = None;
+        // How many components (like a VALARM) the current line is in inside the VTODO, since their
+        // properties aren't the item's.
+        let mut depth = 0;
+
+        for line in unfold(input) {
+            let Some((property, parameters, value)) = split_line(&line) else {
+                continue;
+            };
+
+            match (property.as_str(), &mut current) {
+                ("BEGIN", None) if value.eq_ignore_ascii_case("VTODO") => {
+                    current = Some(Entry::new());
+                    depth = 0;
+                }
+                ("BEGIN", Some(_)) => depth += 1,
+                ("END", Some(_)) if depth > 0 => depth -= 1,
+                ("END", Some(_)) if value.eq_ignore_ascii_case("VTODO") => {
+                    entries.extend(current.take());
+                }
+                ("X-WR-CALNAME", None) => name = Some(unescape(&value)),
+                (_, Some(entry)) if depth == 0 => match_error!(
+                    entry.read(&property, &parameters, &value),
+                    Ics,
+                    format!("Could not read the line '{line}'.")
+                ),
+                _ => {}
+            }
+        }
+
+        let mut visited = vec![false; entries.len()];
+        let mut items: List = vec![];
+        for i in 0..entries.len() {
+            let parent_exists = entries[i].parent.as_ref().is_some_and(|parent| {
+                entries
+                    .iter()
+                    .any(|entry| entry.uid.as_ref() == Some(parent))
+            });
+            if !parent_exists {
+                items.push(attach(i, &entries, &mut visited));
+            }
+        }
+        // Items whose parents are each other's children can't be put anywhere else.
+        for i in 0..entries.len() {
+            if !visited[i] {
+                items.push(attach(i, &entries, &mut visited));
+            }
+        }
+
+        Ok(Document {
+            name: name.unwrap_or("Todo".to_string()),
+            path,
+            file_name: search_paths::default_list_file(),
+            priority: 0,
+            date: None,
+            items,
+            archived: false,
+            alias: None,
+            sort: None,
+            header_lines: vec![],
+            preamble: vec![String::new()],
+            final_newline: true,
+            loaded: None,
+            diagnostics: vec![],
+        })
+    }
+
+    /// Writes an item's VTODO if it has a date, and then its children's.
+    fn write_item(
+        &self,
+        item: &Item,
+        indices: &[usize],
+        parent: Option<&str>,
+        stamp: &str,
+        lines: &mut Vec<String>,
+    ) {
+        let uid = self.uid(item, indices);
+
+        if let Some(date) = item.date {
+            lines.push("BEGIN:VTODO".to_string());
+            lines.push(format!("UID:{}", escape(&uid)));
+            lines.push(format!("DTSTAMP:{stamp}"));
+            lines.push(format!("SUMMARY:{}", escape(&item.name)));
+            lines.push(format!("DUE;VALUE=DATE:{}", compact(date)));
+            if let Some(created) = item.created {
+                lines.push(format!("CREATED:{}T000000Z", compact(created)));
+            }
+            if item.priority != 0 {
+                lines.push(format!("PRIORITY:{}", ics_priority(item.priority)));
+            }
+            if !(0..=9).contains(&item.priority) {
+                lines.push(format!("X-TODO-RS-PRIORITY:{}", item.priority));
+            }
+            // Archived items were finished, so they are completed, and iCalendar has nothing like
+            // archiving, so that is kept in its own property.
+            lines.push(format!(
+                "STATUS:{}",
+                if item.completed || item.archived {
+                    "COMPLETED"
+                } else {
+                    "NEEDS-ACTION"
+                }
+            ));
+            if item.archived {
+                lines.push("X-TODO-RS-ARCHIVED:TRUE".to_string());
+            }
+            if !item.tags.is_empty() {
+                lines.push(format!(
+                    "CATEGORIES:{}",
+                    item.tags
+                        .iter()
+                        .map(|tag| escape(tag))
+                        .collect::<Vec<String>>()
+                        .join(",")
+                ));
+            }
+            if let Some(note) = &item.note {
+                lines.push(format!("DESCRIPTION:{}", escape(note)));
+            }
+            if let Some(parent) = parent {
+                lines.push(format!("RELATED-TO;RELTYPE=PARENT:{}", escape(parent)));
+            }
+            lines.push("END:VTODO".to_string());
+        }
+
+        for (i, child) in item.items.iter().enumerate() {
+            let child_indices = [indices, &[i]].concat();
+            self.write_item(child, &child_indices, Some(&uid), stamp, lines);
+        }
+    }
+
+    /// A UID for an item, from its ID if it has one. Otherwise it is made from where the item is,
+    /// like `Todo/2/1@todo-rs`.
+    fn uid(&self, item: &Item, indices: &[usize]) -> String {
+        match &item.id {
+            Some(id) => format!("{id}{UID_SUFFIX}"),
+            None => format!(
+                "{}/{}{UID_SUFFIX}",
+                self.name,
+                indices
+                    .iter()
+                    .map(|index| (index + 1).to_string())
+                    .collect::<Vec<String>>()
+                    .join("/")
+            ),
+        }
+    }
+}
+
+/// A VTODO that is being read, before it is put in its place.
+struct Entry {
+    uid: Option<String>,
+    parent: Option<String>,
+    item: Item,
+    /// The priority from `X-TODO-RS-PRIORITY`, which is used instead of `PRIORITY`.
+    exact_priority: Option<i64>,
+}
+
+impl Entry {
+    fn new() -> Entry {
+        Entry {
+            uid: None,
+            parent: None,
+            item: Item {
+                completed: false,
+                archived: false,
+                priority: 0,
+                date: None,
+                created: None,
+                recurrence: None,
+                name: String::new(),
+                tags: vec![],
+                id: None,
+                note: None,
+                items: vec![],
+                trailing: vec![],
+                source: None,
+            },
+            exact_priority: None,
+        }
+    }
+
+    fn read(&mut self, property: &str, parameters: &[String], value: &str) -> Result<(), Error> {
+        let item = &mut self.item;
+        match property {
+            "UID" => {
+                let uid = unescape(value);
+                // IDs can't have slashes, so UIDs made from where an item was aren't read as one.
+                if let Some(id) = uid.strip_suffix(UID_SUFFIX)
+                    && !id.is_empty()
+                    && !id.contains("/")
+                {
+                    item.id = Some(id.to_string());
+                }
+                self.uid = Some(uid);
+            }
+            "SUMMARY" => item.name = unescape(value),
+            "DESCRIPTION" => item.note = Some(unescape(value)),
+            "DUE" => item.date = Some(read_date(value)?),
+            "CREATED" => item.created = Some(read_date(value)?),
+            "PRIORITY" => {
+                let priority = match_result!(
+                    value.parse::<i64>(),
+                    Ics,
+                    format!("The priority '{value}' isn't a number.")
+                );
+                if self.exact_priority.is_none() {
+                    item.priority = todo_priority(priority);
+                }
+            }
+            "X-TODO-RS-PRIORITY" => {
+                let priority = match_result!(
+                    value.parse::<i64>(),
+                    Ics,
+                    format!("The priority '{value}' isn't a number.")
+                );
+                self.exact_priority = Some(priority);
+                item.priority = priority;
+            }
+            // Cancelled items won't be done, so they are closed like completed ones are.
+            "STATUS" => match value.to_ascii_uppercase().as_str() {
+                "COMPLETED" | "CANCELLED" => item.completed = true,
+                _ => {}
+            },
+            "X-TODO-RS-ARCHIVED" if value.eq_ignore_ascii_case("TRUE") => {
+                item.completed = true;
+                item.archived = true;
+            }
+            "COMPLETED" => item.completed = true,
+            "CATEGORIES" => {
+                for category in split_list(value) {
+                    let category = unescape(&category);
+                    let tag = if tags::is_tag(&category) {
+                        category
+                    } else {
+                        format!(
+                            "+{}",
+                            category.split_whitespace().collect::<Vec<&str>>().join("-")
+                        )
+                    };
+                    if tags::is_tag(&tag)
+                        && !item
+                            .tags
+                            .iter()
+                            .any(|other| other.eq_ignore_ascii_case(&tag))
+                    {
+                        item.tags.push(tag);
+                    }
+                }
+            }
+            // Only the parent matters, since children point to their parents themselves.
+            "RELATED-TO"
+                if parameters.iter().all(|parameter| {
+                    !parameter.to_ascii_uppercase().starts_with("RELTYPE=")
+                        || parameter.eq_ignore_ascii_case("RELTYPE=PARENT")
+                }) =>
+            {
+                self.parent = Some(unescape(value));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Makes the item for an entry, with the entries under it as its children.
+fn attach(index: usize, entries: &[Entry], visited: &mut [bool]) -> Item {
+    visited[index] = true;
+    let mut item = entries[index].item.clone();
+
+    for child in 0..entries.len() {
+        if !visited[child]
+            && entries[child].parent.is_some()
+            && entries[child].parent == entries[index].uid
+        {
+            item.items.push(attach(child, entries, visited));
+        }
+    }
+
+    item
+}
+
+/// iCalendar priorities go from 1 (the most important) to 9, and 0 means that there isn't one, so
+/// todo's priorities from 1 to 9 are flipped. Higher priorities are 1, and lower ones are 9.
+fn ics_priority(priority: i64) -> i64 {
+    (10 - priority).clamp(1, 9)
+}
+
+fn todo_priority(priority: i64) -> i64 {
+    match priority {
+        1..=9 => 10 - priority,
+        _ => 0,
+    }
+}
+
+/// A date like `20260613`, which is how dates are written in iCalendar.
+fn compact(date: Date) -> String {
+    date.iso().replace("-", "")
+}
+
+/// Reads a date (or the date of a time) like `20260613` or `20260613T170000Z`.
+fn read_date(value: &str) -> Result<Date, Error> {
+    let date = value.get(..8).unwrap_or(value);
+    Ok(match_error!(
+        Date::from_iso(&format!(
+            "{}-{}-{}",
+            date.get(..4).unwrap_or_default(),
+            date.get(4..6).unwrap_or_default(),
+            date.get(6..).unwrap_or_default()
+        )),
+        Ics,
+        format!("'{value}' isn't a date like 20260613.")
+    ))
+}
+
+/// Escapes text so that it can be a value, like `a\, b\; c\n`.
+fn escape(text: &str) -> String {
+    text.replace("\\", "\\\\")
+        .replace(";", "\\;")
+        .replace(",", "\\,")
+        .replace("\r\n", "\\n")
+        .replace("\n", "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut output = String::new();
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            output.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('n') | Some('N') => output.push('\n'),
+            Some(escaped) => output.push(escaped),
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+/// Splits a value with more than one part, like `CATEGORIES:a,b\,c`, on the commas that aren't
+/// escaped.
+fn split_list(value: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut characters = value.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => {
+                let last = parts.last_mut().expect("There is always a part.");
+                last.push(character);
+                last.extend(characters.next());
+            }
+            ',' => parts.push(String::new()),
+            _ => parts
+                .last_mut()
+                .expect("There is always a part.")
+                .push(character),
+        }
+    }
+    parts
+}
+
+/// Splits a line that is longer than 75 bytes over more than one line, each starting with a space.
+fn fold(line: &str) -> String {
+    let mut output = String::new();
+    let mut length = 0;
+    for character in line.chars() {
+        if length + character.len_utf8() > 75 {
+            output += "\r\n ";
+            length = 1;
+        }
+        output.push(character);
+        length += character.len_utf8();
+    }
+    output
+}
+
+/// Joins lines that were split by `fold`.
+fn unfold(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in input.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => *last += rest,
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Splits a line like `RELATED-TO;RELTYPE=PARENT:abc` into its property, its parameters and its
+/// value. Parameters can have colons in quotes, so the value starts at the first colon that isn't.
+fn split_line(line: &str) -> Option<(String, Vec<String>, String)> {
+    let mut quoted = false;
+    let mut value_start = None;
+    for (position, character) in line.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            ':' if !quoted => {
+                value_start = Some(position);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let value_start = value_start?;
+    let mut parts = line[..value_start].split(";");
+    let property = parts.next()?.to_ascii_uppercase();
+    Some((
+        property,
+        parts.map(|part| part.to_string()).collect(),
+        line[value_start + 1..].to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "# Garden, Shed; and \\Yard
+
+- [ ] \\2\\6/13/2026\\ Replant the garden, then water it; if it hasn't rained for a really long time
+   Dig it up first, then plant it.
+   Use the good seeds; the others, not so much.
+ - [ ] \\7\\6/14/2026\\ Research seeds
+ - [ ] \\6/16/2026\\ Buy some compost
+  - [ ] \\6/15/2026\\ Find a shop
+- [a] \\6/1/2026\\ Fix the fence
+- [ ] \\12\\6/20/2026\\ Clean the gutters
+";
+
+    fn parse(text: &str) -> Document {
+        let document =
+            Document::parse(text.to_string(), PathBuf::from("/tmp"), ".todo".to_string());
+        assert!(
+            document.diagnostics.is_empty(),
+            "{:?}",
+            document.diagnostics
+        );
+        document
+    }
+
+    /// The name, priority, date, completion, note and children of an item, which are what are
+    /// kept in a VTODO.
+    fn summary(item: &Item) -> String {
+        format!(
+            "{} {} {:?} {} {} {:?} [{}]",
+            item.name,
+            item.priority,
+            item.date.map(|date| date.iso()),
+            item.completed,
+            item.archived,
+            item.note,
+            item.items
+                .iter()
+                .map(summary)
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        let document = parse(LIST);
+        let ics = document.to_ics(&[]).expect("The list should be exported.");
+
+        assert!(ics.split("\r\n").all(|line| line.len() <= 75), "{ics}");
+        assert!(ics.contains("\r\nPRIORITY:8\r\n"), "{ics}");
+        assert!(ics.contains("\r\nPRIORITY:3\r\n"), "{ics}");
+        assert!(ics.contains("\r\nX-TODO-RS-PRIORITY:12\r\n"), "{ics}");
+        assert!(
+            ics.contains("X-WR-CALNAME:Garden\\, Shed\\; and \\\\Yard"),
+            "{ics}"
+        );
+
+        let imported =
+            Document::from_ics(&ics, PathBuf::from("/tmp")).expect("The list should be imported.");
+        assert_eq!(imported.name, document.name);
+
+        assert_eq!(
+            imported.items.iter().map(summary).collect::<Vec<String>>(),
+            document.items.iter().map(summary).collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn alarms_are_not_part_of_the_item() {
+        let ics = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VTODO\r
+UID:1\r
+SUMMARY:Water the plants\r
+DESCRIPTION:The ones on the windowsill.\r
+DUE;VALUE=DATE:20260613\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+DESCRIPTION:Reminder\r
+SUMMARY:Alarm\r
+TRIGGER:-PT15M\r
+END:VALARM\r
+PRIORITY:1\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+        let document =
+            Document::from_ics(ics, PathBuf::from("/tmp")).expect("The list should be imported.");
+
+        assert_eq!(document.items.len(), 1);
+        assert_eq!(document.items[0].name, "Water the plants");
+        assert_eq!(
+            document.items[0].note.as_deref(),
+            Some("The ones on the windowsill.")
+        );
+        assert_eq!(document.items[0].priority, 9);
+    }
+}
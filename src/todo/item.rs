@@ -1,13 +1,22 @@
 use crate::date;
 use crate::date::Date;
-use crate::error::{CodeComponent::ItemParser, CodeComponent::TodoItem, Error};
+use crate::date::recurrence::Recurrence;
+use crate::error::{CodeComponent::TodoItem, Error};
+use crate::match_error;
 use crate::output::buffer::OutputBuffer;
 use crate::output::color::Color;
 use crate::output::line::OutputLine;
 use crate::output::segment::OutputSegment;
 use crate::output::style::Style;
-use crate::todo::list::TodoList;
-use crate::{match_error, match_option};
+use crate::settings;
+use crate::todo::diagnostic::{Diagnostic, column};
+use crate::todo::list::{List, TodoList};
+use crate::todo::markdown::{self, ListFormat};
+use crate::todo::tags;
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone)]
 pub struct Item {
@@ -15,70 +24,427 @@ pub struct Item {
     pub archived: bool,
     pub priority: i64,
     pub date: Option<date::Date>,
+    /// When the item was added, if that is known.
+    pub created: Option<date::Date>,
+    /// How often the item repeats. When a repeating item is completed, the next occurrence is
+    /// added next to it.
+    pub recurrence: Option<Recurrence>,
     pub name: String,
+    /// Tags like `+work` or `@home`, with their sigil. They are written at the end of the name.
+    pub tags: Vec<String>,
+    /// An optional identifier that doesn't change when the item is renamed or moved.
+    pub id: Option<String>,
+    /// Free text that is written on the lines under the item, like acceptance criteria.
+    pub note: Option<String>,
     pub items: crate::todo::list::List,
+    /// Lines after this item (and its note) that aren't items themselves, like comments or blank
+    /// lines. They are saved exactly as they were written, and aren't a part of the item, so they
+    /// stay where they are when it is removed or moved.
+    pub trailing: Vec<String>,
+    /// The line that the item was parsed from, so that it can be saved as it was written.
+    pub source: Option<SourceLine>,
+}
+
+/// Where an item came from in a file.
+#[derive(Debug, Clone)]
+pub struct SourceLine {
+    pub depth: usize,
+    pub indent: String,
+    /// The line without its indentation.
+    pub text: String,
+    /// What the line would have been if the item was written out from scratch. If that is still
+    /// the same when saving, nothing has changed and the original text can be used.
+    pub canonical: String,
+    /// The note's lines as they were written, and the note that they were parsed into.
+    pub note_lines: Vec<String>,
+    pub note: Option<String>,
+}
+
+/// How many characters of whitespace a line starts with.
+fn indentation(line: &str) -> usize {
+    line.chars().count() - line.trim_start().chars().count()
+}
+
+/// The fields that an item can have in its braces in a Markdown list.
+const KEYS: &[&str] = &[
+    "priority", "due", "date", "repeat", "created", "id", "archived",
+];
+
+/// Escapes a name so that it can be written on an item's line and read back the same. Backslashes
+/// are doubled, words that would be read as tags get a backslash before them (like `\+1st`), and
+/// line breaks, tabs, spaces at either end and other control characters are written as their code,
+/// like `\u{a}`. In Markdown lists, braces are escaped too, so that they aren't read as metadata.
+/// Other escapes aren't used, so that names from before there were escapes (like `C:\temp`) are
+/// mostly read the same.
+pub fn escape(name: &str, format: ListFormat) -> String {
+    let mut output = String::new();
+    let last = name.chars().count().saturating_sub(1);
+    let mut word_start = true;
+
+    for (i, (position, character)) in name.char_indices().enumerate() {
+        match character {
+            '\\' => output += "\\\\",
+            '{' | '}' if format == ListFormat::Markdown => {
+                output.push('\\');
+                output.push(character);
+            }
+            ' ' if i != 0 && i != last => output.push(' '),
+            '+' | '@'
+                if word_start
+                    && tags::is_tag(
+                        name[position..]
+                            .split(char::is_whitespace)
+                            .next()
+                            .unwrap_or_default(),
+                    ) =>
+            {
+                output.push('\\');
+                output.push(character);
+            }
+            character if character.is_whitespace() || character.is_control() => {
+                output += &format!("\\u{{{:x}}}", character as u32);
+            }
+            character => output.push(character),
+        }
+        word_start = character.is_whitespace();
+    }
+
+    output
+}
+
+/// Reads a name that was written by `escape`. Backslashes that aren't part of an escape are kept,
+/// so names from before there were escapes are read the same as they were.
+pub fn unescape(text: &str) -> String {
+    let mut output = String::new();
+    let mut characters = text.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            output.push(character);
+            continue;
+        }
+
+        let escaped = match characters.peek() {
+            Some('\\') => Some('\\'),
+            Some('+') => Some('+'),
+            Some('{') => Some('{'),
+            Some('}') => Some('}'),
+            Some('@') => Some('@'),
+            Some('u') => {
+                let mut code = characters.clone();
+                code.next();
+                if code.next() == Some('{') {
+                    let hex = code
+                        .by_ref()
+                        .take_while(|character| *character != '}')
+                        .collect::<String>();
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Some(escaped) => {
+                            output.push(escaped);
+                            characters = code;
+                            continue;
+                        }
+                        None => None,
+                    }
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        match escaped {
+            Some(escaped) => {
+                output.push(escaped);
+                characters.next();
+            }
+            None => output.push('\\'),
+        }
+    }
+
+    output
 }
 
 impl Item {
-    /// Parses a single line from a file to create a todo item. It does not handle parsing
-    /// children.
-    pub fn from(input: String, sub_items: Vec<Item>) -> Result<Item, Error> {
-        let mut sections = input.split("\\");
+    /// Whether a line from a file is an item, rather than a note or a blank line.
+    pub fn is_item_line(line: &str) -> bool {
+        let line = line.trim_start();
+        line.starts_with("- [") && (line.get(4..6) == Some("] ") || line.get(4..) == Some("]"))
+    }
 
-        let archived = &sections.clone().next().unwrap_or("- [ ]").trim_start()[3..4] == "a";
-        let completed = &sections.next().unwrap_or("- [ ]").trim_start()[3..4] == "x" || archived;
+    /// Parses a single line from a file to create a todo item. It does not handle parsing
+    /// children. If part of the line can't be read, the problems are given back instead, so that
+    /// the line can be kept as it is. Otherwise, anything that looks wrong comes back with the item
+    /// as a warning.
+    pub fn parse(
+        input: &str,
+        format: ListFormat,
+    ) -> Result<(Item, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let line = input.trim_start();
+        // Where the line starts after its indentation, for the columns of problems.
+        let start = input.len() - line.len();
 
-        let has_priority_value = sections.clone().next().unwrap_or("").parse::<i64>().is_ok();
-        let priority = if has_priority_value {
-            sections.next().unwrap_or("0").trim().parse().unwrap_or(0)
-        } else {
-            0
+        let checkbox = line.get(3..).and_then(|rest| rest.chars().next());
+        let (mut completed, mut archived) = match checkbox {
+            Some(' ') => (false, false),
+            Some('x') | Some('X') => (true, false),
+            Some('a') | Some('A') => (true, true),
+            other => {
+                return Err(vec![Diagnostic::error(
+                    column(input, start + 3),
+                    1,
+                    match other {
+                        Some(other) => format!(
+                            "'{other}' isn't a checkbox. Use ' ' (open), 'x' (completed) or 'a' (archived)."
+                        ),
+                        None => "The checkbox isn't finished, like '- [ ]'.".to_string(),
+                    },
+                )]);
+            }
         };
 
-        let has_date_value = (sections.clone().count() > 1) && sections.clone().next().is_some();
-        let date = if has_date_value {
-            let section = &match_option!(
-                sections.next(),
-                ItemParser,
-                format!("Could not parse item when looking for a date.")
-            )
-            .trim()
-            .to_string();
-            if section != "" {
-                Date::from(section).ok()
-            } else {
-                None
+        let mut priority = None;
+        let mut date = None;
+        let mut created = None;
+        let mut recurrence = None;
+        let mut id = None;
+        let mut problems = vec![];
+
+        let body = line.get(6..).unwrap_or_default();
+        let body_start = start + line.len() - body.len();
+        let name = match format {
+            ListFormat::Todo => {
+                // The metadata ends with a backslash followed by a space. Backslashes in the name are
+                // escaped, so the first one that is followed by a space is the end of the metadata. If
+                // there isn't one, the name is after the last backslash.
+                let (metadata, name) = match body.strip_prefix("\\") {
+                    Some(rest) if rest.starts_with(" ") => ("", rest),
+                    Some(rest) => match rest.find("\\ ") {
+                        Some(end) => (&rest[..end], &rest[end + 1..]),
+                        None => rest.rsplit_once("\\").unwrap_or(("", rest)),
+                    },
+                    None => ("", body),
+                };
+
+                let mut offset = body_start + 1;
+                for section in metadata.split("\\").filter(|_| !metadata.is_empty()) {
+                    let section_start = offset + section.len() - section.trim_start().len();
+                    offset += section.len() + 1;
+                    let section = section.trim();
+                    let problem = |message: String| {
+                        Diagnostic::error(
+                            column(input, section_start),
+                            section.chars().count(),
+                            message,
+                        )
+                    };
+
+                    if let Some(value) = section.strip_prefix("@") {
+                        id = Some(value.to_string());
+                    } else if priority.is_none()
+                        && let Ok(value) = section.parse::<i64>()
+                    {
+                        priority = Some(value);
+                    } else if let Some(value) = section.strip_prefix("created ") {
+                        match Date::from(value) {
+                            Ok(value) => created = Some(value),
+                            Err(_) => problems.push(problem(format!(
+                                "'{value}' isn't a date, so it can't be when the item was created."
+                            ))),
+                        }
+                    } else if let Ok(value) = Recurrence::parse(section) {
+                        recurrence = Some(value);
+                    } else if !section.is_empty() {
+                        match Date::from(section) {
+                            Ok(value) if date.is_none() => date = Some(value),
+                            Ok(_) => problems.push(problem(format!(
+                                "The item already has a date, so '{section}' can't be another one."
+                            ))),
+                            Err(_) => problems.push(problem(format!(
+                                "'{section}' isn't a priority, a date, a repeating rule, a creation date or an ID."
+                            ))),
+                        }
+                    }
+                }
+
+                name
+            }
+            ListFormat::Markdown => {
+                let (name, fields) = markdown::split_fields(body, body_start, KEYS);
+                for field in &fields {
+                    let problem = |message: String| field.problem(input, message);
+                    match (field.key, field.value) {
+                        ("priority", Some(value)) => match value.parse::<i64>() {
+                            Ok(value) => priority = Some(value),
+                            Err(_) => problems.push(problem(format!(
+                                "The priority '{value}' isn't a whole number."
+                            ))),
+                        },
+                        ("due" | "date", Some(value)) => match Date::from(value) {
+                            Ok(value) => date = Some(value),
+                            Err(_) => problems.push(problem(format!("'{value}' isn't a date."))),
+                        },
+                        ("repeat", Some(value)) => match Recurrence::parse(value) {
+                            Ok(value) => recurrence = Some(value),
+                            Err(_) => problems.push(problem(format!(
+                                "'{value}' isn't a repeating rule, like \"every monday\"."
+                            ))),
+                        },
+                        ("created", Some(value)) => match Date::from(value) {
+                            Ok(value) => created = Some(value),
+                            Err(_) => problems.push(problem(format!(
+                                "'{value}' isn't a date, so it can't be when the item was created."
+                            ))),
+                        },
+                        ("id", Some(value)) if !value.is_empty() => id = Some(value.to_string()),
+                        ("archived", None) => {
+                            completed = true;
+                            archived = true;
+                        }
+                        _ => problems.push(problem(format!(
+                            "'{}' isn't something that an item can have. Use priority, due, repeat, created, id or archived.",
+                            field.key
+                        ))),
+                    }
+                }
+
+                name
             }
-        } else {
-            None
         };
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        let name = name.trim();
+        let mut warnings = vec![];
+        if name.is_empty() {
+            warnings.push(Diagnostic::warning(
+                column(input, input.len()),
+                1,
+                "The item doesn't have a name.".to_string(),
+            ));
+        }
+        // Tags are found before the name is unescaped, so that escaped words that look like tags
+        // stay in the name.
+        let (name, tags) = tags::split_tags(name);
+        let name = unescape(&name);
 
-        let last_section = sections.collect::<Vec<&str>>().join("\\");
+        Ok((
+            Item {
+                name,
+                priority: priority.unwrap_or(0),
+                date,
+                created,
+                recurrence,
+                tags,
+                id,
+                completed,
+                archived,
+                items: vec![],
+                note: None,
+                trailing: vec![],
+                source: None,
+            },
+            warnings,
+        ))
+    }
 
-        let name = if has_date_value || has_priority_value {
-            last_section.trim().to_string()
-        } else {
-            input.trim_start()[6..].to_string()
+    /// Makes a new ID that isn't used by any item in the list. IDs are short, random and
+    /// lowercase, so that they are easy to type.
+    pub fn generate_id(list: &List) -> String {
+        let alphabet = "0123456789abcdefghijklmnopqrstuvwxyz"
+            .chars()
+            .collect::<Vec<char>>();
+
+        loop {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.as_nanos())
+                    .unwrap_or(0),
+            );
+            let mut value = hasher.finish();
+
+            let mut id = String::new();
+            for _ in 0..5 {
+                id.push(alphabet[(value % 36) as usize]);
+                value /= 36;
+            }
+
+            if !list.has_id(&id) {
+                return id;
+            }
+        }
+    }
+
+    /// This formats it for saving, NOT FOR DISPLAY. Lines that haven't changed since they were
+    /// parsed are written exactly as they were.
+    pub fn to_string(&self, depth: usize, format: ListFormat) -> String {
+        self.write(depth, None, format)
+    }
+
+    /// Items that are new (or have moved) use the same indentation as their siblings, if any of
+    /// them came from the file.
+    fn write(&self, depth: usize, sibling_indent: Option<&str>, format: ListFormat) -> String {
+        let mut output = String::new();
+
+        let line = self.saved_line(format);
+        let source = self.source.as_ref().filter(|source| source.depth == depth);
+        let indent = match (source, sibling_indent) {
+            (Some(source), _) => source.indent.clone(),
+            (None, Some(indent)) => indent.to_string(),
+            (None, None) => format.indent(depth),
         };
 
-        let mut children = sub_items.clone();
-        children.sort_by(|a, b| b.priority.cmp(&a.priority));
-
-        Ok(Item {
-            name: name,
-            priority: priority,
-            date: date,
-            completed: completed,
-            archived: archived,
-            items: children,
-        })
+        match source {
+            Some(source) if source.canonical == line => {
+                output += &format!("{indent}{}\n", source.text);
+            }
+            _ => output += &format!("{indent}{line}\n"),
+        }
+
+        match source {
+            Some(source) if source.note == self.note => {
+                for note_line in &source.note_lines {
+                    output += &format!("{note_line}\n");
+                }
+            }
+            _ => {
+                // Notes are indented a bit more than any children would be.
+                for note_line in self.note.iter().flat_map(|note| note.lines()) {
+                    if note_line.trim().is_empty() {
+                        output += "\n";
+                    } else {
+                        output += &format!("{indent}  {note_line}\n");
+                    }
+                }
+            }
+        }
+
+        for trailing in &self.trailing {
+            output += &format!("{trailing}\n");
+        }
+
+        let child_indent = self.items.iter().find_map(|child| match &child.source {
+            Some(source) if source.depth == depth + 1 => Some(source.indent.clone()),
+            _ => None,
+        });
+        for child in &self.items {
+            output += &child.write(depth + 1, child_indent.as_deref(), format);
+        }
+
+        output
     }
 
-    /// This formats it for saving, NOT FOR DISPLAY
-    pub fn to_string(&self, depth: usize) -> String {
+    /// The line for just this item, without any indentation.
+    fn saved_line(&self, format: ListFormat) -> String {
+        if format == ListFormat::Markdown {
+            return self.markdown_line();
+        }
+
         let mut output = String::new();
 
-        let indent = " ".repeat(depth);
         let completed = if self.archived {
             "a"
         } else if self.completed {
@@ -91,36 +457,187 @@ impl Item {
             Some(val) => &val.display(),
             _ => "",
         };
-        let name = &self.name;
-
-        let mut children = String::new();
-
-        for child in self.clone().items {
-            children += &child.to_string(depth + 1);
-        }
+        let name = self.escaped_name(format);
 
-        output += &format!("{indent}- [{completed}] ");
+        output += &format!("- [{completed}] ");
         if self.priority != 0 {
             output += &format!("\\{priority}");
         }
         if self.date.is_some() {
             output += &format!("\\{date}");
         }
-        if self.date.is_some() || self.priority != 0 {
-            output += &format!("\\ ");
+        if let Some(recurrence) = &self.recurrence {
+            output += &format!("\\{}", recurrence.display());
         }
-        output += &format!("{name}\n");
-
-        if self.items.len() > 0 {
-            output += &children;
+        if let Some(created) = self.created {
+            output += &format!("\\created {}", created.display());
+        }
+        if let Some(id) = &self.id {
+            output += &format!("\\@{id}");
+        }
+        // A name that starts with a backslash would be read as metadata, so it gets an empty
+        // metadata section before it.
+        if self.date.is_some()
+            || self.priority != 0
+            || self.created.is_some()
+            || self.recurrence.is_some()
+            || self.id.is_some()
+            || name.starts_with("\\")
+        {
+            output += "\\ ";
         }
+        output += &name;
 
         output
     }
 
+    /// The line for just this item in a Markdown list. Archived items are checked, so that they
+    /// look done on a forge.
+    fn markdown_line(&self) -> String {
+        let completed = if self.completed { "x" } else { " " };
+
+        let mut fields = vec![];
+        if self.priority != 0 {
+            fields.push(("priority", Some(self.priority.to_string())));
+        }
+        if let Some(date) = self.date {
+            fields.push(("due", Some(date.iso())));
+        }
+        if let Some(recurrence) = &self.recurrence {
+            fields.push(("repeat", Some(recurrence.display())));
+        }
+        if let Some(created) = self.created {
+            fields.push(("created", Some(created.iso())));
+        }
+        if let Some(id) = &self.id {
+            fields.push(("id", Some(id.clone())));
+        }
+        if self.archived {
+            fields.push(("archived", None));
+        }
+
+        format!(
+            "- [{completed}] {}{}",
+            self.escaped_name(ListFormat::Markdown),
+            markdown::write_fields(&fields)
+        )
+    }
+
+    /// Takes out the lines in this item and its children that aren't a part of any of them, like
+    /// comments and headings, in the order that they are in the file. The items' own lines and
+    /// notes stay.
+    pub fn take_loose_lines(&mut self) -> Vec<String> {
+        let mut lines = std::mem::take(&mut self.trailing);
+        for child in &mut self.items {
+            lines.extend(child.take_loose_lines());
+        }
+        lines
+    }
+
+    /// Takes out the lines after the last line of this item and its children.
+    pub fn take_lines_after(&mut self) -> Vec<String> {
+        match self.items.last_mut() {
+            Some(child) => child.take_lines_after(),
+            None => std::mem::take(&mut self.trailing),
+        }
+    }
+
+    /// Puts lines after the last line of this item and its children.
+    pub fn append_lines(&mut self, lines: Vec<String>) {
+        match self.items.last_mut() {
+            Some(child) => child.append_lines(lines),
+            None => self.trailing.extend(lines),
+        }
+    }
+
+    /// Remembers the line that this item was parsed from. The lines after it that are indented
+    /// more than it is are its note, which is taken out of the trailing lines.
+    pub fn set_source(&mut self, line: &str, depth: usize, format: ListFormat) {
+        let text = line.trim_start();
+        let indent = &line[..line.len() - text.len()];
+
+        let note_length = self
+            .trailing
+            .iter()
+            .take_while(|trailing| {
+                trailing.trim().is_empty() || indentation(trailing) > indent.chars().count()
+            })
+            .count();
+        // Blank lines after the note are between the items, not a part of it.
+        let note_length = self.trailing[..note_length]
+            .iter()
+            .rposition(|trailing| !trailing.trim().is_empty())
+            .map_or(0, |last| last + 1);
+        let note_lines = self.trailing.drain(..note_length).collect::<Vec<String>>();
+
+        let note_indentation = note_lines
+            .iter()
+            .filter(|note_line| !note_line.trim().is_empty())
+            .map(|note_line| indentation(note_line))
+            .min()
+            .unwrap_or(0);
+        self.note = if note_lines.is_empty() {
+            None
+        } else {
+            Some(
+                note_lines
+                    .iter()
+                    .map(|note_line| note_line.chars().skip(note_indentation).collect::<String>())
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            )
+        };
+
+        self.source = Some(SourceLine {
+            depth,
+            indent: indent.to_string(),
+            text: text.to_string(),
+            canonical: self.saved_line(format),
+            note_lines,
+            note: self.note.clone(),
+        });
+    }
+
+    /// Sets the note from some text, like what was written in an editor. Blank lines at the start
+    /// and end are left out, and an empty note removes it.
+    pub fn set_note(&mut self, text: &str) {
+        let text = text.trim_end().trim_start_matches(['\n', '\r']);
+        self.note = if text.trim().is_empty() {
+            None
+        } else {
+            Some(
+                text.lines()
+                    .map(|line| line.trim_end())
+                    .collect::<Vec<&str>>()
+                    .join("\n"),
+            )
+        };
+    }
+
+    /// Set colors based on the priority (including urgency), using the config's theme.
+    pub fn priority_color(priority: i64) -> Color {
+        settings::get().theme.priority_color(priority)
+    }
+
+    /// The name with the tags after it, like it is written in the file.
+    pub fn full_name(&self) -> String {
+        let mut words = vec![self.name.clone()];
+        words.extend(self.tags.clone());
+        words.join(" ").trim().to_string()
+    }
+
+    /// The name and tags the way they are written in a file, with anything that would be read as
+    /// something else escaped.
+    fn escaped_name(&self, format: ListFormat) -> String {
+        let mut words = vec![escape(&self.name, format)];
+        words.extend(self.tags.clone());
+        words.retain(|word| !word.is_empty());
+        words.join(" ")
+    }
+
     /// This is a number from 0 to 7+, that represents how close today is to the item's date
     /// It is more than 7 if the day has already passed.
-    /// It starts ticking up at 7 days until the date
+    /// It starts ticking up at 7 days until the date (or however many days are set in the config).
     pub fn urgency(&self) -> Result<Option<i64>, Error> {
         if let Some(date) = self.date {
             let distance = match_error!(
@@ -132,18 +649,40 @@ impl Item {
                 TodoItem,
                 format!("Could not get the temporal distance to the item.")
             );
-            if distance > 7 {
-                return Ok(None);
+            let days = settings::get().urgency_days;
+            if distance > days {
+                Ok(None)
             } else {
-                return Ok(Some(7 - distance));
+                Ok(Some(days - distance))
             }
         } else {
-            return Ok(None);
+            Ok(None)
         }
     }
 
     pub fn format(&self, end: bool, lines: Vec<bool>) -> Result<OutputBuffer, Error> {
         let mut output = OutputBuffer::new();
+
+        let mut new_lines = lines.clone();
+        new_lines.push(end);
+
+        output.add(match_error!(
+            self.format_line(end, lines),
+            TodoItem,
+            format!("Could not format the item '{}'", self.name)
+        ));
+
+        output.append(match_error!(
+            self.items.format(new_lines),
+            TodoItem,
+            format!("Could not format child items")
+        ));
+
+        Ok(output)
+    }
+
+    /// Formats just this item's row of the tree, without any of its children.
+    pub fn format_line(&self, end: bool, lines: Vec<bool>) -> Result<OutputLine, Error> {
         let mut output_line = OutputLine::new();
 
         for level in lines.clone() {
@@ -172,9 +711,6 @@ impl Item {
             ));
         }
 
-        let mut new_lines = lines.clone();
-        new_lines.push(end);
-
         let urgency = match_error!(
             self.urgency(),
             TodoItem,
@@ -189,36 +725,23 @@ impl Item {
         let date = match urgency {
             Some(urgency) => &format!(
                 "{num} day{s}",
-                num = 7 - urgency,
-                s = if 7 - urgency == 1 { "" } else { "s" }
+                num = settings::get().urgency_days - urgency,
+                s = if settings::get().urgency_days - urgency == 1 {
+                    ""
+                } else {
+                    "s"
+                }
             ),
             _ => {
                 if let Some(date) = self.date {
-                    &date.display()
+                    &date.pretty()
                 } else {
                     ""
                 }
             }
         };
 
-        // Set colors based on the priority
-        let color = match priority {
-            i64::MIN..=-7 => Color::Green,
-            -6 => Color::Green,
-            -5 => Color::Green,
-            -4 => Color::Blue,
-            -3 => Color::Blue,
-            -2 => Color::Cyan,
-            -1 => Color::Cyan,
-            0 => Color::Default,
-            1 => Color::Yellow,
-            2 => Color::Yellow,
-            3 => Color::Magenta,
-            4 => Color::Magenta,
-            5 => Color::Red,
-            6 => Color::Red,
-            7..=i64::MAX => Color::Red,
-        };
+        let color = Item::priority_color(priority);
 
         let style = if self.completed {
             *Style::new().dim().strikethrough()
@@ -226,12 +749,18 @@ impl Item {
             Style::normal()
         };
 
+        // Repeating items are marked, since completing them adds the next one.
+        let name = match self.recurrence {
+            Some(_) => format!("{} ↻", self.name),
+            None => self.name.clone(),
+        };
+
         if self.date.is_none() {
             output_line.add(OutputSegment::new(
                 &format!("{box} {priority} {name}",
                     box = if self.archived {"\u{24d0}"} else if self.completed { "▣" } else { "□" },
                     priority = priority,
-                    name = self.name,
+                    name = name,
                 ),
                 color,
                 style,
@@ -241,22 +770,27 @@ impl Item {
                 &format!("{box} {priority} ({date}) {name}",
                     box = if self.archived {"\u{24d0}"} else if self.completed { "▣" } else { "□" },
                     priority = priority,
-                    name = self.name,
+                    name = name,
                     date = date                ),
                 color,
                 style,
             ));
         }
+        self.format_tags(&mut output_line, style);
 
-        output.add(output_line);
-
-        output.append(match_error!(
-            self.items.format(new_lines),
-            TodoItem,
-            format!("Could not format child items")
-        ));
+        Ok(output_line)
+    }
 
-        Ok(output)
+    /// Adds the item's tags to the end of a line, in their own color so that they stand out from
+    /// the name.
+    fn format_tags(&self, line: &mut OutputLine, style: Style) {
+        for tag in &self.tags {
+            line.add(OutputSegment::new(
+                &format!(" {}", tag),
+                settings::get().theme.tags.clone(),
+                style,
+            ));
+        }
     }
 
     pub fn format_detail(&self, show_children: bool) -> Result<OutputBuffer, Error> {
@@ -275,7 +809,7 @@ impl Item {
         };
 
         let date = match self.date {
-            Some(date) => &date.clone().display(),
+            Some(date) => &date.clone().pretty(),
             _ => "",
         };
 
@@ -299,24 +833,7 @@ impl Item {
             _ => "",
         };
 
-        // Set colors based on the priority
-        let color = match priority {
-            i64::MIN..=-7 => Color::Green,
-            -6 => Color::Green,
-            -5 => Color::Green,
-            -4 => Color::Blue,
-            -3 => Color::Blue,
-            -2 => Color::Cyan,
-            -1 => Color::Cyan,
-            0 => Color::Default,
-            1 => Color::Yellow,
-            2 => Color::Yellow,
-            3 => Color::Magenta,
-            4 => Color::Magenta,
-            5 => Color::Red,
-            6 => Color::Red,
-            7..=i64::MAX => Color::Red,
-        };
+        let color = Item::priority_color(priority);
 
         let mut priority_line = OutputLine::new();
         priority_line.add(OutputSegment::new(
@@ -351,6 +868,61 @@ impl Item {
         output.add(date_line);
         output.add(name_line);
 
+        if !self.tags.is_empty() {
+            output.add(
+                OutputLine::new()
+                    .add(OutputSegment::new(
+                        &format!("Tags: {}", self.tags.join(" ")),
+                        settings::get().theme.tags.clone(),
+                        Style::new(),
+                    ))
+                    .clone(),
+            );
+        }
+
+        if let Some(recurrence) = &self.recurrence {
+            output.add(
+                OutputLine::new()
+                    .add(OutputSegment::new(
+                        &format!("Repeats: {}", recurrence.display()),
+                        Color::Default,
+                        Style::new(),
+                    ))
+                    .clone(),
+            );
+        }
+
+        if let Some(note) = &self.note {
+            output.add(
+                OutputLine::new()
+                    .add(OutputSegment::new("Note:", Color::Default, Style::new()))
+                    .clone(),
+            );
+            for note_line in note.lines() {
+                output.add(
+                    OutputLine::new()
+                        .add(OutputSegment::new(
+                            &format!("  {}", note_line),
+                            Color::Default,
+                            *Style::new().dim(),
+                        ))
+                        .clone(),
+                );
+            }
+        }
+
+        if let Some(id) = &self.id {
+            output.add(
+                OutputLine::new()
+                    .add(OutputSegment::new(
+                        &format!("ID: @{}", id),
+                        Color::Default,
+                        *Style::new().dim(),
+                    ))
+                    .clone(),
+            );
+        }
+
         if show_children {
             output.append(match_error!(
                 self.items.clone().format_overview(vec![]),
@@ -412,24 +984,7 @@ impl Item {
             self.priority + urgency.unwrap_or(0)
         };
 
-        // Set colors based on the priority
-        let color = match priority {
-            i64::MIN..=-7 => Color::Green,
-            -6 => Color::Green,
-            -5 => Color::Green,
-            -4 => Color::Blue,
-            -3 => Color::Blue,
-            -2 => Color::Cyan,
-            -1 => Color::Cyan,
-            0 => Color::Default,
-            1 => Color::Yellow,
-            2 => Color::Yellow,
-            3 => Color::Magenta,
-            4 => Color::Magenta,
-            5 => Color::Red,
-            6 => Color::Red,
-            7..=i64::MAX => Color::Red,
-        };
+        let color = Item::priority_color(priority);
 
         let style = if self.completed {
             *Style::new().dim().strikethrough()
@@ -456,6 +1011,7 @@ impl Item {
                 style,
             ));
         }
+        self.format_tags(&mut output_line, style);
 
         output.add(output_line);
 
@@ -470,3 +1026,135 @@ impl Item {
         Ok(output)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small random number generator (xorshift), so that the names are the same on every run.
+    struct Generator(u64);
+
+    impl Generator {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn pick<'a>(&mut self, options: &[&'a str]) -> &'a str {
+            options[(self.next() % options.len() as u64) as usize]
+        }
+    }
+
+    /// Pieces that names are made from, which are the kinds of things that are hard to write and
+    /// read back the same.
+    const PIECES: &[&str] = &[
+        "a",
+        "Fish",
+        "\\",
+        "C:\\temp",
+        "\\ ",
+        "\\\\",
+        "\\u{41}",
+        "\\+",
+        "🐟",
+        "👩‍🚀",
+        "\t",
+        " ",
+        "  ",
+        "+tag",
+        "@ctx",
+        "+",
+        "@",
+        "x",
+        "[ ]",
+        "42",
+        "-3",
+        "2026-10-18",
+        "6/13/2026",
+        "15/dec/2027",
+        "every monday",
+        "created",
+        "{",
+        "}",
+        "{priority=2}",
+        "{draft}",
+        "a=b",
+        "%20",
+        "pri:A",
+        "due:2026-10-18",
+        "\n",
+        "\u{7}",
+    ];
+
+    fn item(name: String, generator: &mut Generator) -> Item {
+        Item {
+            completed: false,
+            archived: false,
+            priority: [0, 3][(generator.next() % 2) as usize],
+            date: None,
+            created: None,
+            recurrence: None,
+            name,
+            tags: vec![],
+            id: generator
+                .next()
+                .is_multiple_of(2)
+                .then(|| "x83r0".to_string()),
+            note: None,
+            items: vec![],
+            trailing: vec![],
+            source: None,
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        let mut generator = Generator(0x5eed);
+
+        for format in [ListFormat::Todo, ListFormat::Markdown] {
+            for _ in 0..5000 {
+                let length = 1 + generator.next() % 6;
+                let name = (0..length)
+                    .map(|_| generator.pick(PIECES))
+                    .collect::<String>();
+                let item = item(name.clone(), &mut generator);
+
+                let line = item.to_string(0, format);
+                let line = line.trim_end_matches('\n');
+                assert!(!line.contains('\n'), "{name:?} was written as {line:?}");
+                let parsed = Item::parse(line, format)
+                    .unwrap_or_else(|problems| panic!("{line:?} couldn't be read: {problems:?}"));
+                assert_eq!(parsed.0.name, name, "{line:?}");
+                assert!(parsed.0.tags.is_empty(), "{line:?}");
+                assert_eq!(parsed.0.priority, item.priority, "{line:?}");
+                assert_eq!(parsed.0.id, item.id, "{line:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn braces_with_other_fields_stay_in_the_name() {
+        for (line, name, priority) in [
+            (
+                "- [ ] document ${HOME} {draft}",
+                "document ${HOME} {draft}",
+                0,
+            ),
+            (
+                "- [ ] Deploy {priority=2 draft}",
+                "Deploy {priority=2 draft}",
+                0,
+            ),
+            ("- [ ] Deploy {priority=2}", "Deploy", 2),
+            ("- [ ] An empty set {}", "An empty set {}", 0),
+            ("- [ ] Spaces { }", "Spaces { }", 0),
+        ] {
+            let (item, _) = Item::parse(line, ListFormat::Markdown)
+                .unwrap_or_else(|problems| panic!("{line:?} couldn't be read: {problems:?}"));
+            assert_eq!(item.name, name);
+            assert_eq!(item.priority, priority);
+        }
+    }
+}
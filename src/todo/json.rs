@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::date::Date;
+use crate::date::recurrence::Recurrence;
+use crate::error::{CodeComponent, CodeComponent::Json, Error};
+use crate::todo::document::Document;
+use crate::todo::item::Item;
+use crate::todo::sort::SortSpec;
+use crate::{match_error, match_result, propagate, search_paths};
+
+/// The JSON representation of a document. This is a stable schema for other programs, so it is
+/// kept separate from `Document` itself.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonDocument {
+    pub name: String,
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub date: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
+    /// A short name from the list's `# alias` line, for telling lists with the same name apart.
+    #[serde(default)]
+    pub alias: Option<String>,
+    /// The sort order from the list's `# sort` line, like "completed,priority:desc".
+    #[serde(default)]
+    pub sort: Option<String>,
+    #[serde(default)]
+    pub items: Vec<JsonItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonItem {
+    #[serde(default)]
+    pub completed: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub priority: i64,
+    #[serde(default)]
+    pub date: Option<String>,
+    /// When the item was added, as an ISO date.
+    #[serde(default)]
+    pub created: Option<String>,
+    /// A repeating rule, like "every monday".
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Free text from the lines under the item, with lines separated by "\n".
+    #[serde(default)]
+    pub note: Option<String>,
+    /// This is computed from the date, so it is ignored when importing.
+    #[serde(default, skip_deserializing)]
+    pub urgency: Option<i64>,
+    #[serde(default)]
+    pub children: Vec<JsonItem>,
+}
+
+impl Document {
+    pub fn to_json_value(&self) -> Result<JsonDocument, Error> {
+        let mut items = vec![];
+        for item in &self.items {
+            items.push(match_error!(
+                item.to_json_value(),
+                Json,
+                format!("Could not convert the item '{}' to JSON.", item.name)
+            ));
+        }
+
+        Ok(JsonDocument {
+            name: self.name.clone(),
+            path: Some(self.path.clone()),
+            priority: self.priority,
+            date: self.date.map(|date| date.iso()),
+            archived: self.archived,
+            alias: self.alias.clone(),
+            sort: self.sort.as_ref().map(|sort| sort.display()),
+            items,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        let value = match_error!(
+            self.to_json_value(),
+            Json,
+            format!("Could not convert the document '#{}' to JSON.", self.name)
+        );
+
+        Ok(match_result!(
+            serde_json::to_string_pretty(&value),
+            Json,
+            format!("Could not serialize the document '#{}'.", self.name)
+        ))
+    }
+
+    /// Create a document from JSON in the same schema that `to_json` outputs. The path in the JSON
+    /// is ignored, since the document is going to be saved at `path`.
+    pub fn from_json(input: &str, path: PathBuf) -> Result<Document, Error> {
+        let value: serde_json::Value = match_result!(
+            serde_json::from_str(input),
+            Json,
+            format!("Could not parse the input as JSON.")
+        );
+
+        // `list --format json` outputs an array, so accept that as long as it is unambiguous.
+        let value = match value {
+            serde_json::Value::Array(mut documents) => {
+                if documents.len() != 1 {
+                    return Err(propagate!(
+                        Json,
+                        format!(
+                            "Expected a single document, but got an array of {}.",
+                            documents.len()
+                        )
+                    ));
+                }
+                documents.remove(0)
+            }
+            value => value,
+        };
+
+        let document: JsonDocument = match_result!(
+            serde_json::from_value(value),
+            Json,
+            format!("The JSON does not match the document schema.")
+        );
+
+        let mut items = vec![];
+        for item in document.items {
+            items.push(match_error!(
+                Item::from_json_value(item),
+                Json,
+                format!(
+                    "Could not read an item of the document '#{}'.",
+                    document.name
+                )
+            ));
+        }
+
+        Ok(Document {
+            name: document.name,
+            path,
+            file_name: search_paths::default_list_file(),
+            priority: document.priority,
+            date: match_error!(
+                parse_json_date(document.date),
+                Json,
+                format!("Could not read the date of the document.")
+            ),
+            items,
+            archived: document.archived,
+            alias: document.alias,
+            sort: match document.sort {
+                Some(sort) => Some(match_error!(
+                    SortSpec::parse(&sort),
+                    Json,
+                    format!("Could not read the sort order of the document.")
+                )),
+                None => None,
+            },
+            header_lines: vec![],
+            preamble: vec![String::new()],
+            final_newline: true,
+            loaded: None,
+            diagnostics: vec![],
+        })
+    }
+}
+
+impl Item {
+    pub fn to_json_value(&self) -> Result<JsonItem, Error> {
+        let mut children = vec![];
+        for child in &self.items {
+            children.push(match_error!(
+                child.to_json_value(),
+                Json,
+                format!("Could not convert the item '{}' to JSON.", child.name)
+            ));
+        }
+
+        Ok(JsonItem {
+            completed: self.completed,
+            archived: self.archived,
+            priority: self.priority,
+            date: self.date.map(|date| date.iso()),
+            created: self.created.map(|date| date.iso()),
+            recurrence: self
+                .recurrence
+                .as_ref()
+                .map(|recurrence| recurrence.display()),
+            name: self.name.clone(),
+            tags: self.tags.clone(),
+            id: self.id.clone(),
+            note: self.note.clone(),
+            urgency: match_error!(
+                self.urgency(),
+                Json,
+                format!("Could not get the urgency of '{}'.", self.name)
+            ),
+            children,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        let value = match_error!(
+            self.to_json_value(),
+            Json,
+            format!("Could not convert the item '{}' to JSON.", self.name)
+        );
+
+        Ok(match_result!(
+            serde_json::to_string_pretty(&value),
+            Json,
+            format!("Could not serialize the item '{}'.", self.name)
+        ))
+    }
+
+    pub fn from_json_value(item: JsonItem) -> Result<Item, Error> {
+        let mut children = vec![];
+        for child in item.children {
+            children.push(match_error!(
+                Item::from_json_value(child),
+                Json,
+                format!("Could not read a child of '{}'.", item.name)
+            ));
+        }
+
+        Ok(Item {
+            completed: item.completed || item.archived,
+            archived: item.archived,
+            priority: item.priority,
+            date: match_error!(
+                parse_json_date(item.date),
+                Json,
+                format!("Could not read the date of '{}'.", item.name)
+            ),
+            created: match_error!(
+                parse_json_date(item.created),
+                Json,
+                format!("Could not read when '{}' was created.", item.name)
+            ),
+            recurrence: match item.recurrence {
+                Some(recurrence) => Some(match_error!(
+                    Recurrence::parse(&recurrence),
+                    Json,
+                    format!("Could not read the repeating rule of '{}'.", item.name)
+                )),
+                None => None,
+            },
+            name: item.name,
+            tags: item.tags,
+            id: item.id,
+            items: children,
+            note: item.note,
+            trailing: vec![],
+            source: None,
+        })
+    }
+}
+
+fn parse_json_date(date: Option<String>) -> Result<Option<Date>, Error> {
+    match date {
+        Some(date) => Ok(Some(match_error!(
+            Date::from_iso(&date),
+            Json,
+            format!("Dates in JSON must be YYYY-MM-DD. Got '{}'.", date)
+        ))),
+        None => Ok(None),
+    }
+}
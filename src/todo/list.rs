@@ -1,157 +1,157 @@
 use crate::error::{CodeComponent::ItemList, CodeComponent::ListParser, Error};
 use crate::output::buffer::OutputBuffer;
+use crate::todo::diagnostic::{Diagnostic, column};
 use crate::todo::item::Item;
-use crate::todo::path::ItemPath;
+use crate::todo::markdown::ListFormat;
+use crate::todo::path::{ItemPath, PathSegment};
 use crate::{match_error, match_option, propagate};
-use std::cmp::Ordering;
 
 pub type List = Vec<Item>;
 
 pub trait TodoList {
-    fn parse(file: String) -> Result<List, Error>;
-    fn to_save(&self) -> String;
+    fn parse(file: String, format: ListFormat) -> Result<List, Error>;
+    fn parse_tolerant(
+        lines: &[&str],
+        first_line: usize,
+        format: ListFormat,
+    ) -> (List, Vec<Diagnostic>);
+    fn to_save(&self, format: ListFormat) -> String;
     fn find(&mut self, path: &ItemPath) -> Result<&mut Item, Error>;
     fn add_item(&mut self, item: Item, path: ItemPath) -> Result<(), Error>;
-    fn recursive_filter(&mut self, predicate: fn(&Item) -> bool);
+    fn recursive_filter(&mut self, predicate: &dyn Fn(&Item) -> bool);
     fn format(&self, lines: Vec<bool>) -> Result<OutputBuffer, Error>;
     fn format_overview(&self, lines: Vec<bool>) -> Result<OutputBuffer, Error>;
     fn prune(&mut self);
-    fn remove_by_path(&mut self, path: &ItemPath) -> Result<Item, Error>;
+    fn remove_by_path(&mut self, path: &ItemPath) -> Result<(Item, Vec<String>), Error>;
+    fn remove_at(&mut self, indices: &[usize]) -> Option<(Item, Vec<String>)>;
+    fn has_id(&self, id: &str) -> bool;
+    fn matching_indices(&self, segments: &[PathSegment]) -> Vec<Vec<usize>>;
+    fn locate(&self, path: &ItemPath) -> Result<Vec<usize>, Error>;
+    fn indices_of_id(&self, id: &str) -> Vec<Vec<usize>>;
+    fn item_at(&self, indices: &[usize]) -> Option<&Item>;
+    fn item_at_mut(&mut self, indices: &[usize]) -> Option<&mut Item>;
+    fn children_at_mut(&mut self, indices: &[usize]) -> Option<&mut List>;
+    fn display_indices(&self, document: &str, indices: &[usize]) -> String;
+    fn complete_at(&mut self, indices: &[usize]) -> Result<Option<Vec<usize>>, Error>;
 }
 
 impl TodoList for List {
-    fn parse(file: String) -> Result<List, Error> {
-        let mut items: Vec<Item> = vec![];
-
-        let content = file.lines();
-
-        if content.clone().count() <= 0 {
-            return Ok(vec![]);
+    /// Parses a list, and fails if any part of it can't be read.
+    fn parse(file: String, format: ListFormat) -> Result<List, Error> {
+        let (list, diagnostics) =
+            List::parse_tolerant(&file.lines().collect::<Vec<&str>>(), 1, format);
+
+        let errors = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .map(|diagnostic| diagnostic.summary())
+            .collect::<Vec<String>>();
+        if !errors.is_empty() {
+            return Err(propagate!(
+                ListParser,
+                format!("Some of the items couldn't be read:\n{}", errors.join("\n"))
+            ));
         }
 
-        let first_row = match_option!(
-            content.clone().nth(0),
-            ListParser,
-            format!("Could not get the first line of the list.")
-        );
-
-        let starting_indentation =
-            first_row.chars().count() - first_row.trim_start().chars().count();
-
-        for (i, line) in content.clone().enumerate() {
-            let current_indentation = line.chars().count() - line.trim_start().chars().count();
-
-            let mut next_indentation = 0;
-            if i < content.clone().count() - 1 {
-                let next_line = match_option!(
-                    content.clone().nth(i + 1),
-                    ListParser,
-                    format!("Could not get line number {} of the list", i + 2)
-                );
-
-                next_indentation =
-                    next_line.chars().count() - next_line.trim_start().chars().count();
-            }
-
-            let mut sub_items: Vec<Item> = vec![];
-
-            // If we rise out of the level that we start at
-            if current_indentation < starting_indentation {
-                break;
-            }
-
-            // Skip the lower-level lines, they are going to be handled recursively
-            if current_indentation > starting_indentation {
-                continue;
-            }
+        Ok(list)
+    }
 
-            // We need to go deeper
-            if current_indentation < next_indentation {
-                let mut remaining_lines = content.clone();
-                remaining_lines.nth(i);
-                if remaining_lines.clone().count() > 0 {
-                    sub_items = match_error!(
-                        List::parse(remaining_lines.collect::<Vec<&str>>().join("\n")),
-                        ListParser,
-                        format!(
-                            "Could not parse list of sub-items at line {index}.",
-                            index = i + 1
+    /// Parses a list, keeping the items that can be read. Lines that can't be read are kept as
+    /// they are (like a note), and what was wrong with them is given back. The first line is
+    /// numbered `first_line`.
+    fn parse_tolerant(
+        lines: &[&str],
+        first_line: usize,
+        format: ListFormat,
+    ) -> (List, Vec<Diagnostic>) {
+        let mut items: Vec<(usize, &str, Item)> = vec![];
+        let mut diagnostics = vec![];
+
+        for (i, line) in lines.iter().enumerate() {
+            let line_number = first_line + i;
+            let parsed = if Item::is_item_line(line) {
+                match Item::parse(line, format) {
+                    Ok((item, warnings)) => Some((item, warnings)),
+                    Err(errors) => {
+                        diagnostics.extend(
+                            errors
+                                .into_iter()
+                                .map(|error| error.on_line(line_number, line)),
+                        );
+                        None
+                    }
+                }
+            } else {
+                if line.trim_start().starts_with("- [") {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            column(line, line.len() - line.trim_start().len()),
+                            3,
+                            "This looks like an item, but it isn't one, so it is read as a note. Items start like '- [ ] '."
+                                .to_string(),
                         )
+                        .on_line(line_number, line),
                     );
                 }
-            }
+                None
+            };
+
+            let Some((item, warnings)) = parsed else {
+                // Anything else belongs to the item before it, so that it is saved in the same
+                // place. Documents keep the lines before their first item themselves.
+                if let Some((_, _, item)) = items.last_mut() {
+                    item.trailing.push(line.to_string());
+                }
+                continue;
+            };
 
-            items.push(match_error!(
-                Item::from(line.to_string(), sub_items),
-                ListParser,
-                format!(
-                    "Could not parse item at line {index}: '{line}'.",
-                    index = i + 1
-                )
-            ));
+            diagnostics.extend(
+                warnings
+                    .into_iter()
+                    .map(|warning| warning.on_line(line_number, line)),
+            );
+            let indentation = line.chars().count() - line.trim_start().chars().count();
+            items.push((indentation, line, item));
         }
 
-        items.sort_by(|a, b| {
-            if a.completed ^ b.completed {
-                if a.completed && !b.completed {
-                    return Ordering::Greater;
-                } else {
-                    return Ordering::Less;
-                }
-            } else {
-                return b.priority.cmp(&a.priority);
-            }
-        });
-
-        Ok(items)
+        (nest(&items, &mut 0, None, 0, format), diagnostics)
     }
 
-    fn to_save(&self) -> String {
+    fn to_save(&self, format: ListFormat) -> String {
         let mut output = String::new();
 
         for item in self.clone() {
-            output += &item.to_string(0);
+            output += &item.to_string(0, format);
         }
 
         output
     }
 
-    /// Get a mutable reference to an item that matches a certain path.
+    /// Get a mutable reference to the one item that matches a certain path.
     fn find(&mut self, path: &ItemPath) -> Result<&mut Item, Error> {
-        let mut matching_itmes = vec![];
-
-        for (i, item) in self.clone().into_iter().enumerate() {
-            if path.clone().matches(item.clone()) {
-                matching_itmes.push(i);
-            }
-        }
-
-        // There is probably a better way to do this, but I don't know enough rust for that.
-        if path.item_prefixes.len() == 1 && matching_itmes.len() > 0 {
-            return Ok(&mut self[matching_itmes[0]]);
-        } else {
-            for i in matching_itmes {
-                let mut cloned_list = self.clone();
-                let result = cloned_list[i].items.find(&path.clone().shifted());
+        let indices = match_error!(
+            self.locate(path),
+            ItemList,
+            format!("Could not find the item at path '{}'.", path.display())
+        );
 
-                if result.is_ok() {
-                    return self[i].items.find(&path.clone().shifted());
-                }
-            }
+        if indices.is_empty() {
+            return Err(propagate!(
+                ItemList,
+                format!("The path '{}' does not point to an item.", path.display())
+            ));
         }
 
-        Err(propagate!(
+        Ok(match_option!(
+            self.item_at_mut(&indices),
             ItemList,
-            format!(
-                "Could not find an item that started with '{}'",
-                path.item_prefixes[0]
-            )
+            format!("Could not get the item at path '{}'.", path.display())
         ))
     }
 
     fn add_item(&mut self, item: Item, path: ItemPath) -> Result<(), Error> {
         // If there is no item specified, simply add it to the root of the list.
-        if path.item_prefixes.len() == 0 {
+        if path.segments.is_empty() {
             self.push(item.clone());
         } else {
             let found_item = match_error!(
@@ -180,7 +180,8 @@ impl TodoList for List {
         Ok(output)
     }
 
-    fn recursive_filter(&mut self, predicate: fn(&Item) -> bool) {
+    /// Removes every item that the predicate returns true for, along with its children.
+    fn recursive_filter(&mut self, predicate: &dyn Fn(&Item) -> bool) {
         // I don't like this, but I don't think there is a particularly better way of doing this.
         let mut removed_items = 0;
 
@@ -204,38 +205,272 @@ impl TodoList for List {
         }
     }
 
-    fn remove_by_path(&mut self, path: &ItemPath) -> Result<Item, Error> {
-        let mut matching_itmes = vec![];
+    /// Removes the item at a path, like `remove_at`.
+    fn remove_by_path(&mut self, path: &ItemPath) -> Result<(Item, Vec<String>), Error> {
+        let indices = match_error!(
+            self.locate(path),
+            ItemList,
+            format!(
+                "Could not find the item at path '{}' to remove.",
+                path.display()
+            )
+        );
 
-        for (i, item) in self.clone().into_iter().enumerate() {
-            if path.clone().matches(item.clone()) {
-                matching_itmes.push(i);
+        Ok(match_option!(
+            self.remove_at(&indices),
+            ItemList,
+            format!("The path '{}' does not point to an item.", path.display())
+        ))
+    }
+
+    /// Removes the item at some indices, along with its children. The lines in it that aren't a
+    /// part of an item (like comments and headings) stay where they were, after the item before
+    /// it. If it was the first item in the list, there isn't one, so they are given back to be
+    /// put before the list.
+    fn remove_at(&mut self, indices: &[usize]) -> Option<(Item, Vec<String>)> {
+        let (last, parent) = indices.split_last()?;
+        let siblings = self.children_at_mut(parent)?;
+        if *last >= siblings.len() {
+            return None;
+        }
+
+        let mut item = siblings.remove(*last);
+        let lines = item.take_loose_lines();
+        if *last > 0 {
+            siblings[last - 1].append_lines(lines);
+            return Some((item, vec![]));
+        }
+        match self.item_at_mut(parent) {
+            // The parent's own trailing lines are right before its first child.
+            Some(parent) => {
+                parent.trailing.extend(lines);
+                Some((item, vec![]))
+            }
+            None => Some((item, lines)),
+        }
+    }
+
+    fn has_id(&self, id: &str) -> bool {
+        !self.indices_of_id(id).is_empty()
+    }
+
+    /// Every item that a path could refer to, as the indices that lead to it through the tree.
+    /// An empty path refers to the root of the list.
+    fn matching_indices(&self, segments: &[PathSegment]) -> Vec<Vec<usize>> {
+        let Some((segment, rest)) = segments.split_first() else {
+            return vec![vec![]];
+        };
+
+        let candidates = match segment {
+            // IDs are unique, so they can be found anywhere below this level.
+            PathSegment::Id(id) => self.indices_of_id(id),
+            PathSegment::Position(position) => {
+                if *position >= 1 && *position <= self.len() {
+                    vec![vec![position - 1]]
+                } else {
+                    vec![]
+                }
+            }
+            _ => {
+                let matching = self
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, item)| segment.matches(item))
+                    .collect::<Vec<(usize, &Item)>>();
+
+                // Archived items (like the old copies of repeating items) are only found by name
+                // when nothing else matches.
+                let active = matching
+                    .iter()
+                    .filter(|(_, item)| !item.archived)
+                    .map(|(i, _)| vec![*i])
+                    .collect::<Vec<Vec<usize>>>();
+                if active.is_empty() {
+                    matching.iter().map(|(i, _)| vec![*i]).collect()
+                } else {
+                    active
+                }
+            }
+        };
+
+        let mut matches = vec![];
+        for candidate in candidates {
+            if let Some(item) = self.item_at(&candidate) {
+                for rest_indices in item.items.matching_indices(rest) {
+                    let mut indices = candidate.clone();
+                    indices.extend(rest_indices);
+                    matches.push(indices);
+                }
             }
         }
 
-        // There is probably a better way to do this, but I don't know enough rust for that.
-        if path.item_prefixes.len() == 1 && matching_itmes.len() > 0 {
-            let item = self[matching_itmes[0]].clone();
-            self.remove(matching_itmes[0]);
-            return Ok(item);
+        matches
+    }
+
+    /// Find the single item that a path refers to. It is an error for the path to match more than
+    /// one item, so that commands never act on the wrong one.
+    fn locate(&self, path: &ItemPath) -> Result<Vec<usize>, Error> {
+        let mut matches = self.matching_indices(&path.segments);
+
+        match matches.len() {
+            0 => Err(propagate!(
+                ItemList,
+                format!("Nothing matches the path '{}'.", path.display())
+            )),
+            1 => Ok(matches.remove(0)),
+            _ => Err(propagate!(
+                ItemList,
+                format!(
+                    "The path '{}' is ambiguous. It could mean any of {}. Use more of the name, the exact name in quotes, a position or an ID to pick one.",
+                    path.display(),
+                    matches
+                        .iter()
+                        .map(|indices| self.display_indices(&path.document, indices))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )
+            )),
+        }
+    }
+
+    fn indices_of_id(&self, id: &str) -> Vec<Vec<usize>> {
+        let mut matches = vec![];
+
+        for (i, item) in self.iter().enumerate() {
+            if item
+                .id
+                .as_ref()
+                .is_some_and(|item_id| item_id.eq_ignore_ascii_case(id))
+            {
+                matches.push(vec![i]);
+            }
+            for mut indices in item.items.indices_of_id(id) {
+                indices.insert(0, i);
+                matches.push(indices);
+            }
+        }
+
+        matches
+    }
+
+    fn item_at(&self, indices: &[usize]) -> Option<&Item> {
+        let (first, rest) = indices.split_first()?;
+        let item = self.get(*first)?;
+
+        if rest.is_empty() {
+            Some(item)
         } else {
-            for i in matching_itmes {
-                let mut cloned_list = self.clone();
-                let result = cloned_list[i].items.remove_by_path(&path.clone().shifted());
+            item.items.item_at(rest)
+        }
+    }
+
+    fn item_at_mut(&mut self, indices: &[usize]) -> Option<&mut Item> {
+        let (first, rest) = indices.split_first()?;
+        let item = self.get_mut(*first)?;
+
+        if rest.is_empty() {
+            Some(item)
+        } else {
+            item.items.item_at_mut(rest)
+        }
+    }
+
+    /// The list of children at some indices. No indices means the root of the list.
+    fn children_at_mut(&mut self, indices: &[usize]) -> Option<&mut List> {
+        if indices.is_empty() {
+            Some(self)
+        } else {
+            self.item_at_mut(indices).map(|item| &mut item.items)
+        }
+    }
 
-                if result.is_ok() {
-                    return self[i].items.remove_by_path(&path.clone().shifted());
+    /// Write out the full path of the item at some indices, using the items' names.
+    fn display_indices(&self, document: &str, indices: &[usize]) -> String {
+        let mut output = format!("#{}", document);
+
+        let mut items = self;
+        let mut id = None;
+        for index in indices {
+            match items.get(*index) {
+                Some(item) => {
+                    output += &format!("/{}", item.name);
+                    id = item.id.clone();
+                    items = &item.items;
                 }
+                None => break,
             }
         }
 
-        Err(propagate!(
+        match id {
+            Some(id) => format!("'{}' (@{})", output, id),
+            None => format!("'{}'", output),
+        }
+    }
+
+    /// Mark the item at some indices as completed. If it repeats, the item is moved on to its next
+    /// occurrence instead, and an archived copy of the completed one is kept right after it. The
+    /// indices of that copy are returned.
+    fn complete_at(&mut self, indices: &[usize]) -> Result<Option<Vec<usize>>, Error> {
+        let item = match_option!(
+            self.item_at_mut(indices),
             ItemList,
-            format!(
-                "Could not find the item at path '{}' to remove.",
-                path.display()
-            )
-        ))
+            format!("There is no item at {:?}.", indices)
+        );
+
+        let Some(recurrence) = item.recurrence.clone() else {
+            item.completed = true;
+            return Ok(None);
+        };
+
+        let mut done = item.clone();
+        done.completed = true;
+        done.archived = true;
+        done.recurrence = None;
+        // The copy only has the items' own lines and notes. Lines that aren't a part of an item stay
+        // with the live one, except for the ones after it, which stay after the copy so that it
+        // doesn't end up under a heading that was after the item.
+        done.take_loose_lines();
+        done.append_lines(item.take_lines_after());
+
+        item.date = Some(match_error!(
+            recurrence.next_occurrence(item.date),
+            ItemList,
+            format!("Could not work out when '{}' is next due.", item.name)
+        ));
+        let lines = item.items.reset_occurrence();
+        item.trailing.extend(lines);
+
+        let (last, parent) = match_option!(
+            indices.split_last(),
+            ItemList,
+            format!("Cannot complete the root of a list.")
+        );
+        let siblings = match_option!(
+            self.children_at_mut(parent),
+            ItemList,
+            format!("There is no item at {:?}.", parent)
+        );
+        siblings.insert(last + 1, done);
+
+        let mut done_indices = parent.to_vec();
+        done_indices.push(last + 1);
+
+        // The item keeps its IDs, so the copy needs new ones, since they have to be unique.
+        let mut with_ids = vec![];
+        if let Some(done) = self.item_at(&done_indices) {
+            done.items.collect_ids(&done_indices, &mut with_ids);
+            if done.id.is_some() {
+                with_ids.push(done_indices.clone());
+            }
+        }
+        for item_indices in with_ids {
+            let id = Item::generate_id(self);
+            if let Some(item) = self.item_at_mut(&item_indices) {
+                item.id = Some(id);
+            }
+        }
+
+        Ok(Some(done_indices))
     }
 
     fn format_overview(&self, lines: Vec<bool>) -> Result<OutputBuffer, Error> {
@@ -253,3 +488,71 @@ impl TodoList for List {
         Ok(output)
     }
 }
+
+/// Builds the tree of items from the lines of a file. Items are children of the item before them
+/// that is indented less than they are.
+fn nest(
+    lines: &[(usize, &str, Item)],
+    position: &mut usize,
+    parent_indentation: Option<usize>,
+    depth: usize,
+    format: ListFormat,
+) -> List {
+    let mut items = vec![];
+
+    while let Some((indentation, line, item)) = lines.get(*position) {
+        if parent_indentation.is_some_and(|parent| *indentation <= parent) {
+            break;
+        }
+        *position += 1;
+
+        let mut item = item.clone();
+        item.set_source(line, depth, format);
+        item.items = nest(lines, position, Some(*indentation), depth + 1, format);
+        items.push(item);
+    }
+
+    items
+}
+
+trait Occurrence {
+    fn reset_occurrence(&mut self) -> Vec<String>;
+    fn collect_ids(&self, prefix: &[usize], output: &mut Vec<Vec<usize>>);
+}
+
+impl Occurrence for List {
+    /// Get the children of a repeating item ready to be done again. Archived children are left
+    /// behind with the completed copy, but the lines around them stay, and the ones that were
+    /// before the first child are given back.
+    fn reset_occurrence(&mut self) -> Vec<String> {
+        let mut first_lines = vec![];
+        let mut i = 0;
+        while i < self.len() {
+            if !self[i].archived {
+                i += 1;
+                continue;
+            }
+            if let Some((_, lines)) = self.remove_at(&[i]) {
+                first_lines.extend(lines);
+            }
+        }
+
+        for item in self.iter_mut() {
+            item.completed = false;
+            let lines = item.items.reset_occurrence();
+            item.trailing.extend(lines);
+        }
+        first_lines
+    }
+
+    fn collect_ids(&self, prefix: &[usize], output: &mut Vec<Vec<usize>>) {
+        for (i, item) in self.iter().enumerate() {
+            let mut indices = prefix.to_vec();
+            indices.push(i);
+            item.items.collect_ids(&indices, output);
+            if item.id.is_some() {
+                output.push(indices);
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::todo::diagnostic::{Diagnostic, column};
+
+/// How a list's file is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    /// The `.todo` format, with the metadata between backslashes before an item's name.
+    Todo,
+    /// A Markdown task list (like `TODO.md`), with the metadata in braces at the end of the line,
+    /// like `- [ ] Deploy {priority=3 due=2026-06-13}`.
+    Markdown,
+}
+
+impl ListFormat {
+    /// The format of a list file, from its name.
+    pub fn of(file_name: &str) -> ListFormat {
+        let file_name = file_name.to_ascii_lowercase();
+        if file_name.ends_with(".md") || file_name.ends_with(".markdown") {
+            ListFormat::Markdown
+        } else {
+            ListFormat::Todo
+        }
+    }
+
+    /// The extension of a file in this format, for files that are opened in the editor.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ListFormat::Todo => "todo",
+            ListFormat::Markdown => "md",
+        }
+    }
+
+    /// How much each level of items is indented when there is nothing else to go by.
+    pub fn indent(&self, depth: usize) -> String {
+        match self {
+            ListFormat::Todo => " ".repeat(depth),
+            ListFormat::Markdown => "  ".repeat(depth),
+        }
+    }
+}
+
+/// One `key=value` (or just `key`) from the braces at the end of a line.
+#[derive(Debug, Clone)]
+pub struct Field<'a> {
+    pub key: &'a str,
+    pub value: Option<&'a str>,
+    /// Where the field starts in the line, in bytes.
+    pub start: usize,
+    pub length: usize,
+}
+
+impl Field<'_> {
+    /// A problem with this field, pointing at it in the line.
+    pub fn problem(&self, line: &str, message: String) -> Diagnostic {
+        Diagnostic::error(
+            column(line, self.start),
+            line.get(self.start..self.start + self.length)
+                .unwrap_or_default()
+                .chars()
+                .count(),
+            message,
+        )
+    }
+}
+
+/// Splits the metadata in braces off the end of some text (which starts at `offset` in its line),
+/// like `Deploy {priority=3}`. Braces in names are escaped, so an escaped brace (or one from a
+/// `\u{a}` escape) doesn't count, and braces are only metadata if every field in them is one of
+/// `keys` (and there are some), so that a name like `document ${HOME} {draft}` or `{}` stays as
+/// it is. It gives back the text before the braces, and the fields in them.
+pub fn split_fields<'a>(text: &'a str, offset: usize, keys: &[&str]) -> (&'a str, Vec<Field<'a>>) {
+    let trimmed = text.trim_end();
+    let Some(inside_end) = trimmed
+        .strip_suffix("}")
+        .map(|inside| inside.len())
+        .filter(|end| !is_escaped(trimmed, *end))
+    else {
+        return (text, vec![]);
+    };
+    let Some(open) = trimmed[..inside_end]
+        .match_indices('{')
+        .map(|(position, _)| position)
+        .rfind(|position| !is_escaped(trimmed, *position) && !starts_code(trimmed, *position))
+    else {
+        return (text, vec![]);
+    };
+
+    let inside = &trimmed[open + 1..inside_end];
+    let mut fields = vec![];
+    let mut position = 0;
+    while position < inside.len() {
+        let rest = &inside[position..];
+        let skipped = rest.len() - rest.trim_start().len();
+        position += skipped;
+        if position >= inside.len() {
+            break;
+        }
+
+        // Values with spaces in them are in quotes, like `repeat="every monday"`.
+        let rest = &inside[position..];
+        let mut length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if let Some(equals) = rest.find("=")
+            && equals < length
+            && rest[equals + 1..].starts_with("\"")
+        {
+            length = rest[equals + 2..]
+                .find("\"")
+                .map_or(rest.len(), |end| equals + 2 + end + 1);
+        }
+
+        let field = &rest[..length];
+        let (key, value) = match field.split_once("=") {
+            Some((key, value)) => (
+                key,
+                Some(
+                    value
+                        .strip_prefix("\"")
+                        .and_then(|value| value.strip_suffix("\""))
+                        .unwrap_or(value),
+                ),
+            ),
+            None => (field, None),
+        };
+        fields.push(Field {
+            key,
+            value,
+            start: offset + open + 1 + position,
+            length,
+        });
+        position += length;
+    }
+
+    if fields.is_empty() || fields.iter().any(|field| !keys.contains(&field.key)) {
+        return (text, vec![]);
+    }
+    (&text[..open], fields)
+}
+
+/// Writes fields as braces to put at the end of a line, or nothing if there aren't any.
+pub fn write_fields(fields: &[(&str, Option<String>)]) -> String {
+    if fields.is_empty() {
+        return String::new();
+    }
+
+    let fields = fields
+        .iter()
+        .map(|(key, value)| match value {
+            Some(value) if value.is_empty() || value.contains(char::is_whitespace) => {
+                format!("{key}=\"{value}\"")
+            }
+            Some(value) => format!("{key}={value}"),
+            None => key.to_string(),
+        })
+        .collect::<Vec<String>>();
+
+    format!(" {{{}}}", fields.join(" "))
+}
+
+/// Whether the character at a byte position has an odd number of backslashes before it.
+fn is_escaped(text: &str, position: usize) -> bool {
+    text[..position]
+        .chars()
+        .rev()
+        .take_while(|character| *character == '\\')
+        .count()
+        % 2
+        == 1
+}
+
+/// Whether the brace at a byte position is the start of a `\u{a}` escape.
+fn starts_code(text: &str, position: usize) -> bool {
+    text[..position].ends_with("\\u") && !is_escaped(text, position - 2)
+}
@@ -1,4 +1,12 @@
+pub mod agenda;
+pub mod diagnostic;
 pub mod document;
+pub mod ics;
 pub mod item;
+pub mod json;
 pub mod list;
+pub mod markdown;
 pub mod path;
+pub mod sort;
+pub mod tags;
+pub mod todotxt;
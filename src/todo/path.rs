@@ -1,39 +1,191 @@
 use crate::{
     error::{CodeComponent, Error},
     match_error, match_result, propagate,
+    registry::Registry,
+    search_paths,
     todo::{document, item},
 };
 
+use std::path::{Path, PathBuf};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ItemPath {
     pub document: String,
-    pub item_prefixes: Vec<String>,
+    /// Which list is meant, when more than one has the same name.
+    pub qualifier: Option<ListQualifier>,
+    pub segments: Vec<PathSegment>,
 }
 
-impl ItemPath {
-    pub fn try_from(value: &String) -> Result<Self, Error> {
-        let mut segments = value.split("/");
-        let mut document = None;
+/// Picks one list out of the ones with the same name, written after an `@` in the first part of
+/// the path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListQualifier {
+    /// The list in this directory, like `#Todo@~/work/api/`.
+    Directory(PathBuf),
+    /// The list with this `# alias`, like `#Todo@api`.
+    Alias(String),
+}
 
-        let first_segment = match segments.clone().next() {
-            Some(val) => val,
-            _ => {
+impl ListQualifier {
+    /// Parses what comes after the `@`, and gives back the rest of the path after it. A directory
+    /// can have slashes in it, so it is the longest part of the path that has a list in it.
+    fn parse(text: &str) -> Result<(ListQualifier, String), Error> {
+        if !(text.starts_with("~") || text.starts_with("/") || text.starts_with(".")) {
+            let (alias, rest) = text.split_once("/").unwrap_or((text, ""));
+            if alias.is_empty() {
                 return Err(propagate!(
                     CodeComponent::DocumentPath,
-                    format!("Couldn't split the string ('{value}') apart. Is it missing slashes?")
+                    format!("There should be an alias or a directory after the '@'.")
                 ));
             }
-        };
+            return Ok((ListQualifier::Alias(alias.to_string()), rest.to_string()));
+        }
+
+        let pieces = text.split("/").collect::<Vec<&str>>();
+        for length in (1..=pieces.len()).rev() {
+            let directory = pieces[..length].join("/");
+            let directory = match directory.strip_prefix("~") {
+                Some(rest) => match std::env::var("HOME") {
+                    Ok(home) => format!("{home}{rest}"),
+                    Err(_) => directory,
+                },
+                None if directory.is_empty() => "/".to_string(),
+                None => directory,
+            };
+            let directory = PathBuf::from(directory);
+
+            if search_paths::has_todo_list(&directory).unwrap_or(false) {
+                let directory = match_result!(
+                    directory.canonicalize(),
+                    CodeComponent::DocumentPath,
+                    format!(
+                        "Could not get the canonical path of '{}'",
+                        directory.display()
+                    )
+                );
+                return Ok((
+                    ListQualifier::Directory(directory),
+                    pieces[length..].join("/"),
+                ));
+            }
+        }
+
+        Err(propagate!(
+            CodeComponent::DocumentPath,
+            format!(
+                "There isn't a list in '{}' (or any of the directories at the start of it).",
+                text
+            )
+        ))
+    }
+
+    pub fn display(&self) -> String {
+        match self {
+            ListQualifier::Directory(directory) => directory.display().to_string(),
+            ListQualifier::Alias(alias) => alias.clone(),
+        }
+    }
+}
+
+/// One of the slash-separated pieces of an `ItemPath`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// Matches items whose name starts with this, ignoring case.
+    Prefix(String),
+    /// Matches items whose whole name is exactly this (written as `"name"`).
+    Exact(String),
+    /// Matches the nth item in the list, starting at 1 (written as just the number).
+    Position(usize),
+    /// Matches the item with this ID (written as `@id`), no matter how deep below the current level
+    /// it is.
+    Id(String),
+}
+
+impl PathSegment {
+    pub fn parse(segment: &str) -> Result<PathSegment, Error> {
+        if segment.len() >= 2 && segment.starts_with('"') && segment.ends_with('"') {
+            return Ok(PathSegment::Exact(
+                segment[1..segment.len() - 1].to_string(),
+            ));
+        }
+
+        if segment.chars().all(|character| character.is_ascii_digit()) {
+            let position = match_result!(
+                segment.parse::<usize>(),
+                CodeComponent::DocumentPath,
+                format!("Could not parse the position '{segment}'.")
+            );
+            if position == 0 {
+                return Err(propagate!(
+                    CodeComponent::DocumentPath,
+                    format!("Positions in paths start at 1, so '0' doesn't point to anything.")
+                ));
+            }
+            return Ok(PathSegment::Position(position));
+        }
+
+        match segment.strip_prefix("@") {
+            Some(id) if !id.is_empty() => Ok(PathSegment::Id(id.to_string())),
+            _ => Ok(PathSegment::Prefix(segment.to_string())),
+        }
+    }
+
+    /// Whether an item matches this segment on its own. Positions depend on the rest of the list,
+    /// so they never match here.
+    pub fn matches(&self, item: &item::Item) -> bool {
+        match self {
+            PathSegment::Prefix(prefix) => item
+                .name
+                .to_ascii_lowercase()
+                .starts_with(&prefix.to_ascii_lowercase()),
+            PathSegment::Exact(name) => item.name == *name,
+            PathSegment::Position(_) => false,
+            PathSegment::Id(id) => item
+                .id
+                .as_ref()
+                .is_some_and(|item_id| item_id.eq_ignore_ascii_case(id)),
+        }
+    }
+
+    pub fn display(&self) -> String {
+        match self {
+            PathSegment::Prefix(prefix) => prefix.clone(),
+            PathSegment::Exact(name) => format!("\"{}\"", name),
+            PathSegment::Position(position) => position.to_string(),
+            PathSegment::Id(id) => format!("@{}", id),
+        }
+    }
+}
+
+impl ItemPath {
+    pub fn try_from(value: &String) -> Result<Self, Error> {
+        let mut document = None;
+        let mut qualifier = None;
 
-        if first_segment.starts_with("#") {
-            document = Some(first_segment[1..].to_string());
-            segments.next();
-        } else if first_segment == "" {
-            // If the first item is blank, that means that the path starts with a slash, so we
-            // should infer the document name.
-            document = None;
-            segments.next();
+        // The part of the path after the list, which is split into segments. List names can have an
+        // `@` in them, so the qualifier starts at the last one before the first `/` (`#me@work@~/proj`
+        // is `#me@work` in `~/proj`). If there isn't a list in the directory after it, the `@` is
+        // part of the name.
+        let rest = if let Some(list) = value.strip_prefix("#")
+            && let Some((name, qualified)) = list[..list.find("/").unwrap_or(list.len())]
+                .rfind("@")
+                .map(|at| (&list[..at], &list[at + 1..]))
+            && let Ok((parsed, rest)) = ListQualifier::parse(qualified)
+        {
+            document = Some(name.to_string());
+            qualifier = Some(parsed);
+            rest
+        } else if let Some(list) = value.strip_prefix("#") {
+            let (name, rest) = list.split_once("/").unwrap_or((list, ""));
+            document = Some(name.to_string());
+            rest.to_string()
+        } else if let Some(rest) = value.strip_prefix("/") {
+            // If the path starts with a slash, we should infer the document name.
+            rest.to_string()
+        } else {
+            value.to_string()
         };
+        let segments = rest.split("/");
 
         let mut prefxes = vec![];
 
@@ -51,12 +203,16 @@ impl ItemPath {
                     ));
                 }
             } else {
-                prefxes.push(piece.to_string());
+                prefxes.push(match_error!(
+                    PathSegment::parse(piece),
+                    CodeComponent::DocumentPath,
+                    format!("Could not parse the segment '{piece}' of the path '{value}'.")
+                ));
             }
         }
 
         let new_path = match_error!(
-            ItemPath::new(document, prefxes),
+            ItemPath::new(document, qualifier, prefxes),
             CodeComponent::DocumentPath,
             format!("Couldn't create the Item Path.")
         );
@@ -66,48 +222,246 @@ impl ItemPath {
 }
 
 impl ItemPath {
-    pub fn new(document: Option<String>, prefixes: Vec<String>) -> Result<ItemPath, Error> {
+    pub fn new(
+        document: Option<String>,
+        qualifier: Option<ListQualifier>,
+        segments: Vec<PathSegment>,
+    ) -> Result<ItemPath, Error> {
         let current_dir = match_result!(
             std::env::current_dir(),
             CodeComponent::DocumentPath,
             "Couldn't read your current directory".to_string()
         );
-        let normalized_document = document.unwrap_or(
-            match_error!(
-                document::Document::from_path(&current_dir),
-                CodeComponent::DocumentPath,
-                format!(
-                    "Could not fetch the document in your current directory ('{}')",
-                    current_dir.display()
-                )
-            )
-            .name,
-        );
+
+        let (normalized_document, qualifier) = match document {
+            Some(document) => (document, qualifier),
+            // The list is the one in a specific directory, so it can't be mixed up with another
+            // list with the same name.
+            None => {
+                let (document, directory) = match_error!(
+                    ItemPath::current_document(&current_dir),
+                    CodeComponent::DocumentPath,
+                    format!(
+                        "Could not fetch the document in your current directory ('{}')",
+                        current_dir.display()
+                    )
+                );
+                (document, Some(ListQualifier::Directory(directory)))
+            }
+        };
 
         Ok(ItemPath {
             document: normalized_document,
-            item_prefixes: prefixes,
+            qualifier,
+            segments,
         })
     }
 
-    pub fn matches(self, item: item::Item) -> bool {
-        if self.item_prefixes.len() == 0 {
-            return false;
+    /// Whether a list is the one this path means, from its name and alias. A directory qualifier
+    /// has to be checked separately.
+    pub fn matches_list(&self, name: &str, alias: Option<&str>) -> bool {
+        let named = name == self.document || alias == Some(self.document.as_str());
+        match &self.qualifier {
+            Some(ListQualifier::Alias(qualifier)) => named && alias == Some(qualifier.as_str()),
+            _ => named,
         }
+    }
 
-        item.name
-            .to_ascii_lowercase()
-            .starts_with(&self.item_prefixes[0].to_ascii_lowercase())
+    /// The same path with the alias as part of the list's name, like `#me@work` for a list called
+    /// `me@work` instead of `#me` with the alias `work`. It is used when no list has that alias.
+    pub fn literal(&self) -> Option<ItemPath> {
+        match &self.qualifier {
+            Some(ListQualifier::Alias(alias)) => Some(ItemPath {
+                document: format!("{}@{}", self.document, alias),
+                qualifier: None,
+                segments: self.segments.clone(),
+            }),
+            _ => None,
+        }
     }
 
-    pub fn shifted(self) -> ItemPath {
-        ItemPath {
-            document: self.document,
-            item_prefixes: self.item_prefixes[1..].to_vec(),
+    /// The name and directory of the list that a path without one means. This is the list in the
+    /// directory, or if there isn't one, the closest registered list above it.
+    fn current_document(current_dir: &Path) -> Result<(String, PathBuf), Error> {
+        let current_dir = current_dir
+            .canonicalize()
+            .unwrap_or(current_dir.to_path_buf());
+        let mut directory = current_dir.clone();
+
+        if !match_error!(
+            search_paths::has_todo_list(&current_dir),
+            CodeComponent::DocumentPath,
+            format!("Could not check for a list in '{}'.", current_dir.display())
+        ) {
+            let registry = match_error!(
+                Registry::load(),
+                CodeComponent::DocumentPath,
+                format!("Could not load the registered lists.")
+            );
+            if let Some(path) = registry.containing(&current_dir) {
+                directory = path;
+            }
         }
+
+        let name = match_error!(
+            document::Document::from_path(&directory),
+            CodeComponent::DocumentPath,
+            format!("Could not read the list in '{}'.", directory.display())
+        )
+        .name;
+        Ok((name, directory))
     }
 
     pub fn display(&self) -> String {
-        return format!("#{}/{}", self.document, self.item_prefixes.join("/"));
+        format!(
+            "#{}{}/{}",
+            self.document,
+            match &self.qualifier {
+                Some(qualifier) => format!("@{}", qualifier.display()),
+                None => String::new(),
+            },
+            self.segments
+                .iter()
+                .map(|segment| segment.display())
+                .collect::<Vec<String>>()
+                .join("/")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::list::TodoList;
+
+    fn path(value: &str) -> ItemPath {
+        ItemPath::try_from(&value.to_string()).expect("The path should be read.")
+    }
+
+    #[test]
+    fn the_last_at_starts_the_qualifier() {
+        let path = path("#me@work@home/Water");
+
+        assert_eq!(path.document, "me@work");
+        assert_eq!(
+            path.qualifier,
+            Some(ListQualifier::Alias("home".to_string()))
+        );
+        assert_eq!(path.segments, [PathSegment::Prefix("Water".to_string())]);
+    }
+
+    #[test]
+    fn names_with_an_at_are_used_when_there_is_no_list_like_that() {
+        let aliased = path("#me@work/Water");
+        assert_eq!(aliased.document, "me");
+        assert_eq!(
+            aliased.literal(),
+            Some(ItemPath {
+                document: "me@work".to_string(),
+                qualifier: None,
+                segments: vec![PathSegment::Prefix("Water".to_string())],
+            })
+        );
+
+        // There isn't a list in this directory, so the `@` is part of the name.
+        let directory = path("#me@.no-todo-list-here/Water");
+        assert_eq!(directory.document, "me@.no-todo-list-here");
+        assert_eq!(directory.qualifier, None);
+        assert_eq!(
+            directory.segments,
+            [PathSegment::Prefix("Water".to_string())]
+        );
+        assert_eq!(directory.literal(), None);
+    }
+
+    #[test]
+    fn segments_are_read() {
+        assert_eq!(
+            PathSegment::parse("\"Deploy\"").ok(),
+            Some(PathSegment::Exact("Deploy".to_string()))
+        );
+        assert_eq!(
+            PathSegment::parse("\"\"").ok(),
+            Some(PathSegment::Exact(String::new()))
+        );
+        assert_eq!(
+            PathSegment::parse("\"").ok(),
+            Some(PathSegment::Prefix("\"".to_string()))
+        );
+        assert_eq!(
+            PathSegment::parse("12").ok(),
+            Some(PathSegment::Position(12))
+        );
+        assert_eq!(
+            PathSegment::parse("12a").ok(),
+            Some(PathSegment::Prefix("12a".to_string()))
+        );
+        assert!(PathSegment::parse("0").is_err());
+        assert_eq!(
+            PathSegment::parse("@x83r0").ok(),
+            Some(PathSegment::Id("x83r0".to_string()))
+        );
+        assert_eq!(
+            PathSegment::parse("@").ok(),
+            Some(PathSegment::Prefix("@".to_string()))
+        );
+
+        let path = path("#Todo/\"Deploy\"/2/@x83r0/wat/");
+        assert_eq!(path.document, "Todo");
+        assert_eq!(
+            path.segments,
+            [
+                PathSegment::Exact("Deploy".to_string()),
+                PathSegment::Position(2),
+                PathSegment::Id("x83r0".to_string()),
+                PathSegment::Prefix("wat".to_string()),
+            ]
+        );
+        assert_eq!(path.display(), "#Todo/\"Deploy\"/2/@x83r0/wat");
+        assert!(ItemPath::try_from(&"#Todo//Deploy".to_string()).is_err());
+    }
+
+    const LIST: &str = "# Todo
+
+- [ ] Deploy
+- [ ] Deploy the API
+ - [ ] Write the changelog
+ - [ ] Tag the release
+  - [ ] \\@x83r0\\ Push the tag
+- [a] Water the plants
+- [ ] Water the plants
+";
+
+    /// Where the item that a path means is, or `None` if it doesn't mean exactly one.
+    fn locate(value: &str) -> Option<Vec<usize>> {
+        let document =
+            document::Document::parse(LIST.to_string(), PathBuf::from("/tmp"), ".todo".to_string());
+        document.items.locate(&path(value)).ok()
+    }
+
+    #[test]
+    fn paths_only_mean_one_item() {
+        // Both items start with "deploy", so it could be either of them.
+        assert_eq!(locate("#Todo/dep"), None);
+        assert_eq!(locate("#Todo/deploy the"), Some(vec![1]));
+        assert_eq!(locate("#Todo/\"Deploy\""), Some(vec![0]));
+        // Exact names have to have the same case.
+        assert_eq!(locate("#Todo/\"deploy\""), None);
+        assert_eq!(locate("#Todo/\"Deploy the\""), None);
+
+        assert_eq!(locate("#Todo/2/t"), Some(vec![1, 1]));
+        assert_eq!(locate("#Todo/2/2/1"), Some(vec![1, 1, 0]));
+        assert_eq!(locate("#Todo/deploy the/3"), None);
+        assert_eq!(locate("#Todo/4"), Some(vec![3]));
+        assert_eq!(locate("#Todo/5"), None);
+
+        // IDs are found however deep they are.
+        assert_eq!(locate("#Todo/@X83R0"), Some(vec![1, 1, 0]));
+        assert_eq!(locate("#Todo/2/@x83r0"), Some(vec![1, 1, 0]));
+        assert_eq!(locate("#Todo/1/@x83r0"), None);
+
+        // The archived copy is only found when nothing else is.
+        assert_eq!(locate("#Todo/water"), Some(vec![3]));
+        assert_eq!(locate("#Todo/3"), Some(vec![2]));
     }
 }
@@ -0,0 +1,356 @@
+use std::cmp::Ordering;
+
+use crate::date::Date;
+use crate::error::{CodeComponent::Sort, Error};
+use crate::propagate;
+use crate::todo::document::Document;
+use crate::todo::item::Item;
+use crate::todo::list::List;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Priority,
+    Date,
+    Urgency,
+    Name,
+    Created,
+    Completed,
+    /// The order that the items are written in the file (or that the lists were found in).
+    FileOrder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortRule {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+/// An ordering for items, like `completed,priority:desc`. Each key is only used to break ties
+/// left by the ones before it, and anything still tied stays in file order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortSpec {
+    pub rules: Vec<SortRule>,
+}
+
+impl Default for SortSpec {
+    /// Unfinished items first, and then the most important ones.
+    fn default() -> SortSpec {
+        SortSpec {
+            rules: vec![
+                SortRule {
+                    key: SortKey::Completed,
+                    descending: false,
+                },
+                SortRule {
+                    key: SortKey::Priority,
+                    descending: true,
+                },
+            ],
+        }
+    }
+}
+
+impl SortSpec {
+    pub fn parse(input: &str) -> Result<SortSpec, Error> {
+        let mut rules = vec![];
+
+        for part in input.split(",") {
+            let part = part.trim().to_ascii_lowercase();
+            if part.is_empty() {
+                continue;
+            }
+
+            let (key_name, direction) = match part.split_once(":") {
+                Some((key_name, direction)) => (key_name.trim(), direction.trim()),
+                None => (&part[..], "asc"),
+            };
+
+            let key = match key_name {
+                "priority" => SortKey::Priority,
+                "date" | "due" => SortKey::Date,
+                "urgency" => SortKey::Urgency,
+                "name" => SortKey::Name,
+                "created" => SortKey::Created,
+                "completed" => SortKey::Completed,
+                "file-order" | "file" => SortKey::FileOrder,
+                _ => {
+                    return Err(propagate!(
+                        Sort,
+                        format!(
+                            "Unknown sort key '{key_name}'. Use priority, date, urgency, name, created, completed or file-order."
+                        )
+                    ));
+                }
+            };
+            let descending = match direction {
+                "asc" | "ascending" => false,
+                "desc" | "descending" => true,
+                _ => {
+                    return Err(propagate!(
+                        Sort,
+                        format!(
+                            "The direction of '{key_name}' must be 'asc' or 'desc', not '{direction}'."
+                        )
+                    ));
+                }
+            };
+
+            rules.push(SortRule { key, descending });
+        }
+
+        Ok(SortSpec { rules })
+    }
+
+    /// This formats it for saving, so it can be read back by `SortSpec::parse`.
+    pub fn display(&self) -> String {
+        self.rules
+            .iter()
+            .map(|rule| {
+                let name = match rule.key {
+                    SortKey::Priority => "priority",
+                    SortKey::Date => "date",
+                    SortKey::Urgency => "urgency",
+                    SortKey::Name => "name",
+                    SortKey::Created => "created",
+                    SortKey::Completed => "completed",
+                    SortKey::FileOrder => "file-order",
+                };
+                if rule.descending {
+                    format!("{name}:desc")
+                } else {
+                    name.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    /// Sorts a list and all of the lists below it.
+    pub fn sort_items(&self, items: &mut List) {
+        self.sort_entries(items, |item| item);
+
+        for item in items {
+            self.sort_items(&mut item.items);
+        }
+    }
+
+    /// Sorts anything that holds an item (like the entries of the agenda) by that item. Their
+    /// current order counts as the file order.
+    pub fn sort_entries<T>(&self, entries: &mut Vec<T>, item: impl Fn(&T) -> &Item) {
+        let mut indexed = entries.drain(..).enumerate().collect::<Vec<(usize, T)>>();
+        indexed.sort_by(|(a_index, a), (b_index, b)| {
+            self.compare_items(item(a), *a_index, item(b), *b_index)
+        });
+
+        entries.extend(indexed.into_iter().map(|(_, entry)| entry));
+    }
+
+    /// Sorts documents by the keys that make sense for them (priority, date, name and the order
+    /// that they were found in). Their items are left alone, since each document can have its
+    /// own order.
+    pub fn sort_documents(&self, documents: &mut Vec<Document>) {
+        let mut indexed = documents
+            .drain(..)
+            .enumerate()
+            .collect::<Vec<(usize, Document)>>();
+
+        indexed.sort_by(|(a_index, a), (b_index, b)| {
+            for rule in &self.rules {
+                let ordering = match rule.key {
+                    SortKey::Priority => a.priority.cmp(&b.priority),
+                    SortKey::Date => compare_missing_last(a.date, b.date, rule.descending),
+                    SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                    SortKey::FileOrder => a_index.cmp(b_index),
+                    _ => Ordering::Equal,
+                };
+                let ordering = if rule.descending && rule.key != SortKey::Date {
+                    ordering.reverse()
+                } else {
+                    ordering
+                };
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+
+            Ordering::Equal
+        });
+
+        documents.extend(indexed.into_iter().map(|(_, document)| document));
+    }
+
+    fn compare_items(&self, a: &Item, a_index: usize, b: &Item, b_index: usize) -> Ordering {
+        for rule in &self.rules {
+            let ordering = match rule.key {
+                // Items without a value always go last, whichever way it is sorted.
+                SortKey::Date => compare_missing_last(a.date, b.date, rule.descending),
+                SortKey::Created => compare_missing_last(a.created, b.created, rule.descending),
+                key => {
+                    let ordering = match key {
+                        SortKey::Priority => a.priority.cmp(&b.priority),
+                        SortKey::Urgency => urgency_or_zero(a).cmp(&urgency_or_zero(b)),
+                        SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                        SortKey::Completed => a.completed.cmp(&b.completed),
+                        SortKey::FileOrder => a_index.cmp(&b_index),
+                        SortKey::Date | SortKey::Created => Ordering::Equal,
+                    };
+                    if rule.descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                }
+            };
+
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        Ordering::Equal
+    }
+}
+
+/// Items that aren't close to their date (or don't have one) aren't urgent at all.
+fn urgency_or_zero(item: &Item) -> i64 {
+    item.urgency().ok().flatten().unwrap_or(0)
+}
+
+fn compare_missing_last(a: Option<Date>, b: Option<Date>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let ordering = a.iso().cmp(&b.iso());
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::markdown::ListFormat;
+
+    fn items(lines: &[&str]) -> List {
+        lines
+            .iter()
+            .map(|line| {
+                Item::parse(line, ListFormat::Todo)
+                    .expect("The item should be read.")
+                    .0
+            })
+            .collect()
+    }
+
+    /// The names of the items after sorting them.
+    fn sorted(spec: &str, items: &List) -> Vec<String> {
+        let mut items = items.clone();
+        SortSpec::parse(spec)
+            .expect("The sort should be parsed.")
+            .sort_items(&mut items);
+        items.into_iter().map(|item| item.name).collect()
+    }
+
+    #[test]
+    fn specs_are_read_and_displayed() {
+        let spec = SortSpec::parse(" Completed , priority:DESC,due:asc,, file:descending")
+            .expect("The sort should be parsed.");
+
+        assert_eq!(
+            spec.display(),
+            "completed,priority:desc,date,file-order:desc"
+        );
+        assert_eq!(SortSpec::parse(&spec.display()).ok(), Some(spec));
+        assert!(SortSpec::parse("size").is_err());
+        assert!(SortSpec::parse("priority:up").is_err());
+    }
+
+    #[test]
+    fn later_keys_break_ties() {
+        // Names are sorted without caring about case, so the two As are only told apart by the
+        // other keys.
+        let items = items(&[
+            "- [x] \\5\\ b",
+            "- [ ] \\1\\ c",
+            "- [ ] \\5\\ d",
+            "- [x] \\9\\ A",
+            "- [ ] \\5\\ a",
+        ]);
+
+        assert_eq!(
+            sorted("completed,priority:desc,name", &items),
+            ["a", "d", "c", "A", "b"]
+        );
+        assert_eq!(
+            sorted("priority,name:desc", &items),
+            ["c", "d", "b", "a", "A"]
+        );
+        assert_eq!(sorted("name,completed", &items), ["a", "A", "b", "c", "d"]);
+        assert_eq!(
+            sorted("name,completed:desc", &items),
+            ["A", "a", "b", "c", "d"]
+        );
+        assert_eq!(sorted("file-order:desc", &items), ["a", "A", "d", "c", "b"]);
+    }
+
+    #[test]
+    fn items_without_a_date_go_last_either_way() {
+        let items = items(&[
+            "- [ ] none",
+            "- [ ] \\13-Jun-2026\\ june",
+            "- [ ] \\1-Jan-2026\\ january",
+            "- [ ] also none",
+        ]);
+
+        assert_eq!(
+            sorted("date", &items),
+            ["january", "june", "none", "also none"]
+        );
+        assert_eq!(
+            sorted("date:desc", &items),
+            ["june", "january", "none", "also none"]
+        );
+    }
+
+    #[test]
+    fn ties_stay_in_file_order() {
+        let items = items(&[
+            "- [ ] \\2\\ first",
+            "- [ ] \\1\\ second",
+            "- [ ] \\2\\ third",
+            "- [ ] \\1\\ fourth",
+            "- [ ] \\2\\ fifth",
+        ]);
+
+        assert_eq!(
+            sorted("priority:desc", &items),
+            ["first", "third", "fifth", "second", "fourth"]
+        );
+        assert_eq!(
+            sorted("priority", &items),
+            ["second", "fourth", "first", "third", "fifth"]
+        );
+        assert_eq!(
+            sorted("", &items),
+            ["first", "second", "third", "fourth", "fifth"]
+        );
+    }
+
+    #[test]
+    fn children_are_sorted_too() {
+        let mut list = items(&["- [ ] \\1\\ parent", "- [ ] \\2\\ other"]);
+        list[0].items = items(&["- [ ] \\1\\ low", "- [ ] \\3\\ high"]);
+        SortSpec::default().sort_items(&mut list);
+
+        assert_eq!(list[0].name, "other");
+        assert_eq!(list[1].items[0].name, "high");
+        assert_eq!(list[1].items[1].name, "low");
+    }
+}
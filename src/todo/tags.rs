@@ -0,0 +1,93 @@
+use crate::todo::item::Item;
+
+/// Whether a word from an item's name is a tag, like `+work` (a project) or `@home` (a context).
+/// The sigil has to be followed by a letter, so that things like "+1" stay in the name.
+pub fn is_tag(word: &str) -> bool {
+    let mut characters = word.chars();
+
+    matches!(characters.next(), Some('+') | Some('@'))
+        && characters
+            .next()
+            .is_some_and(|character| character.is_alphabetic())
+        && characters.all(|character| {
+            character.is_alphanumeric() || matches!(character, '-' | '_' | '.' | ':' | '/')
+        })
+}
+
+/// Splits the tags out of an item's name. The name keeps the rest of the words, in order, with the
+/// spacing between them.
+pub fn split_tags(input: &str) -> (String, Vec<String>) {
+    let mut name = String::new();
+    let mut tags: Vec<String> = vec![];
+
+    let mut rest = input.trim();
+    while !rest.is_empty() {
+        let word_start = rest
+            .find(|character: char| !character.is_whitespace())
+            .unwrap_or(rest.len());
+        let (spacing, after) = rest.split_at(word_start);
+        let word_end = after.find(char::is_whitespace).unwrap_or(after.len());
+        let (word, after) = after.split_at(word_end);
+        rest = after;
+
+        if !is_tag(word) {
+            if !name.is_empty() {
+                name += spacing;
+            }
+            name += word;
+        } else if !tags.iter().any(|tag| tag.eq_ignore_ascii_case(word)) {
+            tags.push(word.to_string());
+        }
+    }
+
+    (name, tags)
+}
+
+/// The tags that `--tag` and `--exclude-tag` filter by.
+#[derive(Debug, Clone, Default)]
+pub struct TagFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> TagFilter {
+        TagFilter { include, exclude }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether the item has a tag. Without a `+` or `@`, either kind matches.
+    pub fn has_tag(item: &Item, tag: &str) -> bool {
+        item.tags.iter().any(|item_tag| {
+            if tag.starts_with('+') || tag.starts_with('@') {
+                item_tag.eq_ignore_ascii_case(tag)
+            } else {
+                item_tag[1..].eq_ignore_ascii_case(tag)
+            }
+        })
+    }
+
+    /// Whether the filter would remove the item. Items are kept if they have every included tag,
+    /// or if one of their children does, so that the matching items still show up in context.
+    pub fn removes(&self, item: &Item) -> bool {
+        if self.exclude.iter().any(|tag| TagFilter::has_tag(item, tag)) {
+            return true;
+        }
+
+        !self.includes(item)
+    }
+
+    fn includes(&self, item: &Item) -> bool {
+        self.include.iter().all(|tag| TagFilter::has_tag(item, tag))
+            || item.items.iter().any(|child| {
+                !self
+                    .exclude
+                    .iter()
+                    .any(|tag| TagFilter::has_tag(child, tag))
+                    && self.includes(child)
+            })
+    }
+}
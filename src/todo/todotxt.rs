@@ -0,0 +1,515 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::date::Date;
+use crate::date::recurrence::Recurrence;
+use crate::error::{CodeComponent::TodoTxt, Error};
+use crate::todo::document::Document;
+use crate::todo::item::Item;
+use crate::todo::list::{List, TodoList};
+use crate::todo::tags;
+use crate::{match_error, match_option, propagate, search_paths};
+
+impl Document {
+    /// Formats the items at some indices (or the whole list, if there aren't any) as todo.txt, with
+    /// one line for each item. Nested items are flattened, and every line has a `path:` with where
+    /// the item is in the list (like `path:#Todo/2/1`), so that importing it puts the item back in
+    /// the same place.
+    pub fn to_todotxt(&self, indices: &[usize]) -> Result<String, Error> {
+        let mut path = format!("#{}", self.name);
+        for index in indices {
+            path += &format!("/{}", index + 1);
+        }
+
+        let mut output = String::new();
+        if indices.is_empty() {
+            for (i, item) in self.items.iter().enumerate() {
+                write_item(item, &format!("{path}/{}", i + 1), &mut output);
+            }
+        } else {
+            let item = match_option!(
+                self.items.item_at(indices),
+                TodoTxt,
+                format!("There is no item at {:?}.", indices)
+            );
+            write_item(item, &path, &mut output);
+        }
+
+        Ok(output)
+    }
+
+    /// Create a document from todo.txt. Items with a `path:` (from `to_todotxt`) are put back under
+    /// the item that they were under, and the list is named after the first one. Anything that todo
+    /// doesn't know about, like other `key:value`s, stays in the item's name.
+    pub fn from_todotxt(input: &str, path: PathBuf) -> Result<Document, Error> {
+        let mut name = None;
+        let mut items: List = vec![];
+        // Where the items that were read are, by the positions in their `path:`.
+        let mut placed: HashMap<Vec<usize>, Vec<usize>> = HashMap::new();
+
+        for (i, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (item, item_path) = match_error!(
+                read_line(line),
+                TodoTxt,
+                format!("Could not read line {} ('{}').", i + 1, line)
+            );
+
+            let Some((list_name, positions)) = item_path else {
+                items.push(item);
+                continue;
+            };
+            name.get_or_insert(list_name);
+
+            let parent = positions[..positions.len() - 1].to_vec();
+            let indices = match placed
+                .get(&parent)
+                .and_then(|parent| Some((parent.clone(), items.children_at_mut(parent)?)))
+            {
+                Some((mut indices, siblings)) => {
+                    siblings.push(item);
+                    indices.push(siblings.len() - 1);
+                    indices
+                }
+                None => {
+                    items.push(item);
+                    vec![items.len() - 1]
+                }
+            };
+            placed.insert(positions, indices);
+        }
+
+        Ok(Document {
+            name: name.unwrap_or("Todo".to_string()),
+            path,
+            file_name: search_paths::default_list_file(),
+            priority: 0,
+            date: None,
+            items,
+            archived: false,
+            alias: None,
+            sort: None,
+            header_lines: vec![],
+            preamble: vec![String::new()],
+            final_newline: true,
+            loaded: None,
+            diagnostics: vec![],
+        })
+    }
+}
+
+/// Writes an item's line, and then its children's.
+fn write_item(item: &Item, path: &str, output: &mut String) {
+    let letter = priority_letter(item.priority);
+    let mut words = vec![];
+
+    // Completed items have the date that they were completed where the creation date would be,
+    // which todo doesn't know, so their creation date is written as a `created:` instead.
+    if item.completed {
+        words.push("x".to_string());
+    } else {
+        if let Some(letter) = letter {
+            words.push(format!("({letter})"));
+        }
+        if let Some(created) = item.created {
+            words.push(created.iso());
+        }
+    }
+
+    // Names that had to be changed to be written as words are written exactly as a `name:` too,
+    // since the words aren't decoded when they are read.
+    let text = write_name(&item.name);
+    let exact = (text != item.name).then(|| format!("name:{}", encode(&item.name)));
+    words.push(text);
+    words.extend(item.tags.clone());
+    words.extend(exact);
+
+    if item.priority != 0 && (item.completed || letter.is_none()) {
+        words.push(match letter {
+            Some(letter) => format!("pri:{letter}"),
+            None => format!("pri:{}", item.priority),
+        });
+    }
+    if let Some(date) = item.date {
+        words.push(format!("due:{}", date.iso()));
+    }
+    if item.completed
+        && let Some(created) = item.created
+    {
+        words.push(format!("created:{}", created.iso()));
+    }
+    if let Some(recurrence) = &item.recurrence {
+        words.push(format!("repeat:{}", encode(&recurrence.display())));
+    }
+    if let Some(id) = &item.id {
+        words.push(format!("id:{id}"));
+    }
+    if item.archived {
+        words.push("archived:true".to_string());
+    }
+    if let Some(note) = &item.note {
+        words.push(format!("note:{}", encode(note)));
+    }
+    words.push(format!("path:{}", encode(path)));
+
+    words.retain(|word| !word.is_empty());
+    *output += &format!("{}\n", words.join(" "));
+
+    for (i, child) in item.items.iter().enumerate() {
+        write_item(child, &format!("{path}/{}", i + 1), output);
+    }
+}
+
+/// The list name and the positions of the items from a `path:`, like `#Todo/2/1`.
+type Location = (String, Vec<usize>);
+
+/// Reads one item, and where it was from its `path:`, if it has one.
+fn read_line(line: &str) -> Result<(Item, Option<Location>), Error> {
+    let mut words = line.trim().split(' ').peekable();
+
+    let mut completed = false;
+    let mut priority = 0;
+    let mut created = None;
+    if words.peek() == Some(&"x") {
+        completed = true;
+        words.next();
+        // The date that it was completed, which todo doesn't keep.
+        if words
+            .peek()
+            .is_some_and(|word| Date::from_iso(word).is_ok())
+        {
+            words.next();
+        }
+    }
+    if let Some(letter) = words.peek().and_then(|word| {
+        word.strip_prefix("(")
+            .and_then(|word| word.strip_suffix(")"))
+            .and_then(|letter| letter.parse::<char>().ok())
+            .and_then(letter_priority)
+    }) {
+        priority = letter;
+        words.next();
+    }
+    if let Some(date) = words.peek().and_then(|word| Date::from_iso(word).ok()) {
+        created = Some(date);
+        words.next();
+    }
+
+    let mut date = None;
+    let mut recurrence = None;
+    let mut id = None;
+    let mut archived = false;
+    let mut note = None;
+    let mut item_path = None;
+    let mut exact_name = None;
+    let mut name = vec![];
+    for word in words {
+        let Some((key, value)) = word.split_once(":") else {
+            name.push(word);
+            continue;
+        };
+
+        match key {
+            "pri" => {
+                priority = match_option!(
+                    value
+                        .parse::<char>()
+                        .ok()
+                        .and_then(letter_priority)
+                        .or(value.parse::<i64>().ok()),
+                    TodoTxt,
+                    format!("'{value}' isn't a priority, like 'A' or '3'.")
+                )
+            }
+            "due" => {
+                date = Some(match_error!(
+                    Date::from_iso(value),
+                    TodoTxt,
+                    format!("Due dates must be YYYY-MM-DD. Got '{value}'.")
+                ))
+            }
+            "created" => {
+                created = Some(match_error!(
+                    Date::from_iso(value),
+                    TodoTxt,
+                    format!("Creation dates must be YYYY-MM-DD. Got '{value}'.")
+                ))
+            }
+            "repeat" => {
+                recurrence = Some(match_error!(
+                    Recurrence::parse(&decode(value)),
+                    TodoTxt,
+                    format!("Could not read the repeating rule '{}'.", decode(value))
+                ))
+            }
+            "id" => id = Some(value.to_string()),
+            "archived" => archived = value == "true",
+            "note" => note = Some(decode(value)),
+            "path" => item_path = Some(read_path(&decode(value))?),
+            "name" => exact_name = Some(decode(value)),
+            _ => name.push(word),
+        }
+    }
+
+    // The words are the name as it is, since other todo.txt tools don't encode anything, unless
+    // todo wrote the name exactly as a `name:`.
+    let (name, tags) = tags::split_tags(&name.join(" "));
+    let item = Item {
+        completed: completed || archived,
+        archived,
+        priority,
+        date,
+        created,
+        recurrence,
+        name: exact_name.unwrap_or(name),
+        tags,
+        id,
+        note,
+        items: vec![],
+        trailing: vec![],
+        source: None,
+    };
+
+    Ok((item, item_path))
+}
+
+/// Reads a path that `to_todotxt` wrote, like `#Todo/2/1`.
+fn read_path(path: &str) -> Result<Location, Error> {
+    let problem = || {
+        propagate!(
+            TodoTxt,
+            format!("'{path}' isn't a path like '#Todo/2/1', with the positions of the items.")
+        )
+    };
+
+    let (list_name, positions) = path
+        .strip_prefix("#")
+        .and_then(|path| path.split_once("/"))
+        .ok_or_else(problem)?;
+    let positions = positions
+        .split("/")
+        .map(|position| {
+            position
+                .parse::<usize>()
+                .ok()
+                .filter(|position| *position > 0)
+        })
+        .collect::<Option<Vec<usize>>>()
+        .ok_or_else(problem)?;
+
+    Ok((list_name.to_string(), positions))
+}
+
+/// todo.txt priorities go from `(A)`, which is the most important, to `(Z)`. Only `(A)` to `(E)`
+/// are used for todo's priorities, as 5 to 1, and other priorities are written as `pri:N` instead.
+/// When reading, `(F)` to `(Z)` are -1 to -21, so that they still come after `(E)`.
+fn priority_letter(priority: i64) -> Option<char> {
+    (1..=5)
+        .contains(&priority)
+        .then(|| (b'A' + (5 - priority) as u8) as char)
+}
+
+fn letter_priority(letter: char) -> Option<i64> {
+    match letter {
+        'A'..='E' => Some(5 - (letter as u8 - b'A') as i64),
+        'F'..='Z' => Some(-((letter as u8 - b'F') as i64) - 1),
+        _ => None,
+    }
+}
+
+/// The `key:value`s that are read from a line, so words in names that look like them are encoded.
+const KEYS: &[&str] = &[
+    "pri", "due", "created", "repeat", "id", "archived", "note", "path", "name",
+];
+
+/// Writes a name as it is, except for what would be read as something else. Whitespace (other than
+/// single spaces between words) and control characters are written like `encode` does, and so is
+/// the first character of words that would be read as tags or `key:value`s, or as a completion
+/// mark, priority or date at the start of the name.
+fn write_name(name: &str) -> String {
+    let characters = name.chars().collect::<Vec<char>>();
+    let mut output = String::new();
+    for (i, character) in characters.iter().enumerate() {
+        let single_space = *character == ' '
+            && i > 0
+            && characters.get(i + 1).is_some_and(|next| *next != ' ')
+            && characters[i - 1] != ' ';
+
+        if single_space {
+            output.push(' ');
+        } else if character.is_whitespace() || character.is_control() {
+            output += &encode_character(*character);
+        } else {
+            output.push(*character);
+        }
+    }
+
+    output
+        .split(' ')
+        .enumerate()
+        .map(|(i, word)| {
+            let key = word.split_once(':').map(|(key, _)| key);
+            let read_as_something_else = tags::is_tag(word)
+                || key.is_some_and(|key| KEYS.contains(&key))
+                || (i == 0
+                    && (word == "x"
+                        || Date::from_iso(word).is_ok()
+                        || word
+                            .strip_prefix('(')
+                            .and_then(|word| word.strip_suffix(')'))
+                            .and_then(|letter| letter.parse::<char>().ok())
+                            .is_some_and(|letter| letter_priority(letter).is_some())));
+            match word.chars().next() {
+                Some(first) if read_as_something_else => {
+                    format!("{}{}", encode_character(first), &word[first.len_utf8()..])
+                }
+                _ => word.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Writes a value so that it is one word, by writing `%`, whitespace and control characters as
+/// their UTF-8 bytes, like `%20` for a space.
+fn encode(value: &str) -> String {
+    let mut output = String::new();
+    for character in value.chars() {
+        if character == '%' || character.is_whitespace() || character.is_control() {
+            output += &encode_character(character);
+        } else {
+            output.push(character);
+        }
+    }
+    output
+}
+
+/// Writes a character as its UTF-8 bytes, like `%20` for a space.
+fn encode_character(character: char) -> String {
+    character
+        .to_string()
+        .bytes()
+        .map(|byte| format!("%{byte:02X}"))
+        .collect()
+}
+
+/// Reads a value that was written by `encode`. A `%` that isn't followed by two hex digits is kept.
+fn decode(value: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = value.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        let code = after
+            .get(..2)
+            .and_then(|code| std::str::from_utf8(code).ok())
+            .and_then(|code| u8::from_str_radix(code, 16).ok());
+        match code {
+            Some(code) if byte == b'%' => {
+                bytes.push(code);
+                rest = &after[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(input: &str) -> Document {
+        Document::from_todotxt(input, PathBuf::from("/tmp")).expect("The todo.txt should be read.")
+    }
+
+    #[test]
+    fn priorities_are_anchored_at_a() {
+        let document = import("(A) a\n(E) e\n(F) f\n(Z) z\nx b pri:B\nc pri:9\nd\n");
+        let priorities = document
+            .items
+            .iter()
+            .map(|item| item.priority)
+            .collect::<Vec<i64>>();
+        assert_eq!(priorities, [5, 1, -1, -21, 4, 9, 0]);
+
+        let output = document
+            .to_todotxt(&[])
+            .expect("The list should be exported.");
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert!(lines[0].starts_with("(A) a "), "{output}");
+        assert!(lines[1].starts_with("(E) e "), "{output}");
+        assert!(lines[2].starts_with("f pri:-1 "), "{output}");
+        assert!(lines[3].starts_with("z pri:-21 "), "{output}");
+        assert!(lines[4].starts_with("x b pri:B "), "{output}");
+        assert!(lines[5].starts_with("c pri:9 "), "{output}");
+        assert!(lines[6].starts_with("d path:"), "{output}");
+
+        let reimported = import(&output);
+        for (item, reimported) in document.items.iter().zip(&reimported.items) {
+            assert_eq!(item.priority, reimported.priority);
+        }
+    }
+
+    #[test]
+    fn names_from_other_tools_are_not_decoded() {
+        let document = import("Get 50%OFF at %20 the shop +errands\n");
+        assert_eq!(document.items[0].name, "Get 50%OFF at %20 the shop");
+        assert_eq!(document.items[0].tags, ["+errands"]);
+
+        let output = document
+            .to_todotxt(&[])
+            .expect("The list should be exported.");
+        assert_eq!(output, "Get 50%OFF at %20 the shop +errands path:#Todo/1\n");
+        assert_eq!(import(&output).items[0].name, "Get 50%OFF at %20 the shop");
+    }
+
+    #[test]
+    fn names_are_kept() {
+        for name in [
+            "C:\\temp cleanup",
+            "\\escaped\\ name\\",
+            "  spaced  out ",
+            "tab\there",
+            "line\nbreak",
+            "+tag and @ctx look like tags",
+            "due:tomorrow and pri:A",
+            "url:https://example.com",
+            "x",
+            "(A) first",
+            "2026-10-18",
+            "42",
+            "100% done",
+            "%41 isn't A",
+            "🐟 fish",
+        ] {
+            let mut document = import("placeholder\n");
+            document.items[0].name = name.to_string();
+
+            let output = document
+                .to_todotxt(&[])
+                .expect("The list should be exported.");
+            assert_eq!(import(&output).items[0].name, name, "{output}");
+        }
+
+        let output = import("C:\\temp cleanup\n")
+            .to_todotxt(&[])
+            .expect("The list should be exported.");
+        assert!(output.starts_with("C:\\temp cleanup path:"), "{output}");
+
+        // Other tools see the name that todo wrote, but todo reads the exact one.
+        let mut document = import("placeholder\n");
+        document.items[0].name = "+work in the  garden".to_string();
+        let output = document
+            .to_todotxt(&[])
+            .expect("The list should be exported.");
+        assert_eq!(
+            output,
+            "%2Bwork in the%20%20garden name:+work%20in%20the%20%20garden path:#Todo/1\n"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use crossterm::event::{self, Event, KeyEventKind};
+use crossterm::{cursor, execute, queue, style, terminal};
+use std::io::{Stdout, Write, stdout};
+
+use crate::error::{CodeComponent, Error};
+use crate::output::Render;
+use crate::output::RenderFormat;
+use crate::todo::document::Document;
+use crate::{match_error, match_result};
+
+mod state;
+
+use state::{App, Mode};
+
+/// Puts the terminal into the state that the editor needs, and puts it back once it is dropped, so
+/// that the user's terminal is restored even if something goes wrong.
+struct Terminal {
+    out: Stdout,
+}
+
+impl Terminal {
+    fn enter() -> Result<Terminal, Error> {
+        let mut out = stdout();
+
+        match_result!(
+            terminal::enable_raw_mode(),
+            CodeComponent::Tui,
+            format!("Could not put the terminal into raw mode.")
+        );
+        match_result!(
+            execute!(
+                out,
+                terminal::EnterAlternateScreen,
+                terminal::DisableLineWrap,
+                cursor::Hide
+            ),
+            CodeComponent::Tui,
+            format!("Could not set up the terminal.")
+        );
+
+        Ok(Terminal { out })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // There isn't much that we can do if this fails.
+        let _ = execute!(
+            self.out,
+            cursor::Show,
+            terminal::EnableLineWrap,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Open the editor on a set of documents. This blocks until the user quits.
+pub fn run(documents: Vec<Document>) -> Result<(), Error> {
+    let mut app = App::new(documents);
+    let mut terminal = match_error!(
+        Terminal::enter(),
+        CodeComponent::Tui,
+        format!("Could not open the editor.")
+    );
+
+    loop {
+        match_error!(
+            draw(&mut app, &mut terminal.out),
+            CodeComponent::Tui,
+            format!("Could not draw the editor.")
+        );
+
+        let event = match_result!(
+            event::read(),
+            CodeComponent::Tui,
+            format!("Could not read input from the terminal.")
+        );
+
+        if let Event::Key(key) = event {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if !app.handle_key(key) {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn draw(app: &mut App, out: &mut Stdout) -> Result<(), Error> {
+    let (width, height) = match_result!(
+        terminal::size(),
+        CodeComponent::Tui,
+        format!("Could not get the size of the terminal.")
+    );
+    // Leave space for the status and help lines at the bottom.
+    let list_height = usize::from(height).saturating_sub(2).max(1);
+
+    let rows = app.rows();
+    app.scroll_to_cursor(list_height);
+
+    let mut screen = vec![];
+    for (i, row) in rows.iter().enumerate().skip(app.scroll).take(list_height) {
+        let line = match_error!(
+            app.format_row(row),
+            CodeComponent::Tui,
+            format!("Could not format row {}.", i)
+        );
+        let marker = if i == app.cursor { "▶ " } else { "  " };
+        screen.push(format!("{}{}", marker, line.render(&RenderFormat::ANSI)));
+    }
+
+    match_result!(
+        queue!(out, cursor::Hide),
+        CodeComponent::Tui,
+        format!("Could not hide the cursor.")
+    );
+    for y in 0..list_height {
+        match_result!(
+            queue!(
+                out,
+                cursor::MoveTo(0, y as u16),
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                style::Print(screen.get(y).map(|a| a.as_str()).unwrap_or(""))
+            ),
+            CodeComponent::Tui,
+            format!("Could not draw line {}.", y)
+        );
+    }
+
+    let status = app.status();
+    let help = app.help();
+    match_result!(
+        queue!(
+            out,
+            cursor::MoveTo(0, height.saturating_sub(2)),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            style::Print(status.render(&RenderFormat::ANSI)),
+            cursor::MoveTo(0, height.saturating_sub(1)),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            style::Print(help.render(&RenderFormat::ANSI))
+        ),
+        CodeComponent::Tui,
+        format!("Could not draw the status line.")
+    );
+
+    // Put the terminal's cursor where the user is typing
+    if let Mode::Edit { prompt, cursor, .. } = &app.mode {
+        let column = (prompt.chars().count() + 2 + cursor).min(usize::from(width));
+        match_result!(
+            queue!(
+                out,
+                cursor::MoveTo(column as u16, height.saturating_sub(2)),
+                cursor::Show
+            ),
+            CodeComponent::Tui,
+            format!("Could not move the cursor.")
+        );
+    }
+
+    match_result!(
+        out.flush(),
+        CodeComponent::Tui,
+        format!("Could not write to the terminal.")
+    );
+
+    Ok(())
+}
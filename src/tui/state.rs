@@ -0,0 +1,633 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashSet;
+
+use crate::date::Date;
+use crate::error::{CodeComponent, Error};
+use crate::match_error;
+use crate::output::color::Color;
+use crate::output::line::OutputLine;
+use crate::output::segment::OutputSegment;
+use crate::output::style::Style;
+use crate::storage;
+use crate::todo::document::Document;
+use crate::todo::item::Item;
+use crate::todo::list::{List, TodoList};
+use crate::todo::tags;
+
+/// A single line of the editor. Items are addressed by the index of their document, and the
+/// indices that lead to them through the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Row {
+    Document(usize),
+    Item(usize, Vec<usize>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Name,
+    Priority,
+    Date,
+    NewItem,
+}
+
+#[derive(Debug, Clone)]
+pub enum Mode {
+    Browse,
+    Edit {
+        field: Field,
+        prompt: String,
+        buffer: String,
+        cursor: usize,
+    },
+    Move {
+        document: usize,
+        indices: Vec<usize>,
+    },
+}
+
+pub struct App {
+    documents: Vec<Document>,
+    dirty: Vec<bool>,
+    collapsed: HashSet<(usize, Vec<usize>)>,
+    pub cursor: usize,
+    pub scroll: usize,
+    pub mode: Mode,
+    message: Option<String>,
+    confirm_quit: bool,
+}
+
+impl App {
+    pub fn new(documents: Vec<Document>) -> App {
+        App {
+            dirty: vec![false; documents.len()],
+            documents,
+            collapsed: HashSet::new(),
+            cursor: 0,
+            scroll: 0,
+            mode: Mode::Browse,
+            message: None,
+            confirm_quit: false,
+        }
+    }
+
+    /// All of the rows that are currently visible, in the order that they are drawn.
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![];
+
+        for (i, document) in self.documents.iter().enumerate() {
+            rows.push(Row::Document(i));
+            self.push_item_rows(&mut rows, i, &document.items, vec![]);
+        }
+
+        rows
+    }
+
+    fn push_item_rows(&self, rows: &mut Vec<Row>, document: usize, items: &List, path: Vec<usize>) {
+        for (i, item) in items.iter().enumerate() {
+            let mut item_path = path.clone();
+            item_path.push(i);
+
+            rows.push(Row::Item(document, item_path.clone()));
+
+            if !self.collapsed.contains(&(document, item_path.clone())) {
+                self.push_item_rows(rows, document, &item.items, item_path);
+            }
+        }
+    }
+
+    pub fn scroll_to_cursor(&mut self, height: usize) {
+        let row_count = self.rows().len();
+        if self.cursor >= row_count {
+            self.cursor = row_count.saturating_sub(1);
+        }
+
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor + 1 - height;
+        }
+    }
+
+    pub fn format_row(&self, row: &Row) -> Result<OutputLine, Error> {
+        match row {
+            Row::Document(i) => {
+                let mut line = self.documents[*i].format_title();
+                if self.dirty[*i] {
+                    line.add(OutputSegment::new(
+                        " [modified]",
+                        Color::Yellow,
+                        Style::normal(),
+                    ));
+                }
+                Ok(line)
+            }
+            Row::Item(document, indices) => {
+                let items = &self.documents[*document].items;
+
+                // Work out which tree lines need to be drawn, the same way `List::format` does.
+                let mut lines = vec![];
+                let mut siblings = items;
+                for index in &indices[..indices.len() - 1] {
+                    lines.push(*index >= siblings.len() - 1);
+                    siblings = &siblings[*index].items;
+                }
+                let last = indices[indices.len() - 1];
+                let item = &siblings[last];
+
+                let mut line = match_error!(
+                    item.format_line(last >= siblings.len() - 1, lines),
+                    CodeComponent::Tui,
+                    format!("Could not format the item '{}'.", item.name)
+                );
+
+                if !item.items.is_empty() && self.collapsed.contains(&(*document, indices.clone()))
+                {
+                    line.add(OutputSegment::new(
+                        &format!(" [+{}]", item.items.len()),
+                        Color::Default,
+                        *Style::new().dim(),
+                    ));
+                }
+
+                Ok(line)
+            }
+        }
+    }
+
+    pub fn status(&self) -> OutputLine {
+        let mut line = OutputLine::new();
+
+        match &self.mode {
+            Mode::Edit { prompt, buffer, .. } => {
+                line.add(OutputSegment::new(
+                    &format!("{}: ", prompt),
+                    Color::Cyan,
+                    *Style::new().bright(),
+                ));
+                line.add(OutputSegment::new(buffer, Color::Default, Style::normal()));
+            }
+            _ => {
+                if let Some(message) = &self.message {
+                    line.add(OutputSegment::new(message, Color::Yellow, Style::normal()));
+                }
+            }
+        }
+
+        line
+    }
+
+    pub fn help(&self) -> OutputLine {
+        let text = match self.mode {
+            Mode::Browse => {
+                "↑↓ move  ←→ collapse/expand  space toggle  e name  p priority  D date  a add  m move  s save  q quit"
+            }
+            Mode::Edit { .. } => "enter confirm  esc cancel",
+            Mode::Move { .. } => "↑↓ choose a destination  m/enter move here  esc cancel",
+        };
+
+        OutputLine::new()
+            .add(OutputSegment::new(
+                text,
+                Color::Default,
+                *Style::new().dim(),
+            ))
+            .clone()
+    }
+
+    /// Handle a key press. Returns `false` once the editor should close.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match self.mode.clone() {
+            Mode::Browse => return self.handle_browse_key(key),
+            Mode::Edit { .. } => self.handle_edit_key(key),
+            Mode::Move { document, indices } => self.handle_move_key(key, document, indices),
+        }
+
+        true
+    }
+
+    fn handle_browse_key(&mut self, key: KeyEvent) -> bool {
+        if key.code != KeyCode::Char('q') {
+            self.confirm_quit = false;
+        }
+        self.message = None;
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('c') => return false,
+                KeyCode::Char('s') => self.save(),
+                _ => {}
+            }
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if self.dirty.contains(&true) && !self.confirm_quit {
+                    self.confirm_quit = true;
+                    self.message = Some(
+                        "There are unsaved changes. Press q again to quit without saving, or s to save."
+                            .to_string(),
+                    );
+                } else {
+                    return false;
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-10),
+            KeyCode::PageDown => self.move_cursor(10),
+            KeyCode::Home | KeyCode::Char('g') => self.cursor = 0,
+            KeyCode::End | KeyCode::Char('G') => self.cursor = self.rows().len().saturating_sub(1),
+            KeyCode::Left | KeyCode::Char('h') => self.collapse(),
+            KeyCode::Right | KeyCode::Char('l') => self.expand(),
+            KeyCode::Enter => self.toggle_collapsed(),
+            KeyCode::Char(' ') | KeyCode::Char('x') => self.toggle_completed(),
+            KeyCode::Char('e') => self.start_edit(Field::Name),
+            KeyCode::Char('p') => self.start_edit(Field::Priority),
+            KeyCode::Char('D') => self.start_edit(Field::Date),
+            KeyCode::Char('a') => self.start_edit(Field::NewItem),
+            KeyCode::Char('m') => self.start_move(),
+            KeyCode::Char('s') => self.save(),
+            _ => {}
+        }
+
+        true
+    }
+
+    fn handle_edit_key(&mut self, key: KeyEvent) {
+        let Mode::Edit {
+            field,
+            prompt,
+            mut buffer,
+            mut cursor,
+        } = self.mode.clone()
+        else {
+            return;
+        };
+
+        // The cursor counts characters, but strings are indexed by bytes.
+        let byte_index = |buffer: &String, cursor: usize| {
+            buffer
+                .char_indices()
+                .nth(cursor)
+                .map(|(i, _)| i)
+                .unwrap_or(buffer.len())
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Browse;
+                return;
+            }
+            KeyCode::Enter => {
+                self.mode = Mode::Browse;
+                self.commit_edit(field, buffer);
+                return;
+            }
+            KeyCode::Left => cursor = cursor.saturating_sub(1),
+            KeyCode::Right => cursor = (cursor + 1).min(buffer.chars().count()),
+            KeyCode::Home => cursor = 0,
+            KeyCode::End => cursor = buffer.chars().count(),
+            KeyCode::Backspace if cursor > 0 => {
+                cursor -= 1;
+                buffer.remove(byte_index(&buffer, cursor));
+            }
+            KeyCode::Delete if cursor < buffer.chars().count() => {
+                buffer.remove(byte_index(&buffer, cursor));
+            }
+            KeyCode::Char(character) => {
+                buffer.insert(byte_index(&buffer, cursor), character);
+                cursor += 1;
+            }
+            _ => {}
+        }
+
+        self.mode = Mode::Edit {
+            field,
+            prompt,
+            buffer,
+            cursor,
+        };
+    }
+
+    fn handle_move_key(&mut self, key: KeyEvent, document: usize, indices: Vec<usize>) {
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Browse;
+                self.message = Some("Cancelled the move.".to_string());
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::Left | KeyCode::Char('h') => self.collapse(),
+            KeyCode::Right | KeyCode::Char('l') => self.expand(),
+            KeyCode::Char('m') | KeyCode::Enter => {
+                self.mode = Mode::Browse;
+                self.finish_move(document, indices);
+            }
+            _ => {}
+        }
+    }
+
+    fn selected(&self) -> Option<Row> {
+        self.rows().get(self.cursor).cloned()
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let row_count = self.rows().len();
+        self.cursor = self
+            .cursor
+            .saturating_add_signed(delta)
+            .min(row_count.saturating_sub(1));
+    }
+
+    fn collapse(&mut self) {
+        if let Some(Row::Item(document, indices)) = self.selected() {
+            let has_children = self.documents[document]
+                .items
+                .item_at(&indices)
+                .map(|item| !item.items.is_empty())
+                .unwrap_or(false);
+
+            if has_children && !self.collapsed.contains(&(document, indices.clone())) {
+                self.collapsed.insert((document, indices));
+            } else {
+                // Jump to the parent, like most tree views do.
+                let parent = if indices.len() > 1 {
+                    Row::Item(document, indices[..indices.len() - 1].to_vec())
+                } else {
+                    Row::Document(document)
+                };
+                if let Some(position) = self.rows().iter().position(|row| *row == parent) {
+                    self.cursor = position;
+                }
+            }
+        }
+    }
+
+    fn expand(&mut self) {
+        if let Some(Row::Item(document, indices)) = self.selected() {
+            self.collapsed.remove(&(document, indices));
+        }
+    }
+
+    fn toggle_collapsed(&mut self) {
+        if let Some(Row::Item(document, indices)) = self.selected()
+            && !self.collapsed.remove(&(document, indices.clone()))
+        {
+            self.collapsed.insert((document, indices));
+        }
+    }
+
+    fn toggle_completed(&mut self) {
+        let Some(Row::Item(document, indices)) = self.selected() else {
+            return;
+        };
+        let items = &mut self.documents[document].items;
+
+        match items.item_at_mut(&indices) {
+            Some(item) if item.completed => item.completed = false,
+            Some(_) => match items.complete_at(&indices) {
+                Ok(Some(_)) => {
+                    if let Some(item) = items.item_at(&indices) {
+                        self.message = Some(format!(
+                            "'{}' is due again {}.",
+                            item.name,
+                            item.date.map(|date| date.pretty()).unwrap_or_default()
+                        ));
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    self.message = Some(err.message);
+                    return;
+                }
+            },
+            None => return,
+        }
+        self.dirty[document] = true;
+    }
+
+    fn start_edit(&mut self, field: Field) {
+        let selected = match self.selected() {
+            Some(row) => row,
+            _ => return,
+        };
+
+        let (prompt, value) = match (&selected, field) {
+            (_, Field::NewItem) => ("New item", String::new()),
+            (Row::Document(_), _) => {
+                self.message = Some("Select an item to edit it.".to_string());
+                return;
+            }
+            (Row::Item(document, indices), _) => {
+                let item = match self.documents[*document].items.item_at(indices) {
+                    Some(item) => item,
+                    _ => return,
+                };
+                match field {
+                    Field::Name => ("Name", item.full_name()),
+                    Field::Priority => ("Priority", item.priority.to_string()),
+                    Field::Date => (
+                        "Date",
+                        item.date.map(|date| date.pretty()).unwrap_or_default(),
+                    ),
+                    Field::NewItem => unreachable!(),
+                }
+            }
+        };
+
+        self.mode = Mode::Edit {
+            field,
+            prompt: prompt.to_string(),
+            cursor: value.chars().count(),
+            buffer: value,
+        };
+    }
+
+    fn commit_edit(&mut self, field: Field, value: String) {
+        let selected = match self.selected() {
+            Some(row) => row,
+            _ => return,
+        };
+
+        if field == Field::NewItem {
+            if value.trim().is_empty() {
+                return;
+            }
+
+            let (document, indices) = match selected {
+                Row::Document(document) => (document, vec![]),
+                Row::Item(document, indices) => (document, indices),
+            };
+            let id = Item::generate_id(&self.documents[document].items);
+            let siblings = match self.documents[document].items.children_at_mut(&indices) {
+                Some(items) => items,
+                _ => return,
+            };
+            let (name, tags) = tags::split_tags(&value);
+            siblings.push(Item {
+                name,
+                date: None,
+                priority: 0,
+                created: Date::today().ok(),
+                recurrence: None,
+                tags,
+                id: Some(id),
+                completed: false,
+                archived: false,
+                items: vec![],
+                note: None,
+                trailing: vec![],
+                source: None,
+            });
+
+            let mut new_indices = indices.clone();
+            new_indices.push(siblings.len() - 1);
+
+            self.dirty[document] = true;
+            self.collapsed.remove(&(document, indices));
+            if let Some(position) = self
+                .rows()
+                .iter()
+                .position(|row| *row == Row::Item(document, new_indices.clone()))
+            {
+                self.cursor = position;
+            }
+            return;
+        }
+
+        let Row::Item(document, indices) = selected else {
+            return;
+        };
+        let item = match self.documents[document].items.item_at_mut(&indices) {
+            Some(item) => item,
+            _ => return,
+        };
+
+        match field {
+            Field::Name => {
+                if value.trim().is_empty() {
+                    self.message = Some("Items need a name.".to_string());
+                    return;
+                }
+                (item.name, item.tags) = tags::split_tags(&value);
+            }
+            Field::Priority => match value.trim().parse::<i64>() {
+                Ok(priority) => item.priority = priority,
+                Err(_) => {
+                    self.message = Some(format!("'{}' is not a valid priority.", value));
+                    return;
+                }
+            },
+            Field::Date => {
+                if value.trim().is_empty() {
+                    item.date = None;
+                } else {
+                    match Date::from(value.trim()) {
+                        Ok(date) => item.date = Some(date),
+                        Err(_) => {
+                            self.message =
+                                Some(format!("Could not understand the date '{}'.", value));
+                            return;
+                        }
+                    }
+                }
+            }
+            Field::NewItem => {}
+        }
+
+        self.dirty[document] = true;
+    }
+
+    fn start_move(&mut self) {
+        match self.selected() {
+            Some(Row::Item(document, indices)) => {
+                let name = self.documents[document]
+                    .items
+                    .item_at(&indices)
+                    .map(|item| item.name.clone())
+                    .unwrap_or_default();
+                self.message = Some(format!("Moving '{}'.", name));
+                self.mode = Mode::Move { document, indices };
+            }
+            _ => self.message = Some("Select an item to move it.".to_string()),
+        }
+    }
+
+    fn finish_move(&mut self, from_document: usize, from_indices: Vec<usize>) {
+        let (to_document, mut to_indices) = match self.selected() {
+            Some(Row::Document(document)) => (document, vec![]),
+            Some(Row::Item(document, indices)) => (document, indices),
+            _ => return,
+        };
+
+        if from_document == to_document && to_indices.starts_with(&from_indices) {
+            self.message = Some("An item can't be moved inside of itself.".to_string());
+            return;
+        }
+
+        let depth = from_indices.len() - 1;
+        let Some(item) = self.documents[from_document].remove_at(&from_indices) else {
+            return;
+        };
+
+        // Removing the item shifts its later siblings (and their children) up by one.
+        if from_document == to_document
+            && to_indices.len() > depth
+            && to_indices[..depth] == from_indices[..depth]
+            && to_indices[depth] > from_indices[depth]
+        {
+            to_indices[depth] -= 1;
+        }
+
+        let name = item.name.clone();
+        match self.documents[to_document]
+            .items
+            .children_at_mut(&to_indices)
+        {
+            Some(siblings) => siblings.push(item),
+            _ => return,
+        }
+
+        // The indices have changed, so the old collapsed state doesn't point to the same things.
+        self.collapsed.clear();
+        self.dirty[from_document] = true;
+        self.dirty[to_document] = true;
+        self.message = Some(format!(
+            "Moved '{}' to #{}.",
+            name, self.documents[to_document].name
+        ));
+    }
+
+    fn save(&mut self) {
+        // A diff can't be shown over the TUI, so nothing is saved at all.
+        if storage::is_dry_run() {
+            self.message = Some("This is a dry run, so nothing was saved.".to_string());
+            return;
+        }
+
+        let mut saved = 0;
+
+        for (i, document) in self.documents.iter_mut().enumerate() {
+            if !self.dirty[i] {
+                continue;
+            }
+
+            if let Err(err) = document.save() {
+                self.message = Some(format!(
+                    "Could not save #{}: {}",
+                    document.name, err.message
+                ));
+                return;
+            }
+            self.dirty[i] = false;
+            saved += 1;
+        }
+
+        self.message = Some(format!(
+            "Saved {} list{}.",
+            saved,
+            if saved == 1 { "" } else { "s" }
+        ));
+    }
+}